@@ -1,4 +1,4 @@
-use clap::{CommandFactory, Parser, Subcommand};
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::{generate_to, Shell};
 use clap_mangen::Man;
 use std::fs;
@@ -8,8 +8,16 @@ use std::path::PathBuf;
 #[derive(Parser)]
 #[command(name = "syo")]
 #[command(version)]
-#[command(about = "Clipboard manager with 12-hour history")]
+#[command(about = "Clipboard manager with configurable history retention")]
 struct Cli {
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<String>,
+    #[arg(long, global = true, value_name = "DIR")]
+    data_dir: Option<String>,
+    #[arg(long, global = true, value_name = "FILE")]
+    db: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -22,6 +30,16 @@ enum Commands {
     List {
         #[arg(short, long, default_value = "20")]
         limit: usize,
+        #[arg(short, long)]
+        tag: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = Sort::Recent)]
+        sort: Sort,
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long)]
+        until: Option<String>,
+        #[arg(short, long, default_value = "1")]
+        page: u32,
     },
     Get {
         id: i64,
@@ -31,8 +49,200 @@ enum Commands {
         #[arg(short, long, default_value = "20")]
         limit: usize,
     },
-    Clear,
+    Edit {
+        id: i64,
+        #[arg(long)]
+        new: bool,
+        #[arg(short, long)]
+        copy: bool,
+    },
+    Revisions {
+        id: i64,
+    },
+    Pin {
+        id: i64,
+    },
+    Unpin {
+        id: i64,
+    },
+    Tag {
+        id: i64,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    Untag {
+        id: i64,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    #[command(subcommand)]
+    Collection(CollectionCommand),
+    Export {
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long = "type", value_enum)]
+        content_type: Option<EntryType>,
+        #[arg(long)]
+        pinned: bool,
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
+    Import {
+        file: PathBuf,
+        #[arg(long, value_enum)]
+        from: Option<ImportFrom>,
+    },
+    #[command(group = ArgGroup::new("target").required(true).multiple(true))]
+    Rm {
+        #[arg(group = "target", conflicts_with_all = ["content_type", "older_than", "query"])]
+        ids: Vec<i64>,
+        #[arg(long = "type", value_enum, group = "target")]
+        content_type: Option<EntryType>,
+        #[arg(long, group = "target")]
+        older_than: Option<String>,
+        #[arg(long = "match", group = "target")]
+        query: Option<String>,
+        #[arg(short, long)]
+        yes: bool,
+    },
+    Clear {
+        #[arg(long)]
+        all: bool,
+        #[arg(long)]
+        shred: bool,
+    },
+    Undo,
+    Restore {
+        id: i64,
+    },
+    #[command(subcommand)]
+    Trash(TrashCommand),
+    #[command(subcommand)]
+    Reg(RegCommand),
+    #[command(subcommand)]
+    Queue(QueueCommand),
+    #[command(subcommand)]
+    Collect(CollectCommand),
+    Merge {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+        #[arg(long, default_value = "newline")]
+        sep: String,
+    },
+    #[command(subcommand)]
+    Snippet(SnippetCommand),
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+    Doctor,
+    Stats {
+        #[arg(long, conflicts_with = "histogram")]
+        json: bool,
+        #[arg(long)]
+        histogram: bool,
+        #[arg(long, default_value = "5")]
+        top: usize,
+    },
     Popup,
+    Unlock,
+    Lock,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sort {
+    Recent,
+    Frecency,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EntryType {
+    Text,
+    Link,
+    Image,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ImportFrom {
+    Cliphist,
+    Clipman,
+    Copyq,
+    Greenclip,
+}
+
+#[derive(Subcommand)]
+enum CollectionCommand {
+    Ls,
+    Keep {
+        name: String,
+        #[arg(long)]
+        off: bool,
+    },
+    Export {
+        name: String,
+    },
+    Import {
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    List,
+    Empty,
+}
+
+#[derive(Subcommand)]
+enum RegCommand {
+    Set { name: char, source: Option<String> },
+    Get { name: char },
+    Ls,
+    Rm { name: char },
+}
+
+#[derive(Subcommand)]
+enum QueueCommand {
+    Start {
+        #[arg(long)]
+        lifo: bool,
+    },
+    Stop,
+    Next,
+    Status,
+}
+
+#[derive(Subcommand)]
+enum CollectCommand {
+    Start {
+        #[arg(long, default_value = "newline")]
+        sep: String,
+    },
+    Stop,
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    Ls,
+    Use { name: String },
+}
+
+#[derive(Subcommand)]
+enum SnippetCommand {
+    Add {
+        name: String,
+        text: Option<String>,
+    },
+    Ls,
+    Rm {
+        name: String,
+    },
+    Edit {
+        name: String,
+    },
+    Get {
+        name: String,
+        #[arg(short, long)]
+        print: bool,
+    },
 }
 
 fn main() {
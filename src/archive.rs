@@ -0,0 +1,292 @@
+use crate::entry::{ContentType, Entry};
+use crate::error::{Result, StickyError};
+use crate::storage::HistoryStore;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+pub const ARCHIVE_FORMAT: &str = "syo-archive";
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// First line of every archive
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+/// One entry per line after the header. Images are base64-encoded PNG.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    content_type: ContentType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    created_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_seen: Option<i64>,
+    #[serde(default = "default_copy_count")]
+    copy_count: i64,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    use_count: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_used: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+fn default_copy_count() -> i64 {
+    1
+}
+
+impl From<Entry> for Record {
+    fn from(entry: Entry) -> Self {
+        Self {
+            content_type: entry.content_type,
+            content: entry.content,
+            image: entry.image_data.as_ref().map(|d| BASE64.encode(d)),
+            created_at: entry.created_at,
+            last_seen: Some(entry.last_seen),
+            copy_count: entry.copy_count,
+            pinned: entry.pinned,
+            use_count: entry.use_count,
+            last_used: entry.last_used,
+            tags: entry.tags,
+        }
+    }
+}
+
+/// Serde adapter writing an `Entry` as an archive record, for the log of
+/// the file storage backend
+pub(crate) mod entry_record {
+    use super::{record_to_entry, Record};
+    use crate::entry::Entry;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(entry: &Entry, serializer: S) -> Result<S::Ok, S::Error> {
+        Record::from(entry.clone()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Entry, D::Error> {
+        record_to_entry(Record::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportReport {
+    pub inserted: usize,
+    /// Already stored
+    pub skipped: usize,
+    /// Items the source had that can't be stored, e.g. JPEG images
+    pub unsupported: usize,
+}
+
+/// Write entries as a versioned NDJSON archive. Entries are consumed one at
+/// a time so images don't all have to be in memory at once.
+pub fn export<I, W>(entries: I, mut out: W) -> Result<usize>
+where
+    I: IntoIterator<Item = Result<Entry>>,
+    W: Write,
+{
+    let header = Header {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+    };
+    write_line(&mut out, &header)?;
+
+    let mut count = 0;
+    for entry in entries {
+        write_line(&mut out, &Record::from(entry?))?;
+        count += 1;
+    }
+
+    out.flush()?;
+    Ok(count)
+}
+
+/// Read an archive into storage. Entries whose hash is already stored are
+/// skipped; the original `created_at` is preserved.
+pub fn import<R: BufRead>(storage: &dyn HistoryStore, input: R) -> Result<ImportReport> {
+    let mut lines = input.lines();
+
+    let header_line = lines
+        .next()
+        .ok_or_else(|| StickyError::Archive("empty archive".into()))??;
+    let header: Header = serde_json::from_str(&header_line)
+        .map_err(|e| StickyError::Archive(format!("invalid header: {e}")))?;
+    if header.format != ARCHIVE_FORMAT {
+        return Err(StickyError::Archive(format!(
+            "unknown format: {}",
+            header.format
+        )));
+    }
+    if header.version > ARCHIVE_VERSION {
+        return Err(StickyError::Archive(format!(
+            "unsupported version {} (max: {ARCHIVE_VERSION})",
+            header.version
+        )));
+    }
+
+    let mut report = ImportReport::default();
+    for (n, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = serde_json::from_str(&line)
+            .map_err(|e| StickyError::Archive(format!("line {}: {e}", n + 2)))?;
+
+        let entry = record_to_entry(record)
+            .map_err(|e| StickyError::Archive(format!("line {}: {e}", n + 2)))?;
+        store(storage, &entry, &mut report)?;
+    }
+
+    Ok(report)
+}
+
+/// Store entries whose hash isn't stored yet, keeping their timestamps
+pub fn import_entries<I>(storage: &dyn HistoryStore, entries: I) -> Result<ImportReport>
+where
+    I: IntoIterator<Item = Entry>,
+{
+    let mut report = ImportReport::default();
+    for entry in entries {
+        store(storage, &entry, &mut report)?;
+    }
+    Ok(report)
+}
+
+fn store(storage: &dyn HistoryStore, entry: &Entry, report: &mut ImportReport) -> Result<()> {
+    if let Some(id) = storage.find_by_hash(&entry.hash)? {
+        // Already stored, but it still joins the imported collections
+        if !entry.tags.is_empty() {
+            storage.add_tags(id, &entry.tags)?;
+        }
+        report.skipped += 1;
+    } else {
+        storage.insert(entry)?;
+        report.inserted += 1;
+    }
+    Ok(())
+}
+
+fn record_to_entry(record: Record) -> std::result::Result<Entry, String> {
+    let mut entry = match record.content_type {
+        ContentType::Image => {
+            let encoded = record.image.ok_or("image entry without data")?;
+            let data = BASE64.decode(encoded).map_err(|e| e.to_string())?;
+            Entry::new_image(data)
+        }
+        ContentType::Text | ContentType::Link => {
+            let text = record.content.ok_or("text entry without content")?;
+            let mut entry = Entry::new_text(text);
+            entry.content_type = record.content_type;
+            entry
+        }
+    };
+    entry.created_at = record.created_at;
+    entry.last_seen = record.last_seen.unwrap_or(record.created_at);
+    entry.copy_count = record.copy_count;
+    entry.pinned = record.pinned;
+    entry.use_count = record.use_count;
+    entry.last_used = record.last_used;
+    entry.tags = record.tags;
+    Ok(entry)
+}
+
+fn write_line<W: Write, T: Serialize>(out: &mut W, value: &T) -> Result<()> {
+    serde_json::to_writer(&mut *out, value).map_err(|e| StickyError::Archive(e.to_string()))?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStore, SortOrder};
+
+    fn full_entries(storage: &MemoryStore) -> Vec<Result<Entry>> {
+        storage
+            .list(10)
+            .unwrap()
+            .iter()
+            .map(|s| storage.get_by_id(s.id))
+            .collect()
+    }
+
+    #[test]
+    fn roundtrip_preserves_fields() {
+        let src = MemoryStore::new();
+        let mut text = Entry::new_text("SELECT * FROM t".into());
+        text.created_at = 1_700_000_000;
+        text.last_seen = 1_700_000_500;
+        text.tags = vec!["sql".into()];
+        src.insert(&text).unwrap();
+        src.insert(&Entry::new_image(vec![0x89, b'P', b'N', b'G', 0, 1]))
+            .unwrap();
+
+        let mut buf = Vec::new();
+        assert_eq!(export(full_entries(&src), &mut buf).unwrap(), 2);
+
+        let dst = MemoryStore::new();
+        let report = import(&dst, buf.as_slice()).unwrap();
+        assert_eq!(report.inserted, 2);
+
+        let sql = dst.list_tagged("sql", 10, SortOrder::Recent).unwrap();
+        assert_eq!(sql.len(), 1);
+        assert_eq!(sql[0].created_at, 1_700_000_000);
+        assert_eq!(sql[0].last_seen, 1_700_000_500);
+        assert_eq!(sql[0].preview, "SELECT * FROM t");
+    }
+
+    #[test]
+    fn import_skips_existing_hashes() {
+        let storage = MemoryStore::new();
+        storage.insert(&Entry::new_text("dup".into())).unwrap();
+
+        let mut buf = Vec::new();
+        export(full_entries(&storage), &mut buf).unwrap();
+        let report = import(&storage, buf.as_slice()).unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                inserted: 0,
+                skipped: 1,
+                unsupported: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn import_merges_tags_into_existing_entries() {
+        let storage = MemoryStore::new();
+        let id = storage.insert(&Entry::new_text("dup".into())).unwrap();
+        storage.add_tags(id, &["mine".into()]).unwrap();
+
+        let source = MemoryStore::new();
+        let mut entry = Entry::new_text("dup".into());
+        entry.tags = vec!["theirs".into()];
+        source.insert(&entry).unwrap();
+        let mut buf = Vec::new();
+        export(full_entries(&source), &mut buf).unwrap();
+
+        assert_eq!(import(&storage, buf.as_slice()).unwrap().skipped, 1);
+        let mut tags = storage.get_by_id(id).unwrap().tags;
+        tags.sort();
+        assert_eq!(tags, vec!["mine", "theirs"]);
+    }
+
+    #[test]
+    fn import_rejects_newer_version() {
+        let storage = MemoryStore::new();
+        let archive = format!("{{\"format\":\"{ARCHIVE_FORMAT}\",\"version\":99}}\n");
+        assert!(matches!(
+            import(&storage, archive.as_bytes()),
+            Err(StickyError::Archive(_))
+        ));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::crypto::Cipher;
+use crate::error::{Result, StickyError};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// Blobs younger than this are never collected, so a blob written just
+/// before its row is inserted can't be removed in between.
+const GC_GRACE: Duration = Duration::from_secs(60);
+
+/// Content-addressed directory of zstd-compressed payloads, optionally
+/// encrypted. Blobs are named by the owning entry's stored hash and fanned
+/// out into subdirectories by the first two characters.
+pub struct BlobStore {
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        if key.len() < 3 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(StickyError::Blob(format!("invalid blob key: {key:?}")));
+        }
+        Ok(self.dir.join(&key[..2]).join(key))
+    }
+
+    /// Compress (then encrypt, with a cipher) and store `data`. Existing
+    /// blobs are left alone since the key identifies the content.
+    pub fn put(&self, key: &str, data: &[u8], cipher: Option<&Cipher>) -> Result<()> {
+        let path = self.path(key)?;
+        if path.exists() {
+            // Refresh the mtime so a pending gc treats it as fresh again
+            fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(SystemTime::now())?;
+            return Ok(());
+        }
+        let parent = path.parent().expect("blob path has a parent");
+        fs::create_dir_all(parent)?;
+
+        let compressed = zstd::encode_all(data, ZSTD_LEVEL)?;
+        let stored = match cipher {
+            Some(cipher) => cipher.encrypt(&compressed),
+            None => compressed,
+        };
+
+        // Write to a temp file and rename so readers never see partial blobs
+        let tmp = parent.join(format!(".{key}.tmp"));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(&stored)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str, cipher: Option<&Cipher>) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.reader(key, cipher)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Stream a blob's content. Plaintext blobs are decompressed as they're
+    /// read; encrypted ones have to be decrypted whole first.
+    pub fn reader(&self, key: &str, cipher: Option<&Cipher>) -> Result<Box<dyn Read>> {
+        let path = self.path(key)?;
+        let mut file = fs::File::open(&path)
+            .map_err(|e| StickyError::Blob(format!("{}: {e}", path.display())))?;
+        Ok(match cipher {
+            Some(cipher) => {
+                let mut stored = Vec::new();
+                file.read_to_end(&mut stored)?;
+                Box::new(zstd::Decoder::new(Cursor::new(cipher.decrypt(&stored)?))?)
+            }
+            None => Box::new(zstd::Decoder::new(file)?),
+        })
+    }
+
+    /// Delete a blob right away, regardless of age
+    pub fn remove(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.path(key)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Overwrite a blob with zeros, then delete it, so its content doesn't
+    /// stay in the freed blocks. Copy-on-write filesystems and SSD wear
+    /// levelling can still keep old copies.
+    pub fn shred(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        let mut file = match fs::OpenOptions::new().write(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let len = file.metadata()?.len();
+        std::io::copy(&mut std::io::repeat(0).take(len), &mut file)?;
+        file.sync_all()?;
+        self.remove(key)
+    }
+
+    /// Remove blobs not in `live`. Returns how many were removed.
+    pub fn gc(&self, live: &HashSet<String>) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let now = SystemTime::now();
+        let mut removed = 0;
+
+        for shard in fs::read_dir(&self.dir)? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for blob in fs::read_dir(&shard)? {
+                let blob = blob?;
+                let name = blob.file_name().to_string_lossy().into_owned();
+                if live.contains(&name) {
+                    continue;
+                }
+                let age = blob
+                    .metadata()?
+                    .modified()
+                    .ok()
+                    .and_then(|m| now.duration_since(m).ok())
+                    .unwrap_or_default();
+                if age < GC_GRACE {
+                    continue;
+                }
+                fs::remove_file(blob.path())?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::generate_key;
+
+    fn temp_store(name: &str) -> BlobStore {
+        let dir = std::env::temp_dir().join(format!("syo-blobs-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        BlobStore::new(dir)
+    }
+
+    #[test]
+    fn put_get_roundtrip_compresses() {
+        let store = temp_store("roundtrip");
+        let data = "abc".repeat(10_000).into_bytes();
+        store.put("abcdef", &data, None).unwrap();
+        assert_eq!(store.get("abcdef", None).unwrap(), data);
+
+        let mut head = [0u8; 6];
+        store
+            .reader("abcdef", None)
+            .unwrap()
+            .read_exact(&mut head)
+            .unwrap();
+        assert_eq!(&head, b"abcabc");
+
+        let on_disk = fs::metadata(store.path("abcdef").unwrap()).unwrap().len();
+        assert!(on_disk < data.len() as u64 / 10);
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[test]
+    fn encrypted_blobs_need_the_key() {
+        let store = temp_store("encrypted");
+        let cipher = Cipher::new(&generate_key());
+        store.put("0123ab", b"secret image", Some(&cipher)).unwrap();
+        assert_eq!(store.get("0123ab", Some(&cipher)).unwrap(), b"secret image");
+        assert!(store.get("0123ab", None).is_err());
+
+        let raw = fs::read(store.path("0123ab").unwrap()).unwrap();
+        assert!(!raw.windows(6).any(|w| w == b"secret"));
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[test]
+    fn rejects_path_like_keys() {
+        let store = temp_store("keys");
+        assert!(store.put("../../etc", b"x", None).is_err());
+        assert!(store.get("ab", None).is_err());
+    }
+
+    #[test]
+    fn gc_keeps_live_and_recent_blobs() {
+        let store = temp_store("gc");
+        store.put("aaaa11", b"live", None).unwrap();
+        store.put("bbbb22", b"fresh orphan", None).unwrap();
+        store.put("cccc33", b"old orphan", None).unwrap();
+
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(store.path("cccc33").unwrap())
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let live: HashSet<String> = ["aaaa11".to_string()].into();
+        assert_eq!(store.gc(&live).unwrap(), 1);
+        assert!(store.get("aaaa11", None).is_ok());
+        assert!(store.get("bbbb22", None).is_ok());
+        assert!(store.get("cccc33", None).is_err());
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+}
@@ -3,7 +3,7 @@ use crate::entry::Entry;
 use crate::error::{Result, StickyError};
 use std::process::Command;
 
-const PNG_MAGIC: &[u8] = b"\x89PNG";
+pub(crate) const PNG_MAGIC: &[u8] = b"\x89PNG";
 
 pub enum ClipboardContent {
     Text(String),
@@ -11,7 +11,7 @@ pub enum ClipboardContent {
     Empty,
 }
 
-fn is_wayland() -> bool {
+pub(crate) fn is_wayland() -> bool {
     std::env::var("WAYLAND_DISPLAY").is_ok()
 }
 
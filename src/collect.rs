@@ -0,0 +1,187 @@
+use crate::config::collector_path;
+use crate::entry::{ContentType, Entry};
+use crate::error::{Result, StickyError};
+use crate::lockfile;
+use crate::storage::HistoryStore;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Text copies gathered into one entry while collecting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collector {
+    pub separator: String,
+    /// Entry holding what's been collected so far
+    pub entry: Option<i64>,
+    /// How many copies it holds
+    pub count: usize,
+}
+
+/// The collector state shared by the daemon and the CLI: a JSON file in
+/// the runtime dir, empty or missing while not collecting
+pub struct CollectorFile {
+    path: PathBuf,
+}
+
+impl CollectorFile {
+    pub fn open() -> Self {
+        Self::at(&collector_path())
+    }
+
+    pub fn at(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Start collecting into a new entry, joining copies with `separator`
+    pub fn start(&self, separator: String) -> Result<()> {
+        self.update(|collector| {
+            *collector = Some(Collector {
+                separator,
+                entry: None,
+                count: 0,
+            });
+            Ok(())
+        })
+    }
+
+    /// Stop collecting. Returns the collector as it was, if collecting.
+    pub fn stop(&self) -> Result<Option<Collector>> {
+        self.update(|collector| Ok(collector.take()))
+    }
+
+    pub fn status(&self) -> Result<Option<Collector>> {
+        self.update(|collector| Ok(collector.clone()))
+    }
+
+    /// Append a newly recorded text entry to the collector, if collecting.
+    /// The first copy starts it as it is; the second makes a new entry
+    /// joining the two, which later copies extend in place. Copying the
+    /// collector itself appends nothing.
+    pub fn capture(&self, store: &dyn HistoryStore, copied: &Entry, id: i64) -> Result<()> {
+        let Some(text) = copied
+            .content
+            .as_deref()
+            .filter(|_| copied.content_type != ContentType::Image)
+        else {
+            return Ok(());
+        };
+        self.update(|collector| {
+            let Some(collector) = collector else {
+                return Ok(());
+            };
+            let current = match collector.entry {
+                Some(current) => match store.get_by_id(current) {
+                    Ok(entry) => Some(entry),
+                    // Deleted meanwhile: start over
+                    Err(StickyError::NotFound(_)) => None,
+                    Err(e) => return Err(e),
+                },
+                None => None,
+            };
+            let Some(current) = current else {
+                collector.entry = Some(id);
+                collector.count = 1;
+                return Ok(());
+            };
+            if current.hash == copied.hash {
+                return Ok(());
+            }
+
+            let joined = format!(
+                "{}{}{text}",
+                current.content.as_deref().unwrap_or_default(),
+                collector.separator
+            );
+            collector.entry = Some(match collector.count {
+                1 => store.insert(&Entry::new_text(joined))?,
+                _ => store.replace(current.id, &joined)?,
+            });
+            collector.count += 1;
+            Ok(())
+        })
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut Option<Collector>) -> Result<T>) -> Result<T> {
+        lockfile::update(&self.path, StickyError::Collector, f)
+    }
+}
+
+/// A separator by name (`newline`, `space`, `comma`, `tab`), or the text
+/// itself
+pub fn separator(name: &str) -> String {
+    match name {
+        "newline" => "\n",
+        "space" => " ",
+        "comma" => ",",
+        "tab" => "\t",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Join text entries into one, in the given order
+pub fn merge(store: &dyn HistoryStore, ids: &[i64], separator: &str) -> Result<i64> {
+    let mut parts = Vec::with_capacity(ids.len());
+    for &id in ids {
+        let entry = store.get_by_id(id)?;
+        match entry.content {
+            Some(text) if entry.content_type != ContentType::Image => parts.push(text),
+            _ => return Err(StickyError::NotText(id)),
+        }
+    }
+    store.insert(&Entry::new_text(parts.join(separator)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+    use std::fs;
+
+    fn copy(collector: &CollectorFile, store: &MemoryStore, text: &str) {
+        let entry = Entry::new_text(text.into());
+        let id = store.insert(&entry).unwrap();
+        collector.capture(store, &entry, id).unwrap();
+    }
+
+    #[test]
+    fn collects_copies_into_one_entry() {
+        let dir = std::env::temp_dir().join(format!("syo-test-collect-{}", std::process::id()));
+        let collector = CollectorFile::at(&dir.join("collector.json"));
+        let store = MemoryStore::new();
+        copy(&collector, &store, "ignored");
+
+        collector.start(separator("comma")).unwrap();
+        copy(&collector, &store, "a");
+        copy(&collector, &store, "b");
+        copy(&collector, &store, "a,b");
+        copy(&collector, &store, "c");
+        let state = collector.stop().unwrap().unwrap();
+        assert_eq!(state.count, 3);
+
+        let id = state.entry.unwrap();
+        let entry = store.get_by_id(id).unwrap();
+        assert_eq!(entry.content.as_deref(), Some("a,b,c"));
+        assert!(store.list_revisions(id).unwrap().is_empty());
+        // The copies are in the history too
+        assert_eq!(store.count().unwrap(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_joins_in_order() {
+        let store = MemoryStore::new();
+        let a = store.insert(&Entry::new_text("one".into())).unwrap();
+        let b = store.insert(&Entry::new_text("two".into())).unwrap();
+        let image = store.insert(&Entry::new_image(vec![1, 2, 3])).unwrap();
+
+        let merged = merge(&store, &[b, a], &separator("newline")).unwrap();
+        let entry = store.get_by_id(merged).unwrap();
+        assert_eq!(entry.content.as_deref(), Some("two\none"));
+        assert!(matches!(
+            merge(&store, &[a, image], " "),
+            Err(StickyError::NotText(_))
+        ));
+    }
+}
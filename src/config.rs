@@ -1,30 +1,236 @@
+use crate::entry::{hash_content, normalize_tag, ContentType};
+use crate::error::{Result, StickyError};
 use directories::ProjectDirs;
 use evdev::KeyCode;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 use std::{collections::HashSet, fs};
 
 pub const APP_NAME: &str = "sticky_one";
 pub const RETENTION_HOURS: i64 = 12;
 pub const POLL_INTERVAL_MS: u64 = 500;
 pub const MAX_IMAGE_SIZE_BYTES: usize = 5 * 1024 * 1024; // 5MB
+pub const BLOB_THRESHOLD_BYTES: usize = 64 * 1024; // larger text goes to the blob store
 pub const PID_FILE: &str = "daemon.pid";
 pub const CONFIG_FILE: &str = "config.toml";
 
-pub fn data_dir() -> PathBuf {
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The profile the paths below point into; `None` is the default profile
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Switch the paths below to another profile
+pub fn set_profile(profile: Option<String>) {
+    *PROFILE.write().unwrap_or_else(|e| e.into_inner()) = profile;
+}
+
+/// The active profile, `None` for the default one
+pub fn profile() -> Option<String> {
+    PROFILE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn profile_label() -> String {
+    profile().unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Check a profile name, which follows the same rules as tags.
+/// `default` is the default profile.
+pub fn profile_name(name: &str) -> Result<Option<String>> {
+    match normalize_tag(name) {
+        Some(name) if name == DEFAULT_PROFILE => Ok(None),
+        Some(name) => Ok(Some(name)),
+        None => Err(StickyError::InvalidProfile(name.to_string())),
+    }
+}
+
+/// Paths given with `--config`, `--data-dir` and `--db` (or `SYO_CONFIG`,
+/// `SYO_DATA_DIR` and `SYO_DB`), which win over the `[paths]` config section
+#[derive(Debug, Clone, Default)]
+pub struct PathOverrides {
+    pub config: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub db: Option<PathBuf>,
+}
+
+impl PathOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.config.is_none() && self.data_dir.is_none() && self.db.is_none()
+    }
+
+    /// Fill in what wasn't given as a flag from `SYO_CONFIG`, `SYO_DATA_DIR`
+    /// and `SYO_DB`, looked up with `var`
+    pub fn or_env(self, var: impl Fn(&str) -> Option<std::ffi::OsString>) -> Self {
+        let env = |name| var(name).filter(|p| !p.is_empty()).map(PathBuf::from);
+        Self {
+            config: self.config.or_else(|| env("SYO_CONFIG")),
+            data_dir: self.data_dir.or_else(|| env("SYO_DATA_DIR")),
+            db: self.db.or_else(|| env("SYO_DB")),
+        }
+    }
+
+    /// The data dir: given, next to a given database, or from `[paths]`.
+    /// A database of its own means an instance of its own, so its daemon
+    /// and runtime files don't clash with the usual ones.
+    fn data_dir(&self, configured: &PathsConfig) -> Option<PathBuf> {
+        let parent = |db: &Option<PathBuf>| db.as_deref()?.parent().map(Path::to_path_buf);
+        self.data_dir
+            .clone()
+            .or_else(|| parent(&self.db))
+            .or_else(|| configured.data_dir.clone())
+            .or_else(|| parent(&configured.db))
+    }
+
+    fn db(&self, configured: &PathsConfig) -> Option<PathBuf> {
+        self.db.clone().or_else(|| configured.db.clone())
+    }
+
+    /// The same overrides as command-line flags, for processes we spawn
+    pub fn args(&self) -> Vec<std::ffi::OsString> {
+        [
+            ("--config", &self.config),
+            ("--data-dir", &self.data_dir),
+            ("--db", &self.db),
+        ]
+        .into_iter()
+        .filter_map(|(flag, path)| path.as_ref().map(|p| [flag.into(), p.into()]))
+        .flatten()
+        .collect()
+    }
+}
+
+/// Explicit overrides, and the `[paths]` section of the active config
+static PATHS: RwLock<(PathOverrides, PathsConfig)> = RwLock::new((
+    PathOverrides {
+        config: None,
+        data_dir: None,
+        db: None,
+    },
+    PathsConfig {
+        data_dir: None,
+        db: None,
+    },
+));
+
+/// Set the explicit path overrides. Relative paths are taken from the
+/// current dir, since the daemon runs from elsewhere.
+pub fn set_path_overrides(overrides: PathOverrides) {
+    let absolute = |path: Option<PathBuf>| path.map(|p| std::path::absolute(&p).unwrap_or(p));
+    PATHS.write().unwrap_or_else(|e| e.into_inner()).0 = PathOverrides {
+        config: absolute(overrides.config),
+        data_dir: absolute(overrides.data_dir),
+        db: absolute(overrides.db),
+    };
+}
+
+pub fn path_overrides() -> PathOverrides {
+    PATHS.read().unwrap_or_else(|e| e.into_inner()).0.clone()
+}
+
+/// Take the data dir and database from a config's `[paths]` section
+pub fn set_configured_paths(paths: &PathsConfig) {
+    PATHS.write().unwrap_or_else(|e| e.into_inner()).1 = paths.resolve(&config_dir());
+}
+
+fn configured_paths() -> PathsConfig {
+    PATHS.read().unwrap_or_else(|e| e.into_inner()).1.clone()
+}
+
+/// Switch to a profile along with the paths its config sets, and return
+/// that config
+pub fn use_profile(profile: Option<String>) -> Config {
+    set_profile(profile);
+    let config = Config::load();
+    set_configured_paths(&config.paths);
+    config
+}
+
+/// Where a profile's files go under `base`. The default profile keeps the
+/// top-level dir so existing history stays where it was.
+fn profile_dir(base: PathBuf, profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(name) => base.join("profiles").join(name),
+        None => base,
+    }
+}
+
+fn base_data_dir() -> PathBuf {
     ProjectDirs::from("", "", APP_NAME)
         .map(|dirs| dirs.data_dir().to_path_buf())
         .unwrap_or_else(|| PathBuf::from("."))
 }
 
-pub fn config_dir() -> PathBuf {
+fn base_config_dir() -> PathBuf {
     ProjectDirs::from("", "", APP_NAME)
         .map(|dirs| dirs.config_dir().to_path_buf())
         .unwrap_or_else(|| PathBuf::from("."))
 }
 
+/// The data dir given explicitly or in `[paths]`, which is used as is,
+/// whatever the profile
+fn data_dir_override() -> Option<PathBuf> {
+    path_overrides().data_dir(&configured_paths())
+}
+
+pub fn data_dir() -> PathBuf {
+    data_dir_override().unwrap_or_else(|| profile_dir(base_data_dir(), profile().as_deref()))
+}
+
+/// The profile's config dir, or the dir of the `--config` file
+pub fn config_dir() -> PathBuf {
+    match path_overrides().config {
+        Some(path) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        None => profile_dir(base_config_dir(), profile().as_deref()),
+    }
+}
+
+/// Holds the profile chosen with `syo profile use`, for every profile
+fn current_profile_path() -> PathBuf {
+    base_config_dir().join("profile")
+}
+
+/// The profile chosen with `syo profile use`, `None` for the default one
+pub fn current_profile() -> Option<String> {
+    let name = fs::read_to_string(current_profile_path()).ok()?;
+    profile_name(name.trim()).ok().flatten()
+}
+
+pub fn save_current_profile(profile: Option<&str>) -> std::io::Result<()> {
+    let path = current_profile_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, profile.unwrap_or(DEFAULT_PROFILE))
+}
+
+/// Every profile with a config or data dir, and the default one, by name
+pub fn list_profiles() -> Vec<String> {
+    let mut names: Vec<String> = [base_data_dir(), base_config_dir()]
+        .iter()
+        .filter_map(|base| fs::read_dir(base.join("profiles")).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| matches!(profile_name(name), Ok(Some(_))))
+        .collect();
+    names.push(DEFAULT_PROFILE.to_string());
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// The SQLite database; its blobs are kept next to it
 pub fn db_path() -> PathBuf {
-    data_dir().join("clipboard.db")
+    path_overrides()
+        .db(&configured_paths())
+        .unwrap_or_else(|| data_dir().join("clipboard.db"))
+}
+
+/// Operation log of the `file` storage backend
+pub fn journal_path() -> PathBuf {
+    data_dir().join("history.jsonl")
 }
 
 pub fn pid_path() -> PathBuf {
@@ -32,23 +238,353 @@ pub fn pid_path() -> PathBuf {
 }
 
 pub fn config_path() -> PathBuf {
-    config_dir().join(CONFIG_FILE)
+    path_overrides()
+        .config
+        .unwrap_or_else(|| config_dir().join(CONFIG_FILE))
+}
+
+/// Named text templates, see `snippet::Snippets`
+pub fn snippets_path() -> PathBuf {
+    config_dir().join("snippets.toml")
 }
 
 pub fn log_path() -> PathBuf {
     data_dir().join("daemon.log")
 }
 
+/// The session's own dir under `XDG_RUNTIME_DIR`, which doesn't outlive a
+/// logout, if there is one. An instance with its own data dir gets its own
+/// too, so it doesn't share a queue or session key with the others.
+pub fn session_dir() -> Option<PathBuf> {
+    let base = PathBuf::from(std::env::var_os("XDG_RUNTIME_DIR")?).join(APP_NAME);
+    Some(match data_dir_override() {
+        Some(dir) => base
+            .join("instances")
+            .join(&hash_content(dir.as_os_str().as_encoded_bytes())[..16]),
+        None => profile_dir(base, profile().as_deref()),
+    })
+}
+
+/// Per-session files: the session dir, or the data dir without one
+pub fn runtime_dir() -> PathBuf {
+    session_dir().unwrap_or_else(data_dir)
+}
+
+/// Queue mode state, see `queue::QueueFile`
+pub fn queue_path() -> PathBuf {
+    runtime_dir().join("queue.json")
+}
+
+/// Collector state, see `collect::CollectorFile`
+pub fn collector_path() -> PathBuf {
+    runtime_dir().join("collector.json")
+}
+
+/// The key `syo unlock` leaves for the session. It's only ever kept in the
+/// session dir: never on persistent disk.
+pub fn session_key_path() -> Option<PathBuf> {
+    session_dir().map(|dir| dir.join("session.key"))
+}
+
+/// Marks history with a `key_file` as locked until `syo unlock`
+pub fn lock_marker_path() -> PathBuf {
+    runtime_dir().join("locked")
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub hotkey: HotkeyConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub trash: TrashConfig,
+    #[serde(default)]
+    pub paths: PathsConfig,
+}
+
+/// Where the data dir and database go instead of the profile's data dir.
+/// `--data-dir` and `--db` win over these.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PathsConfig {
+    /// Daemon files, log and the history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    /// SQLite database file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db: Option<PathBuf>,
+}
+
+impl PathsConfig {
+    /// Relative paths taken from `dir`, the config file's
+    fn resolve(&self, dir: &Path) -> Self {
+        let resolve = |path: &Option<PathBuf>| path.as_ref().map(|p| dir.join(p));
+        Self {
+            data_dir: resolve(&self.data_dir),
+            db: resolve(&self.db),
+        }
+    }
+}
+
+/// Where history is kept
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageConfig {
+    #[serde(default)]
+    pub backend: Backend,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// SQLite database with a blob store; supports encryption
+    #[default]
+    Sqlite,
+    /// Append-only log of operations, replayed into memory on open
+    File,
+}
+
+/// Deleted entries wait in the trash for `grace` before they're purged, so
+/// `syo undo` and `syo restore` can bring them back. Off by default, since
+/// trashed content stays on disk; a `[trash]` section without `grace` keeps
+/// entries for a day, and a grace of `0s` turns the trash off.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TrashConfig {
+    #[serde(default = "default_trash_grace")]
+    pub grace: Age,
+    /// Send entries removed by retention cleanup to the trash as well
+    #[serde(default)]
+    pub expired: bool,
+}
+
+fn default_trash_grace() -> Age {
+    Age(86_400)
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self::off()
+    }
+}
+
+impl TrashConfig {
+    /// No trash: deleted entries are gone right away
+    pub fn off() -> Self {
+        Self {
+            grace: Age(0),
+            expired: false,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.grace.0 > 0
+    }
+}
+
+/// Encryption of entry content at rest. The key comes from `key_file` when
+/// set, otherwise from a passphrase entered with `syo unlock`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// File holding a 32-byte key, created on first use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<PathBuf>,
+}
+
+/// Limits enforced by `Storage::cleanup_old`. Pinned entries and entries in
+/// kept collections are exempt from all of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    /// Entries older than this are removed
+    #[serde(default = "default_max_age")]
+    pub max_age: Age,
+    /// Keep at most this many entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+    /// Keep at most this many bytes of content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<ByteSize>,
+    /// Per-type max age, replacing `max_age` for that type
+    #[serde(default)]
+    pub types: TypeRetention,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TypeRetention {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<Age>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<Age>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<Age>,
+}
+
+fn default_max_age() -> Age {
+    Age(RETENTION_HOURS * 3600)
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age: default_max_age(),
+            max_entries: None,
+            max_bytes: None,
+            types: TypeRetention::default(),
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// Max age in seconds for a content type, with its override applied
+    pub fn max_age_for(&self, content_type: ContentType) -> Age {
+        self.type_override(content_type).unwrap_or(self.max_age)
+    }
+
+    /// The longest any entry is kept, over all types
+    pub fn longest_age(&self) -> Age {
+        [ContentType::Text, ContentType::Link, ContentType::Image]
+            .into_iter()
+            .map(|t| self.max_age_for(t))
+            .max_by_key(|age| age.0)
+            .unwrap_or(self.max_age)
+    }
+
+    pub fn type_override(&self, content_type: ContentType) -> Option<Age> {
+        match content_type {
+            ContentType::Text => self.types.text,
+            ContentType::Link => self.types.link,
+            ContentType::Image => self.types.image,
+        }
+    }
+}
+
+/// A duration in seconds, written as `30s`, `15m`, `12h`, `7d` or `2w`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Age(pub i64);
+
+impl TryFrom<String> for Age {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        parse_duration(&s)
+            .map(Age)
+            .ok_or_else(|| format!("invalid duration: {s:?} (e.g. 30m, 12h, 7d)"))
+    }
+}
+
+impl From<Age> for String {
+    fn from(age: Age) -> Self {
+        age.to_string()
+    }
+}
+
+impl fmt::Display for Age {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (unit, secs) in [("w", 604_800), ("d", 86_400), ("h", 3600), ("m", 60)] {
+            if self.0 != 0 && self.0 % secs == 0 {
+                return write!(f, "{}{unit}", self.0 / secs);
+            }
+        }
+        write!(f, "{}s", self.0)
+    }
+}
+
+/// A size in bytes, written as `512KB`, `100MB`, `1GB` or a plain number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ByteSize(pub u64);
+
+impl TryFrom<String> for ByteSize {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        parse_byte_size(&s)
+            .map(ByteSize)
+            .ok_or_else(|| format!("invalid size: {s:?} (e.g. 512KB, 100MB)"))
+    }
+}
+
+impl From<ByteSize> for String {
+    fn from(size: ByteSize) -> Self {
+        size.to_string()
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (unit, bytes) in [("GB", 1 << 30), ("MB", 1 << 20), ("KB", 1 << 10)] {
+            if self.0 != 0 && self.0.is_multiple_of(bytes) {
+                return write!(f, "{}{unit}", self.0 / bytes);
+            }
+        }
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Parse a duration like `30s`, `15m`, `12h`, `7d` or `2w` into seconds
+pub fn parse_duration(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let (num, unit) = s.split_at(split);
+    let num: i64 = num.parse().ok()?;
+    let secs = match unit.to_lowercase().as_str() {
+        "s" | "sec" | "secs" => 1,
+        "m" | "min" | "mins" => 60,
+        "h" | "hour" | "hours" => 3600,
+        "d" | "day" | "days" => 86_400,
+        "w" | "week" | "weeks" => 604_800,
+        _ => return None,
+    };
+    num.checked_mul(secs)
+}
+
+/// Parse a size like `512KB`, `100MB`, `1GB` or `2048` into bytes
+pub fn parse_byte_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: u64 = num.parse().ok()?;
+    let mult = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return None,
+    };
+    num.checked_mul(mult)
+}
+
+/// Parse a point in time: a duration back from `now` (`7d`), a local date
+/// (`2026-01-31`) or an RFC 3339 timestamp. Returns a unix timestamp.
+pub fn parse_time(s: &str, now: i64) -> Option<i64> {
+    let s = s.trim();
+    if let Some(secs) = parse_duration(s) {
+        return Some(now - secs);
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return date
+            .and_time(chrono::NaiveTime::MIN)
+            .and_local_timezone(chrono::Local)
+            .earliest()
+            .map(|dt| dt.timestamp());
+    }
+    chrono::DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|dt| dt.timestamp())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HotkeyConfig {
     pub modifiers: Vec<String>,
     pub key: String,
+    /// Further hotkeys, each copying a register
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bindings: Vec<HotkeyBinding>,
 }
 
 impl Default for HotkeyConfig {
@@ -56,16 +592,49 @@ impl Default for HotkeyConfig {
         Self {
             modifiers: vec!["Alt".to_string(), "Shift".to_string()],
             key: "C".to_string(),
+            bindings: Vec::new(),
         }
     }
 }
 
 impl HotkeyConfig {
     pub fn modifier_keys(&self) -> HashSet<KeyCode> {
-        self.modifiers
-            .iter()
-            .filter_map(|m| parse_modifier(m))
-            .collect()
+        modifier_keys(&self.modifiers)
+    }
+
+    pub fn trigger_key(&self) -> Option<KeyCode> {
+        parse_key(&self.key)
+    }
+}
+
+/// A `[[hotkey.bindings]]` entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotkeyBinding {
+    pub modifiers: Vec<String>,
+    pub key: String,
+    #[serde(flatten)]
+    pub action: BindingAction,
+}
+
+/// What a bound hotkey does: `register = "a"` or `queue = "next"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BindingAction {
+    /// Copy a register to the clipboard
+    Register(char),
+    Queue(QueueStep),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueStep {
+    /// Put the next queued item on the clipboard
+    Next,
+}
+
+impl HotkeyBinding {
+    pub fn modifier_keys(&self) -> HashSet<KeyCode> {
+        modifier_keys(&self.modifiers)
     }
 
     pub fn trigger_key(&self) -> Option<KeyCode> {
@@ -73,6 +642,10 @@ impl HotkeyConfig {
     }
 }
 
+fn modifier_keys(names: &[String]) -> HashSet<KeyCode> {
+    names.iter().filter_map(|m| parse_modifier(m)).collect()
+}
+
 fn parse_modifier(name: &str) -> Option<KeyCode> {
     match name.to_lowercase().as_str() {
         "alt" | "left_alt" => Some(KeyCode::KEY_LEFTALT),
@@ -161,6 +734,20 @@ impl Config {
         }
     }
 
+    /// Like `load`, but a config file that doesn't parse is an error
+    /// rather than falling back to the defaults
+    pub fn try_load() -> Result<Self> {
+        Self::try_load_from(&config_path())
+    }
+
+    fn try_load_from(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(s) => toml::from_str(&s).map_err(|e| StickyError::Config(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub fn save(&self) -> std::io::Result<()> {
         let path = config_path();
         if let Some(parent) = path.parent() {
@@ -183,6 +770,133 @@ mod tests {
         assert!(c.hotkey.modifiers.contains(&"Shift".to_string()));
     }
 
+    #[test]
+    fn profiles_get_their_own_dirs() {
+        assert_eq!(profile_name("Work").unwrap().as_deref(), Some("work"));
+        assert_eq!(profile_name("default").unwrap(), None);
+        assert!(profile_name("../etc").is_err());
+
+        let base = PathBuf::from("/data/sticky_one");
+        assert_eq!(profile_dir(base.clone(), None), base);
+        assert_eq!(
+            profile_dir(base, Some("work")),
+            PathBuf::from("/data/sticky_one/profiles/work")
+        );
+    }
+
+    #[test]
+    fn paths_section_and_overrides() {
+        let config: Config = toml::from_str(
+            r#"
+            [paths]
+            db = "/mnt/tmpfs/clipboard.db"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.paths.db.as_deref(),
+            Some(Path::new("/mnt/tmpfs/clipboard.db"))
+        );
+        assert!(config.paths.data_dir.is_none());
+        assert!(Config::default().paths.db.is_none());
+
+        let overrides = PathOverrides {
+            data_dir: Some("/tmp/syo".into()),
+            db: Some("/tmp/syo/test.db".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            overrides.args(),
+            ["--data-dir", "/tmp/syo", "--db", "/tmp/syo/test.db"]
+        );
+        assert!(PathOverrides::default().is_empty());
+    }
+
+    #[test]
+    fn path_precedence_and_relative_paths() {
+        let env = |name: &str| match name {
+            "SYO_DATA_DIR" => Some("/env/data".into()),
+            "SYO_DB" => Some("/env/history.db".into()),
+            "SYO_CONFIG" => Some("".into()),
+            _ => None,
+        };
+        let configured = PathsConfig {
+            data_dir: Some("data".into()),
+            db: Some("/vault/clipboard.db".into()),
+        }
+        .resolve(Path::new("/home/me/.config/sticky_one"));
+        assert_eq!(
+            configured.data_dir.as_deref(),
+            Some(Path::new("/home/me/.config/sticky_one/data"))
+        );
+
+        // A flag beats its environment variable, which beats [paths]
+        let overrides = PathOverrides {
+            db: Some("/flag/test.db".into()),
+            ..Default::default()
+        }
+        .or_env(env);
+        assert_eq!(overrides.config, None, "empty variables are ignored");
+        assert_eq!(
+            overrides.db(&configured).as_deref(),
+            Some(Path::new("/flag/test.db"))
+        );
+        assert_eq!(
+            overrides.data_dir(&configured).as_deref(),
+            Some(Path::new("/env/data"))
+        );
+
+        // Without a data dir, an instance's database brings its own
+        let db_only = PathOverrides {
+            db: Some("/tmp/t.db".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            db_only.data_dir(&configured).as_deref(),
+            Some(Path::new("/tmp"))
+        );
+        let none = PathOverrides::default();
+        assert_eq!(
+            none.data_dir(&configured).as_deref(),
+            Some(Path::new("/home/me/.config/sticky_one/data"))
+        );
+        assert_eq!(
+            none.db(&configured).as_deref(),
+            Some(Path::new("/vault/clipboard.db"))
+        );
+        let db_configured = PathsConfig {
+            data_dir: None,
+            ..configured
+        };
+        assert_eq!(
+            none.data_dir(&db_configured).as_deref(),
+            Some(Path::new("/vault"))
+        );
+    }
+
+    #[test]
+    fn try_load_reports_parse_errors() {
+        let dir = std::env::temp_dir().join(format!("syo-test-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE);
+        assert!(
+            Config::try_load_from(&path).is_ok(),
+            "missing means defaults"
+        );
+
+        fs::write(&path, "[retention]\nmax_age = \"forever\"\n").unwrap();
+        assert!(matches!(
+            Config::try_load_from(&path),
+            Err(StickyError::Config(_))
+        ));
+        fs::write(&path, "[storage\n").unwrap();
+        assert!(matches!(
+            Config::try_load_from(&path),
+            Err(StickyError::Config(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn parse_key_letters() {
         assert_eq!(parse_key("A"), Some(KeyCode::KEY_A));
@@ -217,6 +931,146 @@ mod tests {
         assert_eq!(hk.trigger_key(), Some(KeyCode::KEY_C));
     }
 
+    #[test]
+    fn hotkey_bindings_parse() {
+        let c: Config = toml::from_str(
+            r#"
+            [hotkey]
+            modifiers = ["Alt", "Shift"]
+            key = "C"
+
+            [[hotkey.bindings]]
+            modifiers = ["Super"]
+            key = "1"
+            register = "a"
+
+            [[hotkey.bindings]]
+            modifiers = ["Super"]
+            key = "V"
+            queue = "next"
+            "#,
+        )
+        .unwrap();
+        let binding = &c.hotkey.bindings[0];
+        assert_eq!(binding.action, BindingAction::Register('a'));
+        assert_eq!(
+            c.hotkey.bindings[1].action,
+            BindingAction::Queue(QueueStep::Next)
+        );
+        assert_eq!(binding.trigger_key(), Some(KeyCode::KEY_1));
+        assert!(binding.modifier_keys().contains(&KeyCode::KEY_LEFTMETA));
+    }
+
+    #[test]
+    fn parse_time_formats() {
+        let now = 1_700_000_000;
+        assert_eq!(parse_time("1h", now), Some(now - 3600));
+        assert_eq!(parse_time("2026-01-31T12:00:00Z", now), Some(1_769_860_800));
+        let date = parse_time("2026-01-31", now).unwrap();
+        assert!((1_769_860_800 - 86_400..=1_769_860_800 + 86_400).contains(&date));
+        assert_eq!(parse_time("yesterday", now), None);
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("30s"), Some(30));
+        assert_eq!(parse_duration("15m"), Some(900));
+        assert_eq!(parse_duration("12h"), Some(43_200));
+        assert_eq!(parse_duration("7d"), Some(604_800));
+        assert_eq!(parse_duration("2w"), Some(1_209_600));
+        assert_eq!(parse_duration("12"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("5y"), None);
+    }
+
+    #[test]
+    fn parse_byte_size_units() {
+        assert_eq!(parse_byte_size("2048"), Some(2048));
+        assert_eq!(parse_byte_size("512KB"), Some(512 * 1024));
+        assert_eq!(parse_byte_size("100 MB"), Some(100 * 1024 * 1024));
+        assert_eq!(parse_byte_size("1gb"), Some(1 << 30));
+        assert_eq!(parse_byte_size("1TB"), None);
+    }
+
+    #[test]
+    fn age_display_roundtrip() {
+        for s in ["30s", "15m", "12h", "3d", "2w", "90m"] {
+            assert_eq!(Age::try_from(s.to_string()).unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn retention_section_parses() {
+        let c: Config = toml::from_str(
+            r#"
+            [retention]
+            max_age = "1d"
+            max_entries = 500
+            max_bytes = "200MB"
+
+            [retention.types]
+            image = "1h"
+            text = "7d"
+            "#,
+        )
+        .unwrap();
+        let r = &c.retention;
+        assert_eq!(r.max_age, Age(86_400));
+        assert_eq!(r.max_entries, Some(500));
+        assert_eq!(r.max_bytes, Some(ByteSize(200 << 20)));
+        assert_eq!(r.max_age_for(ContentType::Image), Age(3600));
+        assert_eq!(r.max_age_for(ContentType::Text), Age(604_800));
+        assert_eq!(r.max_age_for(ContentType::Link), Age(86_400));
+    }
+
+    #[test]
+    fn retention_defaults_to_twelve_hours() {
+        let c: Config = toml::from_str("").unwrap();
+        assert_eq!(c.retention.max_age, Age(RETENTION_HOURS * 3600));
+        assert!(c.retention.max_entries.is_none());
+        assert!(c.retention.max_bytes.is_none());
+    }
+
+    #[test]
+    fn encryption_disabled_by_default() {
+        let c: Config = toml::from_str("").unwrap();
+        assert!(!c.encryption.enabled);
+        assert!(c.encryption.key_file.is_none());
+
+        let c: Config = toml::from_str(
+            r#"
+            [encryption]
+            enabled = true
+            key_file = "/run/media/vault/syo.key"
+            "#,
+        )
+        .unwrap();
+        assert!(c.encryption.enabled);
+        assert_eq!(
+            c.encryption.key_file,
+            Some(PathBuf::from("/run/media/vault/syo.key"))
+        );
+    }
+
+    #[test]
+    fn storage_backend_defaults_to_sqlite() {
+        let c: Config = toml::from_str("").unwrap();
+        assert_eq!(c.storage.backend, Backend::Sqlite);
+        let c: Config = toml::from_str("[storage]\nbackend = \"file\"").unwrap();
+        assert_eq!(c.storage.backend, Backend::File);
+    }
+
+    #[test]
+    fn trash_defaults_and_zero_grace() {
+        let c: Config = toml::from_str("").unwrap();
+        assert!(!c.trash.enabled());
+        assert!(!c.trash.expired);
+        let c: Config = toml::from_str("[trash]\nexpired = true").unwrap();
+        assert_eq!(c.trash.grace.0, 86_400);
+        let c: Config = toml::from_str("[trash]\ngrace = \"0s\"").unwrap();
+        assert!(!c.trash.enabled());
+    }
+
     #[test]
     fn config_toml_roundtrip() {
         let c = Config::default();
@@ -224,5 +1078,6 @@ mod tests {
         let deserialized: Config = toml::from_str(&serialized).unwrap();
         assert_eq!(deserialized.hotkey.key, c.hotkey.key);
         assert_eq!(deserialized.hotkey.modifiers, c.hotkey.modifiers);
+        assert_eq!(deserialized.retention.max_age, c.retention.max_age);
     }
 }
@@ -0,0 +1,241 @@
+use crate::config::{self, lock_marker_path, EncryptionConfig};
+use crate::error::{Result, StickyError};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const KEY_LEN: usize = 32;
+pub const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// AEAD cipher for entry content plus a MAC key for content hashes, both
+/// derived from one master key.
+pub struct Cipher {
+    aead: ChaCha20Poly1305,
+    mac_key: [u8; KEY_LEN],
+}
+
+impl Cipher {
+    pub fn new(master: &[u8; KEY_LEN]) -> Self {
+        let enc_key = hmac(master, b"sticky_one encryption");
+        let mac_key = hmac(master, b"sticky_one hash");
+        Self {
+            aead: ChaCha20Poly1305::new(&enc_key.into()),
+            mac_key,
+        }
+    }
+
+    /// Encrypt with a random nonce. Output is `nonce || ciphertext`.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .aead
+            .encrypt(&nonce, plaintext)
+            .expect("in-memory encryption cannot fail");
+        let mut out = nonce.to_vec();
+        out.extend(ciphertext);
+        out
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            return Err(StickyError::Crypto("ciphertext too short".into()));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.aead
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| StickyError::Crypto("wrong key or corrupted data".into()))
+    }
+
+    /// Key a content hash so stored hashes can't confirm guessed plaintext
+    pub fn keyed_hash(&self, hash: &str) -> String {
+        hmac(&self.mac_key, hash.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> [u8; KEY_LEN] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+pub fn generate_key() -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+pub fn generate_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Derive a master key from a passphrase with Argon2id
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| StickyError::Crypto(e.to_string()))?;
+    Ok(key)
+}
+
+/// Load the master key: from the key file if configured, otherwise from the
+/// session key written by `syo unlock`.
+pub fn load_key(config: &EncryptionConfig) -> Result<[u8; KEY_LEN]> {
+    match key_path(config) {
+        Some(path) if path.exists() => read_key_file(&path),
+        _ => Err(StickyError::Locked),
+    }
+}
+
+pub fn is_unlocked(config: &EncryptionConfig) -> bool {
+    key_path(config).is_some_and(|path| path.exists())
+}
+
+/// Where the key is while unlocked
+fn key_path(config: &EncryptionConfig) -> Option<PathBuf> {
+    match &config.key_file {
+        Some(_) if lock_marker_path().exists() => None,
+        Some(path) => Some(path.clone()),
+        None => config::session_key_path(),
+    }
+}
+
+/// Where `syo unlock` leaves the key. Without a session dir there's nowhere
+/// it wouldn't outlive the session, so that's an error.
+pub fn session_key_path() -> Result<PathBuf> {
+    config::session_key_path().ok_or_else(|| {
+        StickyError::Crypto(
+            "XDG_RUNTIME_DIR is not set, so there's nowhere to keep the key for \
+             the session without writing it to disk; set [encryption] key_file instead"
+                .into(),
+        )
+    })
+}
+
+pub fn read_key_file(path: &Path) -> Result<[u8; KEY_LEN]> {
+    let data = fs::read(path)?;
+    data.try_into().map_err(|data: Vec<u8>| {
+        StickyError::Crypto(format!(
+            "{}: expected {KEY_LEN}-byte key, found {} bytes",
+            path.display(),
+            data.len()
+        ))
+    })
+}
+
+/// Write a key readable only by the current user
+pub fn write_key_file(path: &Path, key: &[u8; KEY_LEN]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    use std::io::Write;
+    options.open(path)?.write_all(key)?;
+    Ok(())
+}
+
+pub fn save_session_key(key: &[u8; KEY_LEN]) -> Result<()> {
+    write_key_file(&session_key_path()?, key)
+}
+
+/// Lock the history: overwrite and remove the session key, or with a key
+/// file, leave it alone until `syo unlock`. Returns false if already locked.
+pub fn lock(config: &EncryptionConfig) -> Result<bool> {
+    if config.key_file.is_some() {
+        let marker = lock_marker_path();
+        if marker.exists() {
+            return Ok(false);
+        }
+        if let Some(parent) = marker.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(marker, "")?;
+        return Ok(true);
+    }
+    let Some(path) = config::session_key_path().filter(|path| path.exists()) else {
+        return Ok(false);
+    };
+    write_key_file(&path, &[0u8; KEY_LEN])?;
+    fs::remove_file(&path)?;
+    Ok(true)
+}
+
+/// Let a key file be used again after `lock`
+pub fn clear_lock() -> Result<()> {
+    match fs::remove_file(lock_marker_path()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_roundtrip() {
+        let cipher = Cipher::new(&generate_key());
+        let sealed = cipher.encrypt(b"hunter2");
+        assert_ne!(&sealed[NONCE_LEN..], b"hunter2");
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), b"hunter2");
+    }
+
+    #[test]
+    fn encrypt_uses_fresh_nonces() {
+        let cipher = Cipher::new(&generate_key());
+        assert_ne!(cipher.encrypt(b"same"), cipher.encrypt(b"same"));
+    }
+
+    #[test]
+    fn decrypt_rejects_wrong_key_and_tampering() {
+        let cipher = Cipher::new(&generate_key());
+        let mut sealed = cipher.encrypt(b"secret");
+        assert!(Cipher::new(&generate_key()).decrypt(&sealed).is_err());
+
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(cipher.decrypt(&sealed).is_err());
+        assert!(cipher.decrypt(b"short").is_err());
+    }
+
+    #[test]
+    fn keyed_hash_depends_on_key() {
+        let key = generate_key();
+        let a = Cipher::new(&key);
+        let b = Cipher::new(&key);
+        let other = Cipher::new(&generate_key());
+        assert_eq!(a.keyed_hash("abc"), b.keyed_hash("abc"));
+        assert_ne!(a.keyed_hash("abc"), other.keyed_hash("abc"));
+        assert_eq!(a.keyed_hash("abc").len(), 64);
+    }
+
+    #[test]
+    fn derive_key_is_deterministic_per_salt() {
+        let salt = [7u8; SALT_LEN];
+        let k1 = derive_key("correct horse", &salt).unwrap();
+        let k2 = derive_key("correct horse", &salt).unwrap();
+        let k3 = derive_key("correct horse", &[8u8; SALT_LEN]).unwrap();
+        assert_eq!(k1, k2);
+        assert_ne!(k1, k3);
+    }
+}
@@ -1,9 +1,14 @@
-use crate::clipboard::read_as_entry;
-use crate::config::{log_path, pid_path, Config, POLL_INTERVAL_MS};
+use crate::clipboard::{read_as_entry, write_entry};
+use crate::collect::CollectorFile;
+use crate::config::{
+    self, current_profile, log_path, path_overrides, pid_path, profile, profile_label, Config,
+    POLL_INTERVAL_MS,
+};
 use crate::error::{Result, StickyError};
-use crate::hotkey::HotkeyListener;
-use crate::storage::Storage;
-use log::{error, warn};
+use crate::hotkey::{HotkeyAction, HotkeyListener};
+use crate::queue::QueueFile;
+use crate::storage::{self, HistoryStore};
+use log::{error, info, warn};
 use simplelog::{ConfigBuilder, LevelFilter, WriteLogger};
 use std::fs;
 use std::process::Command;
@@ -13,9 +18,15 @@ use tokio::sync::mpsc;
 use tokio::time::interval;
 
 pub struct Daemon {
-    storage: Storage,
+    storage: Box<dyn HistoryStore>,
     last_hash: Option<String>,
     config: Config,
+    queue: QueueFile,
+    collector: CollectorFile,
+    /// Follow `syo profile use`, unless started for a given profile
+    follow: bool,
+    /// Last profile asked for with `syo profile use`
+    requested: Option<String>,
 }
 
 impl Daemon {
@@ -34,23 +45,37 @@ impl Daemon {
             let _ = WriteLogger::init(LevelFilter::Info, log_config, file);
         }
 
-        let storage = Storage::open()?;
+        Self::with_store(storage::open()?)
+    }
+
+    /// A daemon recording into `storage` instead of the configured backend
+    pub fn with_store(storage: Box<dyn HistoryStore>) -> Result<Self> {
         let last_hash = storage.get_latest_hash()?;
         let config = Config::load();
         Ok(Self {
             storage,
             last_hash,
             config,
+            queue: QueueFile::open(),
+            collector: CollectorFile::open(),
+            follow: false,
+            requested: profile(),
         })
     }
 
+    /// Switch to whichever profile `syo profile use` picks while running
+    pub fn follow_current_profile(mut self) -> Self {
+        self.follow = true;
+        self
+    }
+
     pub async fn run(&mut self) -> Result<()> {
-        self.storage.cleanup_old()?;
+        self.cleanup_old()?;
 
         let mut poll = interval(Duration::from_millis(POLL_INTERVAL_MS));
 
         // Setup hotkey listener
-        let (hotkey_tx, mut hotkey_rx) = mpsc::channel::<()>(1);
+        let (hotkey_tx, mut hotkey_rx) = mpsc::channel::<HotkeyAction>(1);
         let hotkey_listener = HotkeyListener::new(&self.config.hotkey)?;
 
         tokio::spawn(async move {
@@ -62,13 +87,26 @@ impl Daemon {
         loop {
             tokio::select! {
                 _ = poll.tick() => {
+                    if let Err(e) = self.check_profile() {
+                        warn!("Profile switch: {}", e);
+                    }
                     if let Err(e) = self.poll_clipboard() {
                         warn!("Clipboard poll error: {}", e);
                     }
                 }
-                Some(()) = hotkey_rx.recv() => {
-                    self.spawn_popup();
-                }
+                Some(action) = hotkey_rx.recv() => match action {
+                    HotkeyAction::Popup => self.spawn_popup(),
+                    HotkeyAction::Register(name) => {
+                        if let Err(e) = self.copy_register(name) {
+                            warn!("Register {} hotkey: {}", name, e);
+                        }
+                    }
+                    HotkeyAction::QueueNext => {
+                        if let Err(e) = self.paste_next() {
+                            warn!("Queue hotkey: {}", e);
+                        }
+                    }
+                },
                 _ = signal::ctrl_c() => {
                     self.cleanup()?;
                     break;
@@ -82,7 +120,76 @@ impl Daemon {
     fn spawn_popup(&self) {
         // Get current executable path
         if let Ok(exe) = std::env::current_exe() {
-            let _ = Command::new(exe).arg("popup").spawn();
+            let _ = Command::new(exe)
+                .args(path_overrides().args())
+                .args(["--profile", &profile_label(), "popup"])
+                .spawn();
+        }
+    }
+
+    /// Switch profiles when `syo profile use` has picked another one
+    fn check_profile(&mut self) -> Result<()> {
+        if !self.follow {
+            return Ok(());
+        }
+        let requested = current_profile();
+        if requested == self.requested {
+            return Ok(());
+        }
+        self.requested = requested.clone();
+        if requested != profile() {
+            self.switch_profile(requested)?;
+        }
+        Ok(())
+    }
+
+    /// Record into another profile's history, with its config, from now on.
+    /// The hotkeys stay as they were at startup.
+    fn switch_profile(&mut self, next: Option<String>) -> Result<()> {
+        let previous = profile();
+        let old_pid = pid_path();
+        let config = config::use_profile(next);
+        let opened = match is_running() {
+            Some(pid) => Err(StickyError::DaemonRunning(pid)),
+            None => storage::open(),
+        };
+        let storage = match opened {
+            Ok(storage) => storage,
+            Err(e) => {
+                config::use_profile(previous);
+                return Err(e);
+            }
+        };
+
+        let pid = pid_path();
+        if let Some(parent) = pid.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&pid, std::process::id().to_string())?;
+        let _ = fs::remove_file(old_pid);
+
+        // The clipboard still holds the last profile's copy: keep last_hash
+        // so it isn't recorded again here
+        self.storage = storage;
+        self.config = config;
+        self.queue = QueueFile::open();
+        self.collector = CollectorFile::open();
+        info!("Switched to profile {}", profile_label());
+        Ok(())
+    }
+
+    fn copy_register(&mut self, name: char) -> Result<()> {
+        let entry = self.storage.get_register(name)?;
+        // So the next poll doesn't record it as a fresh copy
+        self.last_hash = Some(entry.hash.clone());
+        write_entry(&entry)
+    }
+
+    /// Put the next queued item on the clipboard
+    fn paste_next(&self) -> Result<()> {
+        match self.queue.next(&*self.storage)? {
+            Some(entry) => write_entry(&entry),
+            None => Ok(()),
         }
     }
 
@@ -99,12 +206,30 @@ impl Daemon {
             return Ok(());
         }
 
-        self.storage.insert(&entry)?;
-        self.last_hash = Some(entry.hash);
+        // Also queues the entry in queue mode, unless it's the queue's own
+        let recorded = self
+            .queue
+            .capture(&entry.hash, || self.storage.capture(&entry))?;
+        // Recorded now, so it's not recorded again even if what follows fails
+        self.last_hash = Some(entry.hash.clone());
+        if let Some(id) = recorded {
+            if let Err(e) = self.collector.capture(&*self.storage, &entry, id) {
+                warn!("Collector error: {}", e);
+            }
+            // Periodic cleanup
+            if let Err(e) = self.cleanup_old() {
+                warn!("Cleanup error: {}", e);
+            }
+        }
 
-        // Periodic cleanup
-        self.storage.cleanup_old()?;
+        Ok(())
+    }
 
+    fn cleanup_old(&self) -> Result<()> {
+        let report = self.storage.cleanup_old(&self.config.retention)?;
+        if report.total() > 0 || report.purged > 0 {
+            info!("Retention cleanup: {}", report);
+        }
         Ok(())
     }
 
@@ -126,25 +251,32 @@ pub fn is_running() -> Option<u32> {
     let pid_str = fs::read_to_string(&path).ok()?;
     let pid: u32 = pid_str.trim().parse().ok()?;
 
-    // Check if process exists
+    if process_alive(pid) {
+        return Some(pid);
+    }
+
+    // Stale PID file, remove it
+    let _ = fs::remove_file(&path);
+    None
+}
+
+pub(crate) fn process_alive(pid: u32) -> bool {
     #[cfg(unix)]
     {
-        use std::process::Command;
-        let status = Command::new("kill").args(["-0", &pid.to_string()]).status();
-        if status.map(|s| s.success()).unwrap_or(false) {
-            return Some(pid);
-        }
+        use std::process::{Command, Stdio};
+        let status = Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status();
+        status.map(|s| s.success()).unwrap_or(false)
     }
 
     #[cfg(windows)]
     {
         // On Windows, just trust the PID file for now
-        return Some(pid);
+        let _ = pid;
+        true
     }
-
-    // Stale PID file, remove it
-    let _ = fs::remove_file(&path);
-    None
 }
 
 pub fn stop() -> Result<()> {
@@ -0,0 +1,284 @@
+use crate::clipboard::{check_deps, is_wayland};
+use crate::config::{
+    config_path, db_path, journal_path, log_path, pid_path, snippets_path, Backend, Config,
+};
+use crate::daemon::process_alive;
+use crate::hotkey::find_keyboards;
+use crate::snippet::Snippets;
+use crate::storage::{FileStore, Storage};
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+
+/// How a check came out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Works, but something is off or won't work
+    Warn,
+    /// Keeps syo from working
+    Fail,
+}
+
+/// The outcome of one check, and what to do about it
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub check: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    fn ok(check: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(check: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(check: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+const INPUT_GROUP_FIX: &str =
+    "add yourself to the input group (sudo usermod -aG input $USER), then log out and back in";
+
+/// Check the environment and installation: display server and clipboard
+/// tools, keyboard and uinput access, config, history and daemon
+pub fn run() -> Vec<Finding> {
+    let mut findings = vec![display_server(), clipboard_tools()];
+    findings.extend(input_devices());
+    findings.push(uinput());
+    findings.extend(config_files());
+    findings.push(history());
+    findings.push(daemon());
+    findings.extend(daemon_log());
+    findings
+}
+
+fn display_server() -> Finding {
+    const CHECK: &str = "display server";
+    if is_wayland() {
+        Finding::ok(CHECK, "Wayland")
+    } else if std::env::var_os("DISPLAY").is_some() {
+        Finding::ok(CHECK, "X11")
+    } else {
+        Finding::fail(
+            CHECK,
+            "neither WAYLAND_DISPLAY nor DISPLAY is set",
+            "run syo inside your graphical session; for the systemd service, run \
+             `systemctl --user import-environment WAYLAND_DISPLAY DISPLAY`",
+        )
+    }
+}
+
+fn clipboard_tools() -> Finding {
+    const CHECK: &str = "clipboard tools";
+    match (check_deps(), is_wayland()) {
+        (Ok(()), true) => Finding::ok(CHECK, "wl-copy and wl-paste found"),
+        (Ok(()), false) => Finding::ok(CHECK, "xclip found"),
+        (Err(e), true) => Finding::fail(
+            CHECK,
+            e.to_string(),
+            "install wl-clipboard (sudo pacman -S wl-clipboard, sudo apt install wl-clipboard)",
+        ),
+        (Err(e), false) => Finding::fail(
+            CHECK,
+            e.to_string(),
+            "install xclip (sudo pacman -S xclip, sudo apt install xclip)",
+        ),
+    }
+}
+
+/// `/dev/input` access, and whether the hotkey listener finds keyboards
+fn input_devices() -> Vec<Finding> {
+    const CHECK: &str = "input devices";
+    let events: Vec<_> = match fs::read_dir("/dev/input") {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("event"))
+            })
+            .collect(),
+        Err(e) => {
+            return vec![Finding::fail(
+                CHECK,
+                format!("can't read /dev/input: {e}"),
+                "hotkeys need evdev; make sure /dev/input exists and is readable",
+            )];
+        }
+    };
+    let readable = events.iter().filter(|p| fs::File::open(p).is_ok()).count();
+    let devices = match readable {
+        0 => Finding::fail(
+            CHECK,
+            format!("none of {} event devices is readable", events.len()),
+            INPUT_GROUP_FIX,
+        ),
+        n => Finding::ok(
+            CHECK,
+            format!("{n} of {} event devices readable", events.len()),
+        ),
+    };
+
+    const KEYBOARDS: &str = "keyboards";
+    let keyboards = match find_keyboards() {
+        Ok(found) if !found.is_empty() => {
+            Finding::ok(KEYBOARDS, format!("{} found for hotkeys", found.len()))
+        }
+        Ok(_) => Finding::fail(KEYBOARDS, "no keyboard found for hotkeys", INPUT_GROUP_FIX),
+        Err(e) => Finding::fail(KEYBOARDS, e.to_string(), INPUT_GROUP_FIX),
+    };
+    vec![devices, keyboards]
+}
+
+fn uinput() -> Finding {
+    const CHECK: &str = "uinput";
+    let path = Path::new("/dev/uinput");
+    if !path.exists() {
+        return Finding::warn(
+            CHECK,
+            "/dev/uinput not found",
+            "load the module: sudo modprobe uinput",
+        );
+    }
+    match OpenOptions::new().write(true).open(path) {
+        Ok(_) => Finding::ok(CHECK, "/dev/uinput writable"),
+        Err(e) => Finding::warn(
+            CHECK,
+            format!("can't write /dev/uinput: {e}"),
+            "allow the input group with a udev rule: \
+             KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\"",
+        ),
+    }
+}
+
+fn config_files() -> Vec<Finding> {
+    let config = match Config::try_load() {
+        Ok(_) => Finding::ok("config", config_path().display().to_string()),
+        Err(e) => Finding::fail(
+            "config",
+            e.to_string(),
+            format!(
+                "fix the file, or move it away to start from the defaults: {}",
+                config_path().display()
+            ),
+        ),
+    };
+    let snippets = match Snippets::load() {
+        Ok(_) => Finding::ok("snippets", snippets_path().display().to_string()),
+        Err(e) => Finding::fail(
+            "snippets",
+            e.to_string(),
+            "fix the file; each line is `name = \"template\"`",
+        ),
+    };
+    vec![config, snippets]
+}
+
+/// Whether the history opens and, for SQLite, passes an integrity check.
+/// Encrypted entries aren't read, so no key is needed.
+fn history() -> Finding {
+    const CHECK: &str = "history";
+    let backend = Config::try_load().unwrap_or_default().storage.backend;
+    let path = match backend {
+        Backend::Sqlite => db_path(),
+        Backend::File => journal_path(),
+    };
+    if !path.exists() {
+        return Finding::ok(CHECK, "no history yet");
+    }
+    let restore = format!(
+        "restore a backup with `syo import`, or move {} away to start over",
+        path.display()
+    );
+    let problems = match backend {
+        Backend::Sqlite => Storage::integrity_check(&path),
+        Backend::File => FileStore::open(&path).map(|_| Vec::new()),
+    };
+    match problems {
+        Ok(problems) if problems.is_empty() => Finding::ok(CHECK, path.display().to_string()),
+        Ok(problems) => Finding::fail(
+            CHECK,
+            format!("integrity check failed: {}", problems.join("; ")),
+            restore,
+        ),
+        Err(e) => Finding::fail(CHECK, e.to_string(), restore),
+    }
+}
+
+fn daemon() -> Finding {
+    const CHECK: &str = "daemon";
+    let path = pid_path();
+    let pid = match fs::read_to_string(&path) {
+        Ok(pid) => pid,
+        Err(_) => {
+            return Finding::warn(
+                CHECK,
+                "not running",
+                "start it with `syo daemon` or `systemctl --user enable --now syo`",
+            );
+        }
+    };
+    match pid.trim().parse() {
+        Ok(pid) if process_alive(pid) => Finding::ok(CHECK, format!("running (pid {pid})")),
+        _ => Finding::warn(
+            CHECK,
+            format!("not running, stale PID file {}", path.display()),
+            "start it with `syo daemon`, which replaces the stale file",
+        ),
+    }
+}
+
+/// The last error in the daemon log, if any
+fn daemon_log() -> Option<Finding> {
+    let path = log_path();
+    let log = fs::read_to_string(&path).ok()?;
+    let error = last_error(&log)?;
+    Some(Finding::warn(
+        "daemon log",
+        error.to_string(),
+        format!("see {} for what led up to it", path.display()),
+    ))
+}
+
+fn last_error(log: &str) -> Option<&str> {
+    log.lines().rev().find(|line| line.contains("[ERROR]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_last_logged_error() {
+        let log = "10:00:00 [INFO] started\n\
+                   10:01:00 [ERROR] Hotkey listener error: no keyboard devices found\n\
+                   10:02:00 [WARN] Clipboard poll error: timeout\n";
+        assert_eq!(
+            last_error(log),
+            Some("10:01:00 [ERROR] Hotkey listener error: no keyboard devices found")
+        );
+        assert_eq!(last_error("10:00:00 [INFO] started\n"), None);
+    }
+}
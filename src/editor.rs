@@ -0,0 +1,86 @@
+use crate::config::{data_dir, session_dir};
+use crate::error::{Result, StickyError};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+/// Let the user edit `text` in `$EDITOR` (`vi` if unset) and return the
+/// result. The file is readable only by the user and removed afterwards.
+/// It lives in the session dir, which doesn't outlive a logout; without
+/// one, `secret` text is refused rather than written to persistent disk.
+pub fn edit_text(text: &str, secret: bool) -> Result<String> {
+    let dir = match session_dir() {
+        Some(dir) => dir,
+        None if secret => {
+            return Err(StickyError::Editor(
+                "XDG_RUNTIME_DIR is not set, so there's nowhere to edit encrypted \
+                 history without writing it to disk"
+                    .into(),
+            ))
+        }
+        None => data_dir(),
+    };
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("edit-{}.txt", std::process::id()));
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&path)?.write_all(text.as_bytes())?;
+
+    let edited = run_editor(&path).and_then(|()| Ok(fs::read_to_string(&path)?));
+    fs::remove_file(&path)?;
+    Ok(strip_added_newline(text, edited?))
+}
+
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("EDITOR")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    // Allow arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| StickyError::Editor(format!("{program}: {e}")))?;
+    if !status.success() {
+        return Err(StickyError::Editor(format!(
+            "{program} exited with {status}"
+        )));
+    }
+    Ok(())
+}
+
+/// Most editors end the file with a newline on save. Drop it unless the
+/// original text had one.
+fn strip_added_newline(original: &str, mut edited: String) -> String {
+    if !original.ends_with('\n') && edited.ends_with('\n') {
+        edited.pop();
+        if edited.ends_with('\r') {
+            edited.pop();
+        }
+    }
+    edited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_only_the_added_newline() {
+        assert_eq!(strip_added_newline("a", "b\n".into()), "b");
+        assert_eq!(strip_added_newline("a", "b\r\n".into()), "b");
+        assert_eq!(strip_added_newline("a\n", "b\n".into()), "b\n");
+        assert_eq!(strip_added_newline("a", "b\n\n".into()), "b\n");
+        assert_eq!(strip_added_newline("a", "b".into()), "b");
+    }
+}
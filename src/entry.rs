@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use url::Url;
 
+/// Characters of text kept in an `EntrySummary`
+pub const PREVIEW_CHARS: usize = 1024;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ContentType {
     Text,
@@ -35,7 +38,18 @@ pub struct Entry {
     pub content: Option<String>,
     pub image_data: Option<Vec<u8>>,
     pub hash: String,
+    /// When this content was first copied
     pub created_at: i64,
+    /// When this content was most recently copied
+    pub last_seen: i64,
+    /// How many times this content was copied
+    pub copy_count: i64,
+    pub pinned: bool,
+    /// How many times this entry was retrieved from history
+    pub use_count: i64,
+    /// When this entry was last retrieved from history
+    pub last_used: Option<i64>,
+    pub tags: Vec<String>,
 }
 
 impl Entry {
@@ -46,6 +60,7 @@ impl Entry {
             ContentType::Text
         };
         let hash = hash_content(text.as_bytes());
+        let now = chrono::Utc::now().timestamp();
 
         Self {
             id: 0,
@@ -53,12 +68,19 @@ impl Entry {
             content: Some(text),
             image_data: None,
             hash,
-            created_at: chrono::Utc::now().timestamp(),
+            created_at: now,
+            last_seen: now,
+            copy_count: 1,
+            pinned: false,
+            use_count: 0,
+            last_used: None,
+            tags: Vec::new(),
         }
     }
 
     pub fn new_image(data: Vec<u8>) -> Self {
         let hash = hash_content(&data);
+        let now = chrono::Utc::now().timestamp();
 
         Self {
             id: 0,
@@ -66,37 +88,116 @@ impl Entry {
             content: None,
             image_data: Some(data),
             hash,
-            created_at: chrono::Utc::now().timestamp(),
+            created_at: now,
+            last_seen: now,
+            copy_count: 1,
+            pinned: false,
+            use_count: 0,
+            last_used: None,
+            tags: Vec::new(),
         }
     }
 
     pub fn display_preview(&self, max_len: usize) -> String {
         match self.content_type {
             ContentType::Text | ContentType::Link => {
-                let text = self.content.as_deref().unwrap_or("");
-                // Collapse whitespace/newlines to single space
-                let collapsed: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
-                // Safe truncation at char boundary
-                if collapsed.len() > max_len {
-                    let truncated: String = collapsed.chars().take(max_len).collect();
-                    format!("{}...", truncated)
-                } else {
-                    collapsed
-                }
-            }
-            ContentType::Image => {
-                let size = self.image_data.as_ref().map(|d| d.len()).unwrap_or(0);
-                format!("[Image: {} bytes]", size)
+                preview_text(self.content.as_deref().unwrap_or(""), max_len)
             }
+            ContentType::Image => format!("[Image: {} bytes]", self.size()),
+        }
+    }
+
+    /// Payload size in bytes
+    pub fn size(&self) -> usize {
+        match (&self.image_data, &self.content) {
+            (Some(data), _) => data.len(),
+            (None, Some(text)) => text.len(),
+            (None, None) => 0,
+        }
+    }
+
+    pub fn summary(&self) -> EntrySummary {
+        EntrySummary {
+            id: self.id,
+            content_type: self.content_type,
+            preview: self
+                .content
+                .as_deref()
+                .map(|text| text.chars().take(PREVIEW_CHARS).collect())
+                .unwrap_or_default(),
+            size: self.size() as i64,
+            created_at: self.created_at,
+            last_seen: self.last_seen,
+            copy_count: self.copy_count,
+            pinned: self.pinned,
+            use_count: self.use_count,
+            last_used: self.last_used,
+            tags: self.tags.clone(),
         }
     }
 }
 
+/// An entry without its payload, for listings. Text is cut down to the
+/// first `PREVIEW_CHARS` characters and images only carry their size; load
+/// the full entry with `Storage::get_by_id`.
+#[derive(Debug, Clone)]
+pub struct EntrySummary {
+    pub id: i64,
+    pub content_type: ContentType,
+    /// Start of the text, empty for images
+    pub preview: String,
+    /// Payload size in bytes
+    pub size: i64,
+    pub created_at: i64,
+    pub last_seen: i64,
+    pub copy_count: i64,
+    pub pinned: bool,
+    pub use_count: i64,
+    pub last_used: Option<i64>,
+    pub tags: Vec<String>,
+}
+
+impl EntrySummary {
+    pub fn display_preview(&self, max_len: usize) -> String {
+        match self.content_type {
+            ContentType::Text | ContentType::Link => preview_text(&self.preview, max_len),
+            ContentType::Image => format!("[Image: {} bytes]", self.size),
+        }
+    }
+}
+
+/// `text` on one line, cut to `max_len` characters
+pub fn preview_text(text: &str, max_len: usize) -> String {
+    // Collapse whitespace/newlines to single space
+    let collapsed: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    // Safe truncation at char boundary
+    if collapsed.len() > max_len {
+        let truncated: String = collapsed.chars().take(max_len).collect();
+        format!("{}...", truncated)
+    } else {
+        collapsed
+    }
+}
+
+/// Normalize a tag name: trims, strips a leading `#` and lowercases.
+/// Returns `None` for empty names or names with unsupported characters.
+pub fn normalize_tag(name: &str) -> Option<String> {
+    let tag = name.trim().trim_start_matches('#').to_lowercase();
+    if tag.is_empty()
+        || !tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    Some(tag)
+}
+
 fn is_url(text: &str) -> bool {
     Url::parse(text.trim()).is_ok()
 }
 
-fn hash_content(data: &[u8]) -> String {
+pub(crate) fn hash_content(data: &[u8]) -> String {
     let mut hasher = Sha256::new();
     hasher.update(data);
     format!("{:x}", hasher.finalize())
@@ -161,6 +262,18 @@ mod tests {
         assert_eq!(e.display_preview(80), "[Image: 100 bytes]");
     }
 
+    #[test]
+    fn summary_truncates_text() {
+        let e = Entry::new_text("é".repeat(PREVIEW_CHARS + 10));
+        let summary = e.summary();
+        assert_eq!(summary.preview.chars().count(), PREVIEW_CHARS);
+        assert_eq!(summary.size, e.content.unwrap().len() as i64);
+
+        let image = Entry::new_image(vec![0; 100]).summary();
+        assert!(image.preview.is_empty());
+        assert_eq!(image.display_preview(80), "[Image: 100 bytes]");
+    }
+
     #[test]
     fn hash_deterministic() {
         let h1 = hash_content(b"test data");
@@ -175,6 +288,18 @@ mod tests {
         assert_ne!(h1, h2);
     }
 
+    #[test]
+    fn normalize_tag_cleans_input() {
+        assert_eq!(normalize_tag(" #Work ").as_deref(), Some("work"));
+        assert_eq!(
+            normalize_tag("sql_snippets").as_deref(),
+            Some("sql_snippets")
+        );
+        assert_eq!(normalize_tag(""), None);
+        assert_eq!(normalize_tag("a,b"), None);
+        assert_eq!(normalize_tag("two words"), None);
+    }
+
     #[test]
     fn content_type_roundtrip() {
         for ct in [ContentType::Text, ContentType::Link, ContentType::Image] {
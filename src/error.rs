@@ -23,17 +23,86 @@ pub enum StickyError {
     #[error("Entry not found: {0}")]
     NotFound(i64),
 
+    #[error("Tag not found: {0}")]
+    TagNotFound(String),
+
+    #[error("Not in the trash: {0}")]
+    NotInTrash(i64),
+
+    #[error("Entry {0} is an image and can't be edited")]
+    NotText(i64),
+
+    #[error("Editor error: {0}")]
+    Editor(String),
+
+    #[error("Snippet error: {0}")]
+    Snippet(String),
+
+    #[error("Invalid register: {0} (registers are a-z)")]
+    InvalidRegister(String),
+
+    #[error("Register {0} is empty")]
+    EmptyRegister(char),
+
+    #[error("Queue mode is off (start it with `syo queue start`)")]
+    NotQueueing,
+
+    #[error("Queue error: {0}")]
+    Queue(String),
+
+    #[error("Collector error: {0}")]
+    Collector(String),
+
+    #[error("Invalid profile name: {0:?}")]
+    InvalidProfile(String),
+
     #[error("Image too large: {size} bytes (max: {max})")]
     ImageTooLarge { size: usize, max: usize },
 
     #[error("Hotkey error: {0}")]
     Hotkey(String),
 
+    #[error("Invalid config: {0}")]
+    Config(String),
+
     #[error("Missing dependency: {0}. Install it and try again.")]
     MissingDep(String),
 
     #[error("Invalid image: {0}")]
     InvalidImage(String),
+
+    #[error("Invalid tag: {0:?} (use letters, digits, '-' or '_')")]
+    InvalidTag(String),
+
+    #[error("Archive error: {0}")]
+    Archive(String),
+
+    #[error(
+        "Deleted, but another process kept reading the history, so its write-ahead log \
+         still holds the deleted rows. Run the command again once it's done"
+    )]
+    WalBusy,
+
+    #[error("History is locked. Run `syo unlock` first")]
+    Locked,
+
+    #[error("Encryption error: {0}")]
+    Crypto(String),
+
+    #[error("Blob error: {0}")]
+    Blob(String),
+
+    #[error("Import error: {0}")]
+    Import(String),
+
+    #[error("History log error: {0}")]
+    Journal(String),
+
+    #[error("Not supported by this storage backend: {0}")]
+    Unsupported(String),
+
+    #[error("Not confirmed; pass --yes to skip the prompt")]
+    NotConfirmed,
 }
 
 pub type Result<T> = std::result::Result<T, StickyError>;
@@ -1,12 +1,15 @@
 use crate::clipboard::write_entry;
-use crate::entry::{ContentType, Entry};
-use crate::storage::Storage;
+use crate::config::{self, list_profiles, profile_label, profile_name};
+use crate::entry::{normalize_tag, preview_text, ContentType, Entry, EntrySummary};
+use crate::snippet::{self, Answers, Snippets};
+use crate::storage::{self, Cursor, EntryFilter, SortOrder};
 use iced::keyboard::{self, Key, Modifiers};
 use iced::widget::{column, container, row, scrollable, text, text_input, Column};
 use iced::{event, Color, Element, Event, Length, Task as Command};
 use iced_layershell::reexport::{Anchor, KeyboardInteractivity, Layer};
 use iced_layershell::settings::{LayerShellSettings, Settings};
 use iced_layershell::to_layer_message;
+use std::collections::HashSet;
 
 const MAX_ENTRIES: usize = 50;
 const PREVIEW_LEN: usize = 60;
@@ -30,41 +33,198 @@ pub fn run_popup() -> Result<(), iced_layershell::Error> {
 #[derive(Default)]
 struct PopupState {
     search: String,
-    entries: Vec<Entry>,
+    entries: Vec<EntrySummary>,
     filtered: Vec<usize>,
+    /// Name and template of each snippet, listed after the history
+    snippets: Vec<(String, String)>,
+    filtered_snippets: Vec<usize>,
     selected: usize,
+    /// Set while asking for a chosen snippet's `{input:...}` values
+    prompting: Option<Prompting>,
+    profile: String,
+    profiles: Vec<String>,
+    /// Whether older entries can be loaded past those shown
+    more: bool,
+    /// Where the next page of older entries starts
+    next_page: Option<Cursor>,
+    /// The text query `entries` were loaded for, if any
+    searched: Option<String>,
+    /// Why the last action failed, shown under the list
+    error: Option<String>,
+}
+
+struct Prompting {
+    template: String,
+    prompts: Vec<String>,
+    answers: Vec<String>,
 }
 
 impl PopupState {
     fn new() -> Self {
-        let entries = Storage::open()
-            .and_then(|s| s.list(MAX_ENTRIES))
+        let mut state = Self {
+            profile: profile_label(),
+            profiles: list_profiles(),
+            ..Default::default()
+        };
+        state.load();
+        state
+    }
+
+    /// Read the active profile's history and snippets
+    fn load(&mut self) {
+        self.load_entries();
+        self.snippets = Snippets::load()
+            .map(|s| {
+                s.iter()
+                    .map(|(name, body)| (name.to_string(), body.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.filter_entries();
+    }
+
+    /// Read the top-ranked entries, or with a text query the first page of
+    /// those matching it
+    fn load_entries(&mut self) {
+        self.next_page = None;
+        let Some(query) = self.searched.clone() else {
+            let entries = storage::open()
+                .and_then(|s| {
+                    let mut entries = s.list_pinned()?;
+                    entries.extend(
+                        s.list_sorted(MAX_ENTRIES, SortOrder::Frecency)?
+                            .into_iter()
+                            .filter(|e| !e.pinned),
+                    );
+                    Ok(entries)
+                })
+                .unwrap_or_default();
+            // Fewer than a page means everything is there already
+            self.more = entries.len() >= MAX_ENTRIES;
+            self.entries = entries;
+            return;
+        };
+        // Matched by storage against the full text, not just the preview
+        let filter = EntryFilter {
+            query: Some(query),
+            ..Default::default()
+        };
+        let page = storage::open()
+            .and_then(|s| s.list_page(None, MAX_ENTRIES, &filter))
             .unwrap_or_default();
+        self.more = page.next.is_some();
+        self.next_page = page.next;
+        self.entries = page.entries;
+        self.entries.sort_by_key(|e| !e.pinned);
+    }
 
-        let filtered: Vec<usize> = (0..entries.len()).collect();
+    /// Append the next page of entries, most recent first, past the ones
+    /// ranked at the top
+    fn load_more(&mut self) {
+        if self.prompting.is_some() {
+            return;
+        }
+        let Ok(storage) = storage::open() else {
+            return;
+        };
+        let filter = EntryFilter {
+            query: self.searched.clone(),
+            ..Default::default()
+        };
+        let before = self.entries.len();
+        // The first pages mostly repeat the top-ranked entries
+        while self.more && self.entries.len() == before {
+            let Ok(page) = storage.list_page(self.next_page, MAX_ENTRIES, &filter) else {
+                return;
+            };
+            self.more = page.next.is_some();
+            self.next_page = page.next;
+            let shown: HashSet<i64> = self.entries.iter().map(|e| e.id).collect();
+            self.entries
+                .extend(page.entries.into_iter().filter(|e| !shown.contains(&e.id)));
+        }
+        let selected = self.selected;
+        self.filter_entries();
+        self.selected = selected;
+    }
 
-        Self {
-            search: String::new(),
-            entries,
-            filtered,
-            selected: 0,
+    /// Load more entries once the selection nears the end of those loaded
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.rows() {
+            self.selected += 1;
+        }
+        if self.more && self.selected + 5 >= self.filtered.len() {
+            self.load_more();
         }
     }
 
+    /// Move on to the next profile. It becomes the current one for the
+    /// daemon as well, like `syo profile use`.
+    fn next_profile(&mut self) {
+        if self.profiles.len() < 2 || self.prompting.is_some() {
+            return;
+        }
+        let i = self
+            .profiles
+            .iter()
+            .position(|p| *p == self.profile)
+            .map_or(0, |i| (i + 1) % self.profiles.len());
+        let Ok(profile) = profile_name(&self.profiles[i]) else {
+            return;
+        };
+        // The daemon follows the saved profile: don't show one it won't use
+        if let Err(e) = config::save_current_profile(profile.as_deref()) {
+            self.error = Some(format!("Can't switch profile: {e}"));
+            return;
+        }
+        self.error = None;
+        config::use_profile(profile);
+        self.profile = self.profiles[i].clone();
+        self.load();
+    }
+
+    /// Visible rows: matching entries, then matching snippets
+    fn rows(&self) -> usize {
+        self.filtered.len() + self.filtered_snippets.len()
+    }
+
     fn filter_entries(&mut self) {
+        if self.prompting.is_some() {
+            return;
+        }
+        // `#tag` words filter by tag, the rest is a substring query
+        let (tags, words): (Vec<&str>, Vec<&str>) = self
+            .search
+            .split_whitespace()
+            .partition(|w| w.starts_with('#'));
+        let tags: Vec<String> = tags.into_iter().filter_map(normalize_tag).collect();
+        let query = words.join(" ").to_lowercase();
+        let searched = Some(query.clone()).filter(|q| !q.is_empty());
+        if searched != self.searched {
+            self.searched = searched;
+            self.load_entries();
+        }
+
         if self.search.is_empty() {
             self.filtered = (0..self.entries.len()).collect();
+            self.filtered_snippets = (0..self.snippets.len()).collect();
         } else {
-            let query = self.search.to_lowercase();
             self.filtered = self
                 .entries
                 .iter()
                 .enumerate()
-                .filter(|(_, e)| {
-                    e.content
-                        .as_ref()
-                        .map(|c| c.to_lowercase().contains(&query))
-                        .unwrap_or(false)
+                .filter(|(_, e)| tags.iter().all(|t| e.tags.contains(t)))
+                .map(|(i, _)| i)
+                .collect();
+
+            // Snippets have no tags, and match by name or body
+            self.filtered_snippets = self
+                .snippets
+                .iter()
+                .enumerate()
+                .filter(|_| tags.is_empty())
+                .filter(|(_, (name, body))| {
+                    name.to_lowercase().contains(&query) || body.to_lowercase().contains(&query)
                 })
                 .map(|(i, _)| i)
                 .collect();
@@ -72,15 +232,92 @@ impl PopupState {
         self.selected = 0;
     }
 
-    fn selected_entry(&self) -> Option<&Entry> {
+    fn selected_entry(&self) -> Option<&EntrySummary> {
         self.filtered
             .get(self.selected)
             .and_then(|&i| self.entries.get(i))
     }
 
-    fn confirm_selection(&self) {
-        if let Some(entry) = self.selected_entry() {
-            let _ = write_entry(entry);
+    fn toggle_pin(&mut self) {
+        let Some(&idx) = self.filtered.get(self.selected) else {
+            return;
+        };
+        let entry = &mut self.entries[idx];
+        let pinned = !entry.pinned;
+        if storage::open()
+            .and_then(|s| s.set_pinned(entry.id, pinned))
+            .is_err()
+        {
+            return;
+        }
+        entry.pinned = pinned;
+        let id = entry.id;
+
+        // Keep pinned entries grouped at the top, preserving recency order
+        self.entries.sort_by_key(|e| !e.pinned);
+        self.filter_entries();
+        if let Some(pos) = self.filtered.iter().position(|&i| self.entries[i].id == id) {
+            self.selected = pos;
+        }
+    }
+
+    fn delete_selected(&mut self) {
+        let Some(&idx) = self.filtered.get(self.selected) else {
+            return;
+        };
+        let id = self.entries[idx].id;
+        if storage::open().and_then(|s| s.delete(id)).is_err() {
+            return;
+        }
+        self.entries.remove(idx);
+
+        // Stay on the same row, now holding the next entry
+        let selected = self.selected;
+        self.filter_entries();
+        self.selected = selected.min(self.filtered.len().saturating_sub(1));
+    }
+
+    fn confirm_selection(&mut self) {
+        if let Some(prompting) = &mut self.prompting {
+            prompting.answers.push(std::mem::take(&mut self.search));
+            if prompting.answers.len() < prompting.prompts.len() {
+                return;
+            }
+            let answers = std::mem::take(&mut prompting.answers);
+            let template = std::mem::take(&mut prompting.template);
+            copy_snippet(&template, Answers(answers.into()));
+            std::process::exit(0);
+        }
+
+        if let Some(&idx) = self
+            .selected
+            .checked_sub(self.filtered.len())
+            .and_then(|i| self.filtered_snippets.get(i))
+        {
+            let template = self.snippets[idx].1.clone();
+            let prompts = snippet::prompts(&template);
+            if prompts.is_empty() {
+                copy_snippet(&template, Answers(Default::default()));
+                std::process::exit(0);
+            }
+            self.search.clear();
+            self.prompting = Some(Prompting {
+                template,
+                prompts,
+                answers: Vec::new(),
+            });
+            return;
+        }
+
+        if let Some(summary) = self.selected_entry() {
+            // Only the chosen entry's full content is ever loaded
+            if let Ok(storage) = storage::open() {
+                if let Ok(entry) = storage.get_by_id(summary.id) {
+                    if write_entry(&entry).is_ok() {
+                        let _ = storage.record_use(entry.id);
+                    }
+                }
+            }
         }
         std::process::exit(0);
     }
@@ -90,6 +327,12 @@ impl PopupState {
     }
 }
 
+fn copy_snippet(template: &str, mut answers: Answers) {
+    if let Ok(text) = snippet::expand(template, &mut answers) {
+        let _ = write_entry(&Entry::new_text(text));
+    }
+}
+
 #[to_layer_message]
 #[derive(Debug, Clone)]
 enum Message {
@@ -98,6 +341,7 @@ enum Message {
     SelectPrev,
     Confirm,
     Cancel,
+    Scrolled(scrollable::Viewport),
     IcedEvent(Event),
 }
 
@@ -117,9 +361,7 @@ fn update(state: &mut PopupState, message: Message) -> Command<Message> {
             Command::none()
         }
         Message::SelectNext => {
-            if !state.filtered.is_empty() && state.selected < state.filtered.len() - 1 {
-                state.selected += 1;
-            }
+            state.select_next();
             Command::none()
         }
         Message::SelectPrev => {
@@ -136,6 +378,12 @@ fn update(state: &mut PopupState, message: Message) -> Command<Message> {
             state.cancel();
             Command::none()
         }
+        Message::Scrolled(viewport) => {
+            if state.more && viewport.relative_offset().y > 0.9 {
+                state.load_more();
+            }
+            Command::none()
+        }
         Message::IcedEvent(Event::Keyboard(keyboard::Event::KeyPressed {
             key, modifiers, ..
         })) => handle_key(state, key, modifiers),
@@ -143,16 +391,26 @@ fn update(state: &mut PopupState, message: Message) -> Command<Message> {
     }
 }
 
-fn handle_key(state: &mut PopupState, key: Key, _modifiers: Modifiers) -> Command<Message> {
+fn handle_key(state: &mut PopupState, key: Key, modifiers: Modifiers) -> Command<Message> {
     match key {
+        Key::Character(c) if modifiers.control() && c.as_str() == "p" => {
+            state.toggle_pin();
+            Command::none()
+        }
+        Key::Character(c) if modifiers.control() && c.as_str() == "d" => {
+            state.delete_selected();
+            Command::none()
+        }
+        Key::Character(c) if modifiers.control() && c.as_str() == "o" => {
+            state.next_profile();
+            Command::none()
+        }
         Key::Named(keyboard::key::Named::Escape) => {
             state.cancel();
             Command::none()
         }
         Key::Named(keyboard::key::Named::ArrowDown) => {
-            if !state.filtered.is_empty() && state.selected < state.filtered.len() - 1 {
-                state.selected += 1;
-            }
+            state.select_next();
             Command::none()
         }
         Key::Named(keyboard::key::Named::ArrowUp) => {
@@ -170,30 +428,75 @@ fn handle_key(state: &mut PopupState, key: Key, _modifiers: Modifiers) -> Comman
 }
 
 fn view(state: &PopupState) -> Element<'_, Message> {
-    let search_input = text_input("Search...", &state.search)
+    let placeholder = match &state.prompting {
+        Some(p) => p.prompts[p.answers.len()].as_str(),
+        None => "Search... (#tag to filter)",
+    };
+    let search_input = text_input(placeholder, &state.search)
         .on_input(Message::SearchChanged)
         .padding(10)
         .size(16);
 
-    let entries_list: Column<Message> =
-        state
-            .filtered
-            .iter()
-            .enumerate()
-            .fold(Column::new().spacing(2), |col, (i, &entry_idx)| {
-                let entry = &state.entries[entry_idx];
-                let is_selected = i == state.selected;
-                col.push(entry_row(entry, is_selected))
-            });
+    let has_pinned = state
+        .filtered
+        .first()
+        .is_some_and(|&i| state.entries[i].pinned);
 
-    let content = column![
+    let entries_list: Column<Message> = state.filtered.iter().enumerate().fold(
+        Column::new().spacing(2),
+        |mut col, (i, &entry_idx)| {
+            let entry = &state.entries[entry_idx];
+            if has_pinned && i == 0 {
+                col = col.push(section_header("Pinned"));
+            }
+            if has_pinned && !entry.pinned && state.entries[state.filtered[i - 1]].pinned {
+                col = col.push(section_header("Recent"));
+            }
+            let is_selected = i == state.selected;
+            col.push(entry_row(entry, is_selected))
+        },
+    );
+    let entries_list = if state.filtered_snippets.is_empty() || state.prompting.is_some() {
+        entries_list
+    } else {
+        state.filtered_snippets.iter().enumerate().fold(
+            entries_list.push(section_header("Snippets")),
+            |col, (i, &idx)| {
+                let (name, body) = &state.snippets[idx];
+                let is_selected = state.filtered.len() + i == state.selected;
+                col.push(list_row(
+                    "SNP",
+                    preview_text(body, PREVIEW_LEN),
+                    name.clone(),
+                    is_selected,
+                ))
+            },
+        )
+    };
+
+    let mut content = column![
         search_input,
         scrollable(entries_list)
+            .on_scroll(Message::Scrolled)
             .height(Length::Fill)
             .width(Length::Fill),
     ]
     .spacing(10)
     .padding(15);
+    if let Some(error) = &state.error {
+        content = content.push(
+            text(error.clone())
+                .size(12)
+                .color(Color::from_rgb(0.9, 0.4, 0.4)),
+        );
+    }
+    // Only worth a line once there's more than one
+    if state.profiles.len() > 1 {
+        content = content.push(section_header(&format!(
+            "Profile: {} (Ctrl+O to switch)",
+            state.profile
+        )));
+    }
 
     container(content)
         .width(Length::Fill)
@@ -210,14 +513,42 @@ fn view(state: &PopupState) -> Element<'_, Message> {
         .into()
 }
 
-fn entry_row(entry: &Entry, selected: bool) -> Element<'static, Message> {
-    let type_badge = match entry.content_type {
-        ContentType::Text => text("TXT").size(10),
-        ContentType::Link => text("URL").size(10),
-        ContentType::Image => text("IMG").size(10),
+fn section_header(label: &str) -> Element<'static, Message> {
+    container(
+        text(label.to_string())
+            .size(11)
+            .color(Color::from_rgb(0.6, 0.6, 0.65)),
+    )
+    .padding([4, 8])
+    .into()
+}
+
+fn entry_row(entry: &EntrySummary, selected: bool) -> Element<'static, Message> {
+    let badge = match entry.content_type {
+        ContentType::Text => "TXT",
+        ContentType::Link => "URL",
+        ContentType::Image => "IMG",
     };
+    let tags = entry
+        .tags
+        .iter()
+        .map(|t| format!("#{t}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    list_row(badge, entry.display_preview(PREVIEW_LEN), tags, selected)
+}
 
-    let preview = text(entry.display_preview(PREVIEW_LEN)).size(14);
+/// A row with a type badge, a preview and a dimmer note (tags, or a
+/// snippet's name)
+fn list_row(
+    badge: &'static str,
+    preview: String,
+    note: String,
+    selected: bool,
+) -> Element<'static, Message> {
+    let type_badge = text(badge).size(10);
+    let preview = text(preview).size(14);
+    let tags = text(note).size(11).color(Color::from_rgb(0.45, 0.6, 0.85));
 
     let bg_color = if selected {
         Color::from_rgb(0.2, 0.25, 0.35)
@@ -237,6 +568,7 @@ fn entry_row(entry: &Entry, selected: bool) -> Element<'static, Message> {
                 ..Default::default()
             }),
         preview,
+        tags,
     ]
     .spacing(10)
     .align_y(iced::Alignment::Center);
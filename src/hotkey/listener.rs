@@ -1,33 +1,81 @@
-use crate::config::HotkeyConfig;
+use crate::config::{BindingAction, HotkeyConfig, QueueStep};
 use crate::error::{Result, StickyError};
+use crate::storage::register_name;
 use evdev::{Device, EventSummary, EventType, KeyCode};
 use std::collections::HashSet;
 use std::path::Path;
 use tokio::sync::mpsc;
 
-pub struct HotkeyListener {
+/// What a hotkey does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    Popup,
+    /// Copy a register to the clipboard
+    Register(char),
+    /// Put the next queued item on the clipboard
+    QueueNext,
+}
+
+struct Hotkey {
     modifiers: HashSet<KeyCode>,
     trigger_key: KeyCode,
+    action: HotkeyAction,
 }
 
-impl HotkeyListener {
-    pub fn new(config: &HotkeyConfig) -> Result<Self> {
-        let modifiers = config.modifier_keys();
-        let trigger_key = config
-            .trigger_key()
-            .ok_or_else(|| StickyError::Hotkey(format!("invalid trigger key: {}", config.key)))?;
+impl Hotkey {
+    fn new(
+        modifiers: HashSet<KeyCode>,
+        trigger_key: Option<KeyCode>,
+        key: &str,
+        action: HotkeyAction,
+    ) -> Result<Self> {
+        let trigger_key = trigger_key
+            .ok_or_else(|| StickyError::Hotkey(format!("invalid trigger key: {key}")))?;
 
         if modifiers.is_empty() {
-            return Err(StickyError::Hotkey("no valid modifiers configured".into()));
+            return Err(StickyError::Hotkey(format!(
+                "no valid modifiers configured for {key}"
+            )));
         }
 
         Ok(Self {
             modifiers,
             trigger_key,
+            action,
         })
     }
+}
+
+pub struct HotkeyListener {
+    hotkeys: Vec<Hotkey>,
+}
+
+impl HotkeyListener {
+    pub fn new(config: &HotkeyConfig) -> Result<Self> {
+        let mut hotkeys = vec![Hotkey::new(
+            config.modifier_keys(),
+            config.trigger_key(),
+            &config.key,
+            HotkeyAction::Popup,
+        )?];
+        for binding in &config.bindings {
+            let action = match binding.action {
+                BindingAction::Register(name) => HotkeyAction::Register(
+                    register_name(name).map_err(|e| StickyError::Hotkey(e.to_string()))?,
+                ),
+                BindingAction::Queue(QueueStep::Next) => HotkeyAction::QueueNext,
+            };
+            hotkeys.push(Hotkey::new(
+                binding.modifier_keys(),
+                binding.trigger_key(),
+                &binding.key,
+                action,
+            )?);
+        }
+        Ok(Self { hotkeys })
+    }
 
-    pub async fn listen(&self, tx: mpsc::Sender<()>) -> Result<()> {
+    pub async fn listen(&self, tx: mpsc::Sender<HotkeyAction>) -> Result<()> {
         let devices = find_keyboards()?;
         if devices.is_empty() {
             return Err(StickyError::Hotkey(
@@ -51,17 +99,20 @@ impl HotkeyListener {
         let mut pressed: HashSet<KeyCode> = HashSet::new();
 
         while let Some((key, is_press)) = event_rx.recv().await {
-            if is_press {
-                pressed.insert(key);
-            } else {
+            if !is_press {
                 pressed.remove(&key);
+                continue;
             }
-
-            if key == self.trigger_key
-                && is_press
-                && self.modifiers.iter().all(|m| pressed.contains(m))
-            {
-                let _ = tx.send(()).await;
+            pressed.insert(key);
+
+            // Alt+Shift+1 also holds Alt+1: the most specific hotkey wins
+            let hotkey = self
+                .hotkeys
+                .iter()
+                .filter(|h| h.trigger_key == key && h.modifiers.iter().all(|m| pressed.contains(m)))
+                .max_by_key(|h| h.modifiers.len());
+            if let Some(hotkey) = hotkey {
+                let _ = tx.send(hotkey.action).await;
             }
         }
 
@@ -69,7 +120,7 @@ impl HotkeyListener {
     }
 }
 
-fn find_keyboards() -> Result<Vec<Device>> {
+pub(crate) fn find_keyboards() -> Result<Vec<Device>> {
     let mut keyboards = Vec::new();
     let input_dir = Path::new("/dev/input");
 
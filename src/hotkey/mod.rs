@@ -1,3 +1,4 @@
 mod listener;
 
-pub use listener::HotkeyListener;
+pub(crate) use listener::find_keyboards;
+pub use listener::{HotkeyAction, HotkeyListener};
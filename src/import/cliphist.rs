@@ -0,0 +1,282 @@
+use super::Parsed;
+
+/// cliphist keeps every item in this bucket, keyed by a big-endian u64 id
+const BUCKET: &[u8] = b"b";
+
+const MAGIC: u32 = 0xED0C_DAED;
+const PAGE_HEADER_LEN: usize = 16;
+const ELEMENT_LEN: usize = 16;
+const BUCKET_HEADER_LEN: usize = 16;
+const BRANCH_PAGE: u16 = 0x01;
+const LEAF_PAGE: u16 = 0x02;
+const BUCKET_LEAF: u32 = 0x01;
+/// Deeper trees than this mean a corrupt (or cyclic) file
+const MAX_DEPTH: usize = 32;
+
+/// cliphist stores raw clipboard bytes in a bbolt database, oldest first
+pub(super) fn parse(data: &[u8]) -> Result<Parsed, String> {
+    let db = Bolt::open(data)?;
+    let root = db.bucket_root(db.root, BUCKET)?;
+
+    let mut items = Vec::new();
+    db.walk(&root, 0, &mut |key, value, flags| {
+        if flags & BUCKET_LEAF == 0 {
+            items.push((key.to_vec(), value.to_vec()));
+        }
+        Ok(())
+    })?;
+    // Keys are big-endian ids, so byte order is insertion order
+    items.sort();
+
+    let mut parsed = Parsed::default();
+    for (_, value) in items {
+        parsed.push_bytes(value);
+    }
+    Ok(parsed)
+}
+
+/// Read-only view of a bbolt file: fixed-size pages, two meta pages at the
+/// start, and a B+tree of buckets. Integers are little-endian.
+struct Bolt<'a> {
+    data: &'a [u8],
+    page_size: usize,
+    root: Node<'a>,
+}
+
+/// A tree node: a page of the file, or a bucket's inline page
+#[derive(Clone, Copy)]
+struct Node<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Bolt<'a> {
+    fn open(data: &'a [u8]) -> Result<Self, String> {
+        let meta = |offset: usize| -> Option<(u64, usize, u64)> {
+            let m = data
+                .get(offset.checked_add(PAGE_HEADER_LEN)?..)?
+                .get(..64)?;
+            if le_u32(m, 0).ok()? != MAGIC {
+                return None;
+            }
+            let page_size = le_u32(m, 8).ok()? as usize;
+            let root = le_u64(m, 16).ok()?;
+            let txid = le_u64(m, 48).ok()?;
+            Some((txid, page_size, root))
+        };
+
+        // The second meta page sits one page in; read the first for its size
+        let first = meta(0);
+        let second = first.and_then(|(_, page_size, _)| meta(page_size));
+        let (_, page_size, root) = match (first, second) {
+            (Some(a), Some(b)) => a.max(b),
+            (Some(m), None) | (None, Some(m)) => m,
+            (None, None) => return Err("not a bbolt database".into()),
+        };
+        if page_size < PAGE_HEADER_LEN {
+            return Err(format!("invalid page size {page_size}"));
+        }
+
+        let mut db = Self {
+            data,
+            page_size,
+            root: Node { buf: &[] },
+        };
+        db.root = db.page(root)?;
+        Ok(db)
+    }
+
+    fn page(&self, id: u64) -> Result<Node<'a>, String> {
+        let out_of_range = || format!("page {id} out of range");
+        let start = usize::try_from(id)
+            .ok()
+            .and_then(|id| id.checked_mul(self.page_size))
+            .ok_or_else(out_of_range)?;
+        let header = start
+            .checked_add(PAGE_HEADER_LEN)
+            .and_then(|end| self.data.get(start..end))
+            .ok_or_else(out_of_range)?;
+        let overflow = le_u32(header, 12)? as usize;
+        let end = overflow
+            .checked_add(1)
+            .and_then(|pages| pages.checked_mul(self.page_size))
+            .and_then(|len| start.checked_add(len))
+            .ok_or_else(out_of_range)?;
+        Ok(Node {
+            buf: &self.data[start..end.min(self.data.len())],
+        })
+    }
+
+    /// Find a bucket in the tree rooted at `node` and return its own root
+    fn bucket_root(&self, node: Node<'a>, name: &[u8]) -> Result<Node<'a>, String> {
+        let mut found = None;
+        self.walk(&node, 0, &mut |key, value, flags| {
+            if flags & BUCKET_LEAF != 0 && key == name {
+                found = Some(value);
+            }
+            Ok(())
+        })?;
+        let value = found.ok_or("history bucket not found")?;
+
+        let root = le_u64(value, 0)?;
+        if root == 0 {
+            // Small buckets are stored inline after their header
+            Ok(Node {
+                buf: value.get(BUCKET_HEADER_LEN..).ok_or("truncated bucket")?,
+            })
+        } else {
+            self.page(root)
+        }
+    }
+
+    /// Call `f` with every leaf element under `node`
+    fn walk(
+        &self,
+        node: &Node<'a>,
+        depth: usize,
+        f: &mut dyn FnMut(&'a [u8], &'a [u8], u32) -> Result<(), String>,
+    ) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err("tree too deep".into());
+        }
+        let buf = node.buf;
+        let flags = le_u16(buf, 8)?;
+        let count = le_u16(buf, 10)? as usize;
+
+        for i in 0..count {
+            let elem = PAGE_HEADER_LEN + i * ELEMENT_LEN;
+            if flags & LEAF_PAGE != 0 {
+                let elem_flags = le_u32(buf, elem)?;
+                let pos = elem + le_u32(buf, elem + 4)? as usize;
+                let ksize = le_u32(buf, elem + 8)? as usize;
+                let vsize = le_u32(buf, elem + 12)? as usize;
+                let key = slice(buf, pos, ksize)?;
+                let value = slice(buf, pos + ksize, vsize)?;
+                f(key, value, elem_flags)?;
+            } else if flags & BRANCH_PAGE != 0 {
+                let child = le_u64(buf, elem + 8)?;
+                self.walk(&self.page(child)?, depth + 1, f)?;
+            } else {
+                return Err(format!("unexpected page type {flags:#x}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn slice(buf: &[u8], start: usize, len: usize) -> Result<&[u8], String> {
+    start
+        .checked_add(len)
+        .and_then(|end| buf.get(start..end))
+        .ok_or_else(|| "element out of bounds".to_string())
+}
+
+fn le_u16(buf: &[u8], at: usize) -> Result<u16, String> {
+    Ok(u16::from_le_bytes(slice(buf, at, 2)?.try_into().unwrap()))
+}
+
+fn le_u32(buf: &[u8], at: usize) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(slice(buf, at, 4)?.try_into().unwrap()))
+}
+
+fn le_u64(buf: &[u8], at: usize) -> Result<u64, String> {
+    Ok(u64::from_le_bytes(slice(buf, at, 8)?.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::ContentType;
+
+    const PAGE: usize = 4096;
+
+    /// A page holding leaf elements `(flags, key, value)`
+    fn leaf(id: u64, elements: &[(u32, &[u8], &[u8])]) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE];
+        page[..8].copy_from_slice(&id.to_le_bytes());
+        page[8..10].copy_from_slice(&LEAF_PAGE.to_le_bytes());
+        page[10..12].copy_from_slice(&(elements.len() as u16).to_le_bytes());
+
+        let mut data_pos = PAGE_HEADER_LEN + elements.len() * ELEMENT_LEN;
+        for (i, (flags, key, value)) in elements.iter().enumerate() {
+            let elem = PAGE_HEADER_LEN + i * ELEMENT_LEN;
+            page[elem..elem + 4].copy_from_slice(&flags.to_le_bytes());
+            page[elem + 4..elem + 8].copy_from_slice(&((data_pos - elem) as u32).to_le_bytes());
+            page[elem + 8..elem + 12].copy_from_slice(&(key.len() as u32).to_le_bytes());
+            page[elem + 12..elem + 16].copy_from_slice(&(value.len() as u32).to_le_bytes());
+            page[data_pos..data_pos + key.len()].copy_from_slice(key);
+            data_pos += key.len();
+            page[data_pos..data_pos + value.len()].copy_from_slice(value);
+            data_pos += value.len();
+        }
+        page
+    }
+
+    fn meta(id: u64, root: u64, txid: u64) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE];
+        page[..8].copy_from_slice(&id.to_le_bytes());
+        page[8..10].copy_from_slice(&0x04u16.to_le_bytes());
+        let m = PAGE_HEADER_LEN;
+        page[m..m + 4].copy_from_slice(&MAGIC.to_le_bytes());
+        page[m + 4..m + 8].copy_from_slice(&2u32.to_le_bytes());
+        page[m + 8..m + 12].copy_from_slice(&(PAGE as u32).to_le_bytes());
+        page[m + 16..m + 24].copy_from_slice(&root.to_le_bytes());
+        page[m + 48..m + 56].copy_from_slice(&txid.to_le_bytes());
+        page
+    }
+
+    #[test]
+    fn reads_history_bucket_in_id_order() {
+        let png = b"\x89PNG\r\n\x1a\npixels";
+        let items = leaf(
+            4,
+            &[
+                (0, &1u64.to_be_bytes(), b"first"),
+                (0, &2u64.to_be_bytes(), png),
+                (0, &3u64.to_be_bytes(), b"\xff\xd8\xff jpeg"),
+            ],
+        );
+        let mut bucket = vec![0u8; BUCKET_HEADER_LEN];
+        bucket[..8].copy_from_slice(&4u64.to_le_bytes());
+
+        let mut db = Vec::new();
+        db.extend(meta(0, 3, 7));
+        // Stale meta page pointing nowhere useful
+        db.extend(meta(1, 99, 6));
+        db.extend(vec![0u8; PAGE]);
+        db.extend(leaf(3, &[(BUCKET_LEAF, BUCKET, &bucket)]));
+        db.extend(items);
+
+        let parsed = parse(&db).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0].content.as_deref(), Some("first"));
+        assert_eq!(parsed.entries[1].content_type, ContentType::Image);
+        assert_eq!(parsed.unsupported, 1);
+    }
+
+    #[test]
+    fn reads_inline_bucket() {
+        let inline = leaf(0, &[(0, &1u64.to_be_bytes(), b"only item")]);
+        let mut bucket = vec![0u8; BUCKET_HEADER_LEN];
+        bucket.extend(&inline[..PAGE_HEADER_LEN + ELEMENT_LEN + 8 + 9]);
+
+        let mut db = Vec::new();
+        db.extend(meta(0, 2, 1));
+        db.extend(meta(1, 2, 0));
+        db.extend(leaf(2, &[(BUCKET_LEAF, BUCKET, &bucket)]));
+
+        let parsed = parse(&db).unwrap();
+        assert_eq!(parsed.entries[0].content.as_deref(), Some("only item"));
+
+        // An overflow count past the end of the file is cut short
+        let leaf = 2 * PAGE;
+        db[leaf + 12..leaf + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        let parsed = parse(&db).unwrap();
+        assert_eq!(parsed.entries[0].content.as_deref(), Some("only item"));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse(b"SQLite format 3\0").is_err());
+        assert!(parse(&meta(0, 50, 1)).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use super::Parsed;
+
+/// clipman keeps its history as a JSON array of strings, oldest first
+pub(super) fn parse(data: &[u8]) -> Result<Parsed, String> {
+    let items: Vec<String> = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+    let mut parsed = Parsed::default();
+    for item in items {
+        parsed.push_text(item);
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_string_array() {
+        let parsed = parse(br#"["first", "", "second\nline"]"#).unwrap();
+        let texts: Vec<_> = parsed
+            .entries
+            .iter()
+            .filter_map(|e| e.content.as_deref())
+            .collect();
+        assert_eq!(texts, vec!["first", "second\nline"]);
+        assert!(parse(b"{\"not\": \"a list\"}").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use super::{Parsed, Reader};
+
+const NULL_LEN: u32 = u32::MAX;
+/// Marks an item in the current serialization (short mime names)
+const ITEM_V2: i32 = -2;
+
+/// One clipboard format of an item
+struct Format {
+    mime: String,
+    compressed: bool,
+    data: Vec<u8>,
+}
+
+/// CopyQ tab files are a Qt `QDataStream`: an i32 row count, newest row
+/// first, then each item's formats as (mime, compressed flag, bytes). Items
+/// are stored as their PNG image if they have one, else their plain text.
+/// Items saved compressed by older CopyQ versions are skipped.
+pub(super) fn parse(data: &[u8]) -> Result<Parsed, String> {
+    let mut reader = Reader::new(data);
+    let rows = reader.u32()? as i32;
+    if rows < 0 {
+        return Err("not a CopyQ tab file".into());
+    }
+
+    let mut items = Vec::new();
+    for _ in 0..rows {
+        items.push(read_item(&mut reader)?);
+    }
+
+    let mut parsed = Parsed::default();
+    for formats in items.into_iter().rev() {
+        let png = formats.iter().find(|f| f.mime == "image/png");
+        let text = formats
+            .iter()
+            .find(|f| f.mime == "text/plain" || f.mime.starts_with("text/plain;"));
+        match png.or(text) {
+            Some(format) if !format.compressed => {
+                if format.mime == "image/png" {
+                    parsed.push_bytes(format.data.clone());
+                } else {
+                    parsed.push_text(String::from_utf8_lossy(&format.data).into_owned());
+                }
+            }
+            _ => parsed.unsupported += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+fn read_item(reader: &mut Reader) -> Result<Vec<Format>, String> {
+    let marker = reader.u32()? as i32;
+    let (v2, count) = match marker {
+        ITEM_V2 => (true, reader.u32()? as i32),
+        n => (false, n),
+    };
+    if count < 0 {
+        return Err(format!("corrupt item (format count {count})"));
+    }
+
+    let mut formats = Vec::new();
+    for _ in 0..count {
+        let mime = if v2 {
+            expand_mime(&String::from_utf8_lossy(&read_bytes(reader)?))
+        } else {
+            read_string(reader)?
+        };
+        let compressed = reader.u8()? != 0;
+        let data = read_bytes(reader)?;
+        formats.push(Format {
+            mime,
+            compressed,
+            data,
+        });
+    }
+    Ok(formats)
+}
+
+/// Short mime names start with a digit: `0` for a full name, `1` for
+/// CopyQ's private `application/x-copyq-` types, `2` for `text/`
+fn expand_mime(short: &str) -> String {
+    match short.split_at_checked(1) {
+        Some(("1", rest)) => format!("application/x-copyq-{rest}"),
+        Some(("2", rest)) => format!("text/{rest}"),
+        Some(("0", rest)) => rest.to_string(),
+        _ => short.to_string(),
+    }
+}
+
+/// `QByteArray`: u32 length (all ones for null), then the bytes
+fn read_bytes(reader: &mut Reader) -> Result<Vec<u8>, String> {
+    match reader.u32()? {
+        NULL_LEN => Ok(Vec::new()),
+        len => Ok(reader.take(len as usize)?.to_vec()),
+    }
+}
+
+/// `QString`: u32 byte length (all ones for null), then UTF-16BE
+fn read_string(reader: &mut Reader) -> Result<String, String> {
+    let bytes = read_bytes(reader)?;
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::ContentType;
+
+    fn bytes(data: &[u8]) -> Vec<u8> {
+        let mut out = (data.len() as u32).to_be_bytes().to_vec();
+        out.extend(data);
+        out
+    }
+
+    fn v2_item(formats: &[(&str, bool, &[u8])]) -> Vec<u8> {
+        let mut out = ITEM_V2.to_be_bytes().to_vec();
+        out.extend((formats.len() as i32).to_be_bytes());
+        for (mime, compressed, data) in formats {
+            out.extend(bytes(mime.as_bytes()));
+            out.push(*compressed as u8);
+            out.extend(bytes(data));
+        }
+        out
+    }
+
+    #[test]
+    fn parses_v2_items_newest_first() {
+        let mut data = 3i32.to_be_bytes().to_vec();
+        data.extend(v2_item(&[
+            ("2plain", false, b"newest"),
+            ("1owner", false, b"x"),
+        ]));
+        data.extend(v2_item(&[
+            ("2plain", false, b"[image]"),
+            ("0image/png", false, b"\x89PNG\r\n\x1a\npixels"),
+        ]));
+        data.extend(v2_item(&[("2plain", true, b"zlib data")]));
+
+        let parsed = parse(&data).unwrap();
+        assert_eq!(parsed.unsupported, 1);
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0].content_type, ContentType::Image);
+        assert_eq!(parsed.entries[1].content.as_deref(), Some("newest"));
+    }
+
+    #[test]
+    fn parses_legacy_items() {
+        let mime: Vec<u8> = "text/plain"
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes())
+            .collect();
+        let mut data = 1i32.to_be_bytes().to_vec();
+        data.extend(1i32.to_be_bytes());
+        data.extend(bytes(&mime));
+        data.push(0);
+        data.extend(bytes(b"old format"));
+
+        let parsed = parse(&data).unwrap();
+        assert_eq!(parsed.entries[0].content.as_deref(), Some("old format"));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse(b"\xff\xff\xff\xf0").is_err());
+        assert!(parse(b"\x00\x00\x00\x05").is_err());
+    }
+}
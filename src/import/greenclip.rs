@@ -0,0 +1,58 @@
+use super::{Parsed, Reader};
+
+/// greenclip stores its history with Haskell's `Data.Binary`: a big-endian
+/// u64 item count, then per item a constructor tag byte and a u64
+/// length-prefixed payload (UTF-8 text or raw image bytes). Items are
+/// classified by content rather than tag; newest comes first.
+pub(super) fn parse(data: &[u8]) -> Result<Parsed, String> {
+    let mut reader = Reader::new(data);
+    let count = reader.u64()?;
+
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let _tag = reader.u8()?;
+        let len = usize::try_from(reader.u64()?).map_err(|e| e.to_string())?;
+        items.push(reader.take(len)?.to_vec());
+    }
+    if !reader.is_empty() {
+        return Err("trailing data after history".into());
+    }
+
+    let mut parsed = Parsed::default();
+    for item in items.into_iter().rev() {
+        parsed.push_bytes(item);
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::ContentType;
+
+    fn item(tag: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        out.extend((payload.len() as u64).to_be_bytes());
+        out.extend(payload);
+        out
+    }
+
+    #[test]
+    fn parses_history_newest_first() {
+        let mut data = 2u64.to_be_bytes().to_vec();
+        data.extend(item(3, b"newest text"));
+        data.extend(item(1, b"\x89PNG\r\n\x1a\npixels"));
+
+        let parsed = parse(&data).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0].content_type, ContentType::Image);
+        assert_eq!(parsed.entries[1].content.as_deref(), Some("newest text"));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let mut data = 1u64.to_be_bytes().to_vec();
+        data.extend(&item(0, b"cut short")[..6]);
+        assert!(parse(&data).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+mod cliphist;
+mod clipman;
+mod copyq;
+mod greenclip;
+
+use crate::archive::{import_entries, ImportReport};
+use crate::clipboard::PNG_MAGIC;
+use crate::config::MAX_IMAGE_SIZE_BYTES;
+use crate::entry::Entry;
+use crate::error::{Result, StickyError};
+use crate::storage::HistoryStore;
+use std::fs;
+use std::path::Path;
+
+/// Clipboard managers whose history can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// bbolt database, usually `~/.cache/cliphist/db`
+    Cliphist,
+    /// JSON history, usually `~/.local/share/clipman.json`
+    Clipman,
+    /// Tab data file, e.g. `~/.config/copyq/copyq_tab_JmNsaXBib2FyZA==.dat`
+    Copyq,
+    /// History cache, usually `~/.cache/greenclip.history`
+    Greenclip,
+}
+
+impl Source {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cliphist => "cliphist",
+            Self::Clipman => "clipman",
+            Self::Copyq => "copyq",
+            Self::Greenclip => "greenclip",
+        }
+    }
+}
+
+/// What a history file held, oldest first
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub entries: Vec<Entry>,
+    /// Items syo can't store, e.g. JPEG images
+    pub unsupported: usize,
+}
+
+impl Parsed {
+    /// Add an item from its raw bytes: PNG data becomes an image, UTF-8 a
+    /// text or link entry. Anything else is counted as unsupported.
+    fn push_bytes(&mut self, data: Vec<u8>) {
+        if data.starts_with(PNG_MAGIC) {
+            if data.len() <= MAX_IMAGE_SIZE_BYTES {
+                self.entries.push(Entry::new_image(data));
+            } else {
+                self.unsupported += 1;
+            }
+            return;
+        }
+        match String::from_utf8(data) {
+            Ok(text) => self.push_text(text),
+            Err(_) => self.unsupported += 1,
+        }
+    }
+
+    fn push_text(&mut self, text: String) {
+        if !text.trim().is_empty() {
+            self.entries.push(Entry::new_text(text));
+        }
+    }
+}
+
+/// Parse another tool's history file
+pub fn parse(source: Source, data: &[u8]) -> Result<Parsed> {
+    let parsed = match source {
+        Source::Cliphist => cliphist::parse(data),
+        Source::Clipman => clipman::parse(data),
+        Source::Copyq => copyq::parse(data),
+        Source::Greenclip => greenclip::parse(data),
+    };
+    parsed.map_err(|e| StickyError::Import(format!("{}: {e}", source.name())))
+}
+
+/// Import another tool's history, skipping entries already stored
+pub fn import(storage: &dyn HistoryStore, source: Source, path: &Path) -> Result<ImportReport> {
+    let parsed = parse(source, &fs::read(path)?)?;
+    let mut report = import_entries(storage, stamp(parsed.entries))?;
+    report.unsupported = parsed.unsupported;
+    Ok(report)
+}
+
+/// None of these tools record when an item was copied. Space entries a
+/// second apart, ending now, so they keep their order.
+fn stamp(mut entries: Vec<Entry>) -> Vec<Entry> {
+    let now = chrono::Utc::now().timestamp();
+    let len = entries.len() as i64;
+    for (i, entry) in entries.iter_mut().enumerate() {
+        entry.created_at = now - len + 1 + i as i64;
+        entry.last_seen = entry.created_at;
+    }
+    entries
+}
+
+/// Cursor over big-endian binary data
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> std::result::Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| format!("unexpected end of data at byte {}", self.pos))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> std::result::Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> std::result::Result<u64, String> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::ContentType;
+    use crate::storage::MemoryStore;
+
+    #[test]
+    fn push_bytes_classifies_items() {
+        let mut parsed = Parsed::default();
+        parsed.push_bytes(b"\x89PNG\r\n\x1a\nrest".to_vec());
+        parsed.push_bytes(b"https://example.com".to_vec());
+        parsed.push_bytes(b"\xff\xd8\xff\xe0 jpeg".to_vec());
+        parsed.push_bytes(b"  \n".to_vec());
+
+        let types: Vec<_> = parsed.entries.iter().map(|e| e.content_type).collect();
+        assert_eq!(types, vec![ContentType::Image, ContentType::Link]);
+        assert_eq!(parsed.unsupported, 1);
+    }
+
+    #[test]
+    fn stamp_keeps_order() {
+        let entries = stamp(vec![
+            Entry::new_text("old".into()),
+            Entry::new_text("new".into()),
+        ]);
+        assert_eq!(entries[0].last_seen + 1, entries[1].last_seen);
+        assert!(entries[1].last_seen <= chrono::Utc::now().timestamp());
+    }
+
+    #[test]
+    fn import_dedupes_and_reports() {
+        let storage = MemoryStore::new();
+        storage.insert(&Entry::new_text("known".into())).unwrap();
+        let path = std::env::temp_dir().join(format!("syo-clipman-{}.json", std::process::id()));
+        fs::write(&path, r#"["known", "fresh"]"#).unwrap();
+
+        let report = import(&storage, Source::Clipman, &path).unwrap();
+        assert_eq!((report.inserted, report.skipped), (1, 1));
+        assert_eq!(storage.count().unwrap(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,12 +1,22 @@
+pub mod archive;
+pub mod blob;
 pub mod clipboard;
+pub mod collect;
 pub mod config;
+pub mod crypto;
 pub mod daemon;
+pub mod doctor;
+pub mod editor;
 pub mod entry;
 pub mod error;
 pub mod gui;
 pub mod hotkey;
+pub mod import;
+mod lockfile;
+pub mod queue;
+pub mod snippet;
 pub mod storage;
 
-pub use entry::{ContentType, Entry};
+pub use entry::{ContentType, Entry, EntrySummary};
 pub use error::{Result, StickyError};
-pub use storage::Storage;
+pub use storage::{HistoryStore, Storage};
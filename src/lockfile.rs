@@ -0,0 +1,49 @@
+use crate::error::{Result, StickyError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Read, change and write back a small JSON state file shared between
+/// processes, holding a lock on it throughout. An empty or missing file is
+/// `None`; setting `None` empties it. Parse errors are reported via `err`.
+pub(crate) fn update<S, T>(
+    path: &Path,
+    err: fn(String) -> StickyError,
+    f: impl FnOnce(&mut Option<S>) -> Result<T>,
+) -> Result<T>
+where
+    S: Serialize + DeserializeOwned,
+{
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create(true).truncate(false);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // Released when the file is closed
+    file.lock()?;
+
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    let mut state = match text.trim() {
+        "" => None,
+        text => Some(serde_json::from_str(text).map_err(|e| err(e.to_string()))?),
+    };
+    let value = f(&mut state)?;
+
+    let text = match &state {
+        Some(state) => serde_json::to_string(state).map_err(|e| err(e.to_string()))?,
+        None => String::new(),
+    };
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(text.as_bytes())?;
+    Ok(value)
+}
@@ -1,12 +1,29 @@
-use clap::{Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
 use daemonize::Daemonize;
-use sticky_one::clipboard::{check_deps, write_entry};
-use sticky_one::config::{data_dir, pid_path};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufReader, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use sticky_one::archive;
+use sticky_one::clipboard::{check_deps, read_as_entry, write_entry};
+use sticky_one::collect::{self, Collector, CollectorFile};
+use sticky_one::config::{
+    current_profile, data_dir, db_path, list_profiles, parse_time, path_overrides, pid_path,
+    profile, profile_label, profile_name, save_current_profile, set_path_overrides, set_profile,
+    use_profile, Age, Backend, Config, PathOverrides,
+};
+use sticky_one::crypto::{self, Cipher};
 use sticky_one::daemon::{is_running, stop, Daemon};
-use sticky_one::entry::ContentType;
+use sticky_one::doctor::{self, Status};
+use sticky_one::editor;
+use sticky_one::entry::{preview_text, ContentType, Entry};
 use sticky_one::error::StickyError;
 use sticky_one::gui::run_popup;
+use sticky_one::import::{self, Source};
+use sticky_one::queue::{QueueFile, QueueMode};
+use sticky_one::snippet::{self, Answers, Snippets};
+use sticky_one::storage::{self, EntryFilter, SortOrder};
 use sticky_one::Storage;
 use tabled::settings::{object::Columns, Modify, Style, Width};
 use tabled::{Table, Tabled};
@@ -14,8 +31,20 @@ use tabled::{Table, Tabled};
 #[derive(Parser)]
 #[command(name = "syo")]
 #[command(version)]
-#[command(about = "Clipboard manager with 12-hour history")]
+#[command(about = "Clipboard manager with configurable history retention")]
 struct Cli {
+    /// Profile to use instead of the current one [env: SYO_PROFILE]
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Config file to use instead of the profile's [env: SYO_CONFIG]
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Dir for the history, log and daemon files [env: SYO_DATA_DIR]
+    #[arg(long, global = true, value_name = "DIR")]
+    data_dir: Option<PathBuf>,
+    /// SQLite database to use [env: SYO_DB]
+    #[arg(long, global = true, value_name = "FILE")]
+    db: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -33,6 +62,21 @@ enum Commands {
         /// Max entries to show
         #[arg(short, long, default_value = "20")]
         limit: usize,
+        /// Only show entries with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Ordering of entries
+        #[arg(short, long, value_enum, default_value_t = Sort::Recent)]
+        sort: Sort,
+        /// Only entries copied since this time (2h, 2026-01-31 or RFC 3339)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<i64>,
+        /// Only entries copied before this time (30m, 2026-01-31 or RFC 3339)
+        #[arg(long, value_parser = parse_since)]
+        until: Option<i64>,
+        /// Page to show, `limit` entries each, most recent first
+        #[arg(short, long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+        page: u32,
     },
     /// Copy a specific entry back to clipboard
     Get {
@@ -47,10 +91,359 @@ enum Commands {
         #[arg(short, long, default_value = "20")]
         limit: usize,
     },
-    /// Clear all history
-    Clear,
+    /// Edit a text entry in $EDITOR, keeping the old content as a revision
+    Edit {
+        /// Entry ID
+        id: i64,
+        /// Store the result as a new entry, leaving this one as it is
+        #[arg(long)]
+        new: bool,
+        /// Copy the result to the clipboard
+        #[arg(short, long)]
+        copy: bool,
+    },
+    /// Show the earlier content of an edited entry
+    Revisions {
+        /// Entry ID
+        id: i64,
+    },
+    /// Pin an entry so it survives retention and clear
+    Pin {
+        /// Entry ID
+        id: i64,
+    },
+    /// Unpin an entry
+    Unpin {
+        /// Entry ID
+        id: i64,
+    },
+    /// Add tags to an entry
+    Tag {
+        /// Entry ID
+        id: i64,
+        /// Tags to add
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from an entry
+    Untag {
+        /// Entry ID
+        id: i64,
+        /// Tags to remove
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Manage tag collections
+    #[command(subcommand)]
+    Collection(CollectionCommand),
+    /// Write history to stdout as an NDJSON archive
+    Export {
+        /// Only entries copied since this time (7d, 2026-01-31 or RFC 3339)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<i64>,
+        /// Only entries of this type
+        #[arg(long = "type", value_enum)]
+        content_type: Option<EntryType>,
+        /// Only pinned entries
+        #[arg(long)]
+        pinned: bool,
+        /// Only entries with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
+    /// Import entries from an NDJSON archive or another clipboard manager,
+    /// skipping ones already stored
+    Import {
+        /// Archive file (- for stdin), or the other tool's history file
+        file: PathBuf,
+        /// Read another clipboard manager's history instead of an archive
+        #[arg(long, value_enum)]
+        from: Option<ImportFrom>,
+    },
+    /// Delete entries by id, or every unpinned entry matching the filters
+    #[command(group = ArgGroup::new("target").required(true).multiple(true))]
+    Rm {
+        /// Entry IDs
+        #[arg(group = "target", conflicts_with_all = ["content_type", "older_than", "query"])]
+        ids: Vec<i64>,
+        /// Entries of this type
+        #[arg(long = "type", value_enum, group = "target")]
+        content_type: Option<EntryType>,
+        /// Entries last copied before this (30m, 2026-01-31 or RFC 3339)
+        #[arg(long, value_parser = parse_since, group = "target")]
+        older_than: Option<i64>,
+        /// Text entries containing this, ignoring case
+        #[arg(long = "match", group = "target")]
+        query: Option<String>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Clear history (pinned entries are kept)
+    Clear {
+        /// Also remove pinned entries
+        #[arg(long)]
+        all: bool,
+        /// Overwrite image and large text files before deleting them
+        #[arg(long)]
+        shred: bool,
+    },
+    /// Restore the entries removed by the last deletion
+    Undo,
+    /// Restore an entry from the trash
+    Restore {
+        /// Entry ID, as shown by `syo trash list`
+        id: i64,
+    },
+    /// Manage deleted entries
+    #[command(subcommand)]
+    Trash(TrashCommand),
+    /// Named registers a-z, kept apart from history
+    #[command(subcommand)]
+    Reg(RegCommand),
+    /// Collect copies in a queue and paste them one by one
+    #[command(subcommand)]
+    Queue(QueueCommand),
+    /// Append copies to one growing entry
+    #[command(subcommand)]
+    Collect(CollectCommand),
+    /// Join entries into a new one, in the given order
+    Merge {
+        /// Entry IDs
+        #[arg(required = true)]
+        ids: Vec<i64>,
+        /// Separator: newline, space, comma, tab, or any text
+        #[arg(long, default_value = "newline")]
+        sep: String,
+    },
+    /// Manage snippets: permanent text templates
+    #[command(subcommand)]
+    Snippet(SnippetCommand),
+    /// Separate histories, each with its own config
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+    /// Check the setup and suggest fixes for problems
+    Doctor,
+    /// Show history statistics and disk usage
+    Stats {
+        /// Print JSON instead
+        #[arg(long, conflicts_with = "histogram")]
+        json: bool,
+        /// Chart copies over the retention window
+        #[arg(long)]
+        histogram: bool,
+        /// How many of the most reused entries to show
+        #[arg(long, default_value = "5")]
+        top: usize,
+    },
     /// Open GUI popup
     Popup,
+    /// Unlock encrypted history for this session
+    Unlock,
+    /// Forget the session key so encrypted history can't be read
+    Lock,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sort {
+    /// Most recently copied first
+    Recent,
+    /// Most frequently and recently used first
+    Frecency,
+}
+
+impl From<Sort> for SortOrder {
+    fn from(sort: Sort) -> Self {
+        match sort {
+            Sort::Recent => SortOrder::Recent,
+            Sort::Frecency => SortOrder::Frecency,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EntryType {
+    Text,
+    Link,
+    Image,
+}
+
+impl From<EntryType> for ContentType {
+    fn from(t: EntryType) -> Self {
+        match t {
+            EntryType::Text => ContentType::Text,
+            EntryType::Link => ContentType::Link,
+            EntryType::Image => ContentType::Image,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ImportFrom {
+    Cliphist,
+    Clipman,
+    Copyq,
+    Greenclip,
+}
+
+impl From<ImportFrom> for Source {
+    fn from(from: ImportFrom) -> Self {
+        match from {
+            ImportFrom::Cliphist => Source::Cliphist,
+            ImportFrom::Clipman => Source::Clipman,
+            ImportFrom::Copyq => Source::Copyq,
+            ImportFrom::Greenclip => Source::Greenclip,
+        }
+    }
+}
+
+fn parse_since(s: &str) -> Result<i64, String> {
+    parse_time(s, chrono::Utc::now().timestamp())
+        .ok_or_else(|| format!("invalid time: {s:?} (e.g. 7d, 2026-01-31)"))
+}
+
+#[derive(Subcommand)]
+enum CollectionCommand {
+    /// List collections with entry counts
+    Ls,
+    /// Exempt a collection's entries from expiry
+    Keep {
+        /// Collection (tag) name
+        name: String,
+        /// Let the collection's entries expire again
+        #[arg(long)]
+        off: bool,
+    },
+    /// Write a collection to stdout as an NDJSON archive
+    Export {
+        /// Collection (tag) name
+        name: String,
+    },
+    /// Import entries from an NDJSON archive
+    Import {
+        /// Archive file
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    /// List deleted entries, most recent first
+    List,
+    /// Delete everything in the trash for good
+    Empty,
+}
+
+#[derive(Subcommand)]
+enum RegCommand {
+    /// Store content in a register
+    Set {
+        /// Register name (a-z)
+        name: char,
+        /// Entry ID, or - to read text from stdin (default: the clipboard)
+        #[arg(value_parser = parse_reg_source)]
+        source: Option<RegSource>,
+    },
+    /// Copy a register to the clipboard
+    Get {
+        /// Register name (a-z)
+        name: char,
+    },
+    /// List registers that hold something
+    Ls,
+    /// Empty a register
+    Rm {
+        /// Register name (a-z)
+        name: char,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum RegSource {
+    Entry(i64),
+    Stdin,
+}
+
+fn parse_reg_source(s: &str) -> Result<RegSource, String> {
+    match s {
+        "-" => Ok(RegSource::Stdin),
+        _ => s
+            .parse()
+            .map(RegSource::Entry)
+            .map_err(|_| format!("expected an entry ID or -, got {s:?}")),
+    }
+}
+
+#[derive(Subcommand)]
+enum QueueCommand {
+    /// Queue every copy from now on (needs the daemon)
+    Start {
+        /// Paste the newest copy first instead of the oldest
+        #[arg(long)]
+        lifo: bool,
+    },
+    /// Stop queueing and drop what's left
+    Stop,
+    /// Put the next queued item on the clipboard
+    Next,
+    /// Show whether queue mode is on and how many items wait
+    Status,
+}
+
+#[derive(Subcommand)]
+enum CollectCommand {
+    /// Append every text copy from now on (needs the daemon)
+    Start {
+        /// Separator: newline, space, comma, tab, or any text
+        #[arg(long, default_value = "newline")]
+        sep: String,
+    },
+    /// Stop collecting and copy the result
+    Stop,
+    /// Show whether collecting and how many copies so far
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// List profiles
+    Ls,
+    /// Make a profile the current one; a running daemon switches to it
+    Use {
+        /// Profile name (`default` for the default profile)
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnippetCommand {
+    /// Add a snippet
+    Add {
+        /// Snippet name
+        name: String,
+        /// Template text; opens $EDITOR when omitted
+        text: Option<String>,
+    },
+    /// List snippets
+    Ls,
+    /// Remove a snippet
+    Rm {
+        /// Snippet name
+        name: String,
+    },
+    /// Edit a snippet in $EDITOR
+    Edit {
+        /// Snippet name
+        name: String,
+    },
+    /// Fill in a snippet's placeholders and copy the result to the clipboard
+    Get {
+        /// Snippet name
+        name: String,
+        /// Print the result instead of copying it
+        #[arg(short, long)]
+        print: bool,
+    },
 }
 
 #[derive(Tabled)]
@@ -68,9 +461,35 @@ struct EntryRow {
 fn main() {
     let cli = Cli::parse();
 
+    let overrides = PathOverrides {
+        config: cli.config,
+        data_dir: cli.data_dir,
+        db: cli.db,
+    }
+    .or_env(|name| std::env::var_os(name));
+    let isolated = !overrides.is_empty();
+    set_path_overrides(overrides);
+
+    // An explicit profile pins this run to it; otherwise it's the current one
+    let pinned = cli
+        .profile
+        .or_else(|| std::env::var("SYO_PROFILE").ok().filter(|p| !p.is_empty()));
+    match pinned.as_deref().map(profile_name) {
+        Some(Ok(profile)) => {
+            use_profile(profile);
+        }
+        Some(Err(e)) => {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+            std::process::exit(1);
+        }
+        None => {
+            use_profile(current_profile());
+        }
+    }
+
     // Daemon must fork BEFORE tokio runtime starts
     if matches!(cli.command, Commands::Daemon) {
-        if let Err(e) = run_daemon() {
+        if let Err(e) = run_daemon(pinned.is_none() && !isolated) {
             eprintln!("{} {}", "Error:".red().bold(), e);
             std::process::exit(1);
         }
@@ -84,11 +503,81 @@ fn main() {
             Commands::Daemon => unreachable!(),
             Commands::Stop => cmd_stop(),
             Commands::Status => cmd_status(),
-            Commands::List { limit } => cmd_list(limit),
+            Commands::List {
+                limit,
+                tag,
+                sort,
+                since,
+                until,
+                page,
+            } => cmd_list(
+                limit,
+                sort.into(),
+                page,
+                EntryFilter {
+                    since,
+                    until,
+                    tag,
+                    ..Default::default()
+                },
+            ),
             Commands::Get { id } => cmd_get(id),
             Commands::Search { query, limit } => cmd_search(&query, limit),
-            Commands::Clear => cmd_clear(),
+            Commands::Edit { id, new, copy } => cmd_edit(id, new, copy),
+            Commands::Revisions { id } => cmd_revisions(id),
+            Commands::Pin { id } => cmd_pin(id, true),
+            Commands::Unpin { id } => cmd_pin(id, false),
+            Commands::Tag { id, tags } => cmd_tag(id, &tags, true),
+            Commands::Untag { id, tags } => cmd_tag(id, &tags, false),
+            Commands::Collection(cmd) => cmd_collection(cmd),
+            Commands::Export {
+                since,
+                content_type,
+                pinned,
+                tag,
+            } => cmd_export(&EntryFilter {
+                since,
+                content_type: content_type.map(Into::into),
+                pinned,
+                tag,
+                ..Default::default()
+            }),
+            Commands::Rm {
+                ids,
+                content_type,
+                older_than,
+                query,
+                yes,
+            } => cmd_rm(
+                &ids,
+                EntryFilter {
+                    until: older_than,
+                    content_type: content_type.map(Into::into),
+                    query,
+                    ..Default::default()
+                },
+                yes,
+            ),
+            Commands::Import { file, from } => cmd_import(&file, from.map(Into::into)),
+            Commands::Clear { all, shred } => cmd_clear(all, shred),
+            Commands::Undo => cmd_undo(),
+            Commands::Restore { id } => cmd_restore(id),
+            Commands::Reg(cmd) => cmd_reg(cmd),
+            Commands::Queue(cmd) => cmd_queue(cmd),
+            Commands::Collect(cmd) => cmd_collect(cmd),
+            Commands::Merge { ids, sep } => cmd_merge(&ids, &sep),
+            Commands::Snippet(cmd) => cmd_snippet(cmd),
+            Commands::Profile(cmd) => cmd_profile(cmd),
+            Commands::Trash(cmd) => cmd_trash(cmd),
+            Commands::Doctor => cmd_doctor(),
+            Commands::Stats {
+                json,
+                histogram,
+                top,
+            } => cmd_stats(json, histogram, top),
             Commands::Popup => cmd_popup(),
+            Commands::Unlock => cmd_unlock(),
+            Commands::Lock => cmd_lock(),
         }
     });
 
@@ -98,13 +587,23 @@ fn main() {
     }
 }
 
-fn run_daemon() -> sticky_one::Result<()> {
+/// `follow`: switch along with `syo profile use` while running
+fn run_daemon(follow: bool) -> sticky_one::Result<()> {
     check_deps()?;
 
     if let Some(pid) = is_running() {
         return Err(StickyError::DaemonRunning(pid));
     }
 
+    // Prompt for the passphrase while we still have a terminal
+    let encryption = Config::load().encryption;
+    if encryption.enabled && !crypto::is_unlocked(&encryption) {
+        if encryption.key_file.is_none() && !std::io::stdin().is_terminal() {
+            return Err(StickyError::Locked);
+        }
+        cmd_unlock()?;
+    }
+
     std::fs::create_dir_all(data_dir())?;
     println!("{}", "Starting daemon...".green());
 
@@ -119,6 +618,9 @@ fn run_daemon() -> sticky_one::Result<()> {
                 tokio::runtime::Runtime::new().map_err(|e| StickyError::Daemon(e.to_string()))?;
             rt.block_on(async {
                 let mut daemon = Daemon::new()?;
+                if follow {
+                    daemon = daemon.follow_current_profile();
+                }
                 daemon.run().await
             })
         }
@@ -133,10 +635,27 @@ fn cmd_stop() -> sticky_one::Result<()> {
 }
 
 fn cmd_status() -> sticky_one::Result<()> {
+    if profile().is_some() {
+        println!("Profile: {}", profile_label().bold());
+    }
     match is_running() {
         Some(pid) => println!("{} (pid: {})", "Daemon running".green(), pid),
         None => println!("{}", "Daemon not running".yellow()),
     }
+    let config = Config::load();
+    let paths = &config.paths;
+    if !path_overrides().is_empty() || paths.data_dir.is_some() || paths.db.is_some() {
+        println!("Data dir: {}", data_dir().display());
+        println!("Database: {}", db_path().display());
+    }
+    let encryption = config.encryption;
+    if encryption.enabled {
+        if crypto::is_unlocked(&encryption) {
+            println!("{}", "History unlocked".green());
+        } else {
+            println!("{}", "History locked".yellow());
+        }
+    }
     Ok(())
 }
 
@@ -148,7 +667,21 @@ fn format_type(ct: ContentType) -> String {
     }
 }
 
-fn print_entries(entries: Vec<sticky_one::Entry>) {
+fn format_preview(e: &sticky_one::EntrySummary) -> String {
+    let preview = e.display_preview(80);
+    if e.tags.is_empty() {
+        return preview;
+    }
+    let tags: Vec<String> = e.tags.iter().map(|t| format!("#{t}")).collect();
+    format!("{} {}", tags.join(" ").blue(), preview)
+}
+
+fn print_entries(entries: Vec<sticky_one::EntrySummary>) {
+    print_table(entries.into_iter().map(|e| (e.last_seen, e)).collect());
+}
+
+/// Entries along with the time to show for each
+fn print_table(entries: Vec<(i64, sticky_one::EntrySummary)>) {
     if entries.is_empty() {
         println!("{}", "No entries".dimmed());
         return;
@@ -156,16 +689,22 @@ fn print_entries(entries: Vec<sticky_one::Entry>) {
 
     let rows: Vec<EntryRow> = entries
         .into_iter()
-        .map(|e| {
-            let ts = chrono::DateTime::from_timestamp(e.created_at, 0)
+        .map(|(time, e)| {
+            let ts = chrono::DateTime::from_timestamp(time, 0)
                 .map(|dt| dt.format("%H:%M").to_string())
                 .unwrap_or_else(|| "???".into());
 
+            let id = if e.pinned {
+                format!("{}*", e.id).yellow().bold().to_string()
+            } else {
+                e.id.to_string().bold().to_string()
+            };
+
             EntryRow {
-                id: e.id.to_string().bold().to_string(),
+                id,
                 content_type: format_type(e.content_type),
                 time: ts.dimmed().to_string(),
-                preview: e.display_preview(80),
+                preview: format_preview(&e),
             }
         })
         .collect();
@@ -178,41 +717,856 @@ fn print_entries(entries: Vec<sticky_one::Entry>) {
     println!("{}", table);
 }
 
-fn cmd_list(limit: usize) -> sticky_one::Result<()> {
-    let storage = Storage::open()?;
-    let entries = storage.list(limit)?;
-    print_entries(entries);
+fn cmd_list(
+    limit: usize,
+    order: SortOrder,
+    page: u32,
+    filter: EntryFilter,
+) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    if order == SortOrder::Frecency {
+        if filter.since.is_some() || filter.until.is_some() || page > 1 {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--since, --until and --page only work with --sort recent",
+                )
+                .exit();
+        }
+        let entries = match &filter.tag {
+            Some(tag) => storage.list_tagged(tag, limit, order)?,
+            None => storage.list_sorted(limit, order)?,
+        };
+        print_entries(entries);
+        return Ok(());
+    }
+
+    let mut listed = storage.list_page(None, limit, &filter)?;
+    for _ in 1..page {
+        let Some(next) = listed.next else {
+            println!("{}", format!("No page {page}").yellow());
+            return Ok(());
+        };
+        listed = storage.list_page(Some(next), limit, &filter)?;
+    }
+    let more = listed.next.is_some();
+    print_entries(listed.entries);
+    if more {
+        println!("{}", format!("More: --page {}", page + 1).dimmed());
+    }
     Ok(())
 }
 
 fn cmd_get(id: i64) -> sticky_one::Result<()> {
-    let storage = Storage::open()?;
+    let storage = storage::open()?;
     let entry = storage.get_by_id(id)?;
     write_entry(&entry)?;
+    storage.record_use(id)?;
     println!("{} {}", "Copied entry".green(), id.to_string().bold());
     Ok(())
 }
 
 fn cmd_search(query: &str, limit: usize) -> sticky_one::Result<()> {
-    let storage = Storage::open()?;
+    let storage = storage::open()?;
     let entries = storage.search(query, limit)?;
+    // A broken snippets file shouldn't stop the history search
+    let snippets = Snippets::load()
+        .map_err(|e| eprintln!("{} {}", "Warning:".yellow().bold(), e))
+        .ok();
+    let matching: Vec<_> = snippets.iter().flat_map(|s| s.search(query)).collect();
 
-    if entries.is_empty() {
+    if entries.is_empty() && matching.is_empty() {
         println!("{} '{}'", "No matches for".yellow(), query);
         return Ok(());
     }
 
-    print_entries(entries);
+    if !entries.is_empty() {
+        print_entries(entries);
+    }
+    if !matching.is_empty() {
+        println!("{}", "Snippets".bold());
+        for (name, body) in matching {
+            println!("  {} {}", name.bold(), preview_text(body, 60).dimmed());
+        }
+    }
+    Ok(())
+}
+
+fn cmd_edit(id: i64, new: bool, copy: bool) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    let entry = storage.get_by_id(id)?;
+    let Some(text) = entry
+        .content
+        .as_deref()
+        .filter(|_| entry.content_type != ContentType::Image)
+    else {
+        return Err(StickyError::NotText(id));
+    };
+
+    let edited = editor::edit_text(text, Config::load().encryption.enabled)?;
+    let id = if edited == text {
+        println!("{}", "No changes".dimmed());
+        id
+    } else if new {
+        let id = storage.insert(&Entry::new_text(edited))?;
+        println!("{} {}", "Saved as entry".green(), id.to_string().bold());
+        id
+    } else {
+        let id = storage.edit(id, &edited)?;
+        println!("{} {}", "Updated entry".green(), id.to_string().bold());
+        id
+    };
+
+    if copy {
+        write_entry(&storage.get_by_id(id)?)?;
+        println!("{} {}", "Copied entry".green(), id.to_string().bold());
+    }
+    Ok(())
+}
+
+fn cmd_revisions(id: i64) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    let revisions = storage.list_revisions(id)?;
+    if revisions.is_empty() {
+        println!("{}", "No revisions".dimmed());
+    }
+    for (n, revision) in revisions.iter().enumerate() {
+        let time = chrono::DateTime::from_timestamp(revision.replaced_at, 0)
+            .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "???".into());
+        println!(
+            "{} {}",
+            format!("#{}", n + 1).bold(),
+            format!("replaced {time}").dimmed()
+        );
+        println!("{}\n", revision.content);
+    }
+    Ok(())
+}
+
+fn cmd_pin(id: i64, pinned: bool) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    storage.set_pinned(id, pinned)?;
+    let verb = if pinned {
+        "Pinned entry"
+    } else {
+        "Unpinned entry"
+    };
+    println!("{} {}", verb.green(), id.to_string().bold());
     Ok(())
 }
 
-fn cmd_clear() -> sticky_one::Result<()> {
-    let storage = Storage::open()?;
-    let count = storage.clear()?;
+fn cmd_tag(id: i64, tags: &[String], add: bool) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    if add {
+        storage.add_tags(id, tags)?;
+    } else {
+        storage.remove_tags(id, tags)?;
+    }
+    let tags = storage.get_by_id(id)?.tags;
+    let tags: Vec<String> = tags.iter().map(|t| format!("#{t}")).collect();
+    println!(
+        "{} {} {}",
+        "Entry".green(),
+        id.to_string().bold(),
+        tags.join(" ").blue()
+    );
+    Ok(())
+}
+
+fn cmd_collection(cmd: CollectionCommand) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    match cmd {
+        CollectionCommand::Ls => {
+            let tags = storage.list_tags()?;
+            if tags.is_empty() {
+                println!("{}", "No collections".dimmed());
+            }
+            for tag in tags {
+                let keep = if tag.keep {
+                    " (kept)".yellow().to_string()
+                } else {
+                    String::new()
+                };
+                println!("{} {}{}", format!("#{}", tag.name).blue(), tag.count, keep);
+            }
+        }
+        CollectionCommand::Keep { name, off } => {
+            storage.set_tag_keep(&name, !off)?;
+            let msg = if off { "will expire" } else { "kept" };
+            println!("{} {}", format!("#{name}").blue(), msg.green());
+        }
+        CollectionCommand::Export { name } => {
+            return cmd_export(&EntryFilter {
+                tag: Some(name),
+                ..Default::default()
+            });
+        }
+        CollectionCommand::Import { file } => return cmd_import(&file, None),
+    }
+    Ok(())
+}
+
+fn cmd_export(filter: &EntryFilter) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    let entries = storage
+        .list_filtered(filter)?
+        .into_iter()
+        .map(|s| storage.get_by_id(s.id));
+    let count = archive::export(entries, std::io::BufWriter::new(std::io::stdout().lock()))?;
+    eprintln!("{} {} entries", "Exported".green(), count);
+    Ok(())
+}
+
+fn cmd_import(file: &Path, from: Option<Source>) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    let report = match from {
+        Some(source) => import::import(&*storage, source, file)?,
+        None if file == Path::new("-") => archive::import(&*storage, std::io::stdin().lock())?,
+        None => archive::import(&*storage, BufReader::new(std::fs::File::open(file)?))?,
+    };
+    print!(
+        "{} {} entries ({} skipped",
+        "Imported".green(),
+        report.inserted,
+        report.skipped
+    );
+    if report.unsupported > 0 {
+        print!(", {} unsupported", report.unsupported);
+    }
+    println!(")");
+    Ok(())
+}
+
+fn cmd_rm(ids: &[i64], filter: EntryFilter, yes: bool) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    if !ids.is_empty() {
+        let noun = if ids.len() == 1 { "entry" } else { "entries" };
+        let list = ids
+            .iter()
+            .map(i64::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if !yes && !confirm(&format!("Delete {noun} {list}?"))? {
+            return Ok(());
+        }
+        storage.delete_ids(ids)?;
+        println!("{} {}", format!("Deleted {noun}").yellow(), list.bold());
+        return Ok(());
+    }
+
+    let matching = storage
+        .list_filtered(&filter)?
+        .into_iter()
+        .filter(|e| !e.pinned)
+        .count();
+    if matching == 0 {
+        println!("{}", "No matching entries".dimmed());
+        return Ok(());
+    }
+    if !yes && !confirm(&format!("Delete {matching} entries?"))? {
+        return Ok(());
+    }
+    let count = storage.delete_where(&filter)?;
+    println!("{} {} entries", "Deleted".yellow(), count);
+    Ok(())
+}
+
+/// Ask a yes/no question on the terminal; anything but "y" is a no
+fn confirm(question: &str) -> sticky_one::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(StickyError::NotConfirmed);
+    }
+    eprint!("{question} [y/N] ");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn cmd_clear(all: bool, shred: bool) -> sticky_one::Result<()> {
+    let mut storage = storage::open()?;
+    storage.set_shred(shred)?;
+    let count = if all {
+        storage.clear_all()?
+    } else {
+        storage.clear()?
+    };
     println!("{} {} entries", "Cleared".yellow(), count);
+    if Config::load().storage.backend == Backend::File {
+        println!(
+            "{}",
+            "The file backend keeps deleted entries in its log until it's removed: \
+             it can't wipe them from disk"
+                .dimmed()
+        );
+    }
+    Ok(())
+}
+
+fn cmd_undo() -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    let ids = storage.undo()?;
+    if ids.is_empty() {
+        println!("{}", "Nothing to undo".dimmed());
+        return Ok(());
+    }
+    println!("{} {} entries", "Restored".green(), ids.len());
+    Ok(())
+}
+
+fn cmd_restore(id: i64) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    let restored = storage.restore(id)?;
+    println!(
+        "{} {}",
+        "Restored entry".green(),
+        restored.to_string().bold()
+    );
+    Ok(())
+}
+
+fn cmd_trash(cmd: TrashCommand) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    match cmd {
+        TrashCommand::List => {
+            storage.purge_trash(false)?;
+            let items = storage.list_trash()?;
+            print_table(items.into_iter().map(|t| (t.deleted_at, t.entry)).collect());
+        }
+        TrashCommand::Empty => {
+            let count = storage.purge_trash(true)?;
+            println!("{} {} entries", "Purged".yellow(), count);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_reg(cmd: RegCommand) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    match cmd {
+        RegCommand::Set { name, source } => {
+            let entry = match source {
+                Some(RegSource::Entry(id)) => storage.get_by_id(id)?,
+                Some(RegSource::Stdin) => {
+                    let mut text = String::new();
+                    std::io::stdin().read_to_string(&mut text)?;
+                    Entry::new_text(text)
+                }
+                None => read_as_entry()?
+                    .ok_or_else(|| StickyError::Clipboard("clipboard is empty".into()))?,
+            };
+            storage.set_register(name, &entry)?;
+            println!(
+                "{} {}",
+                "Set register".green(),
+                name.to_ascii_lowercase().to_string().bold()
+            );
+        }
+        RegCommand::Get { name } => {
+            write_entry(&storage.get_register(name)?)?;
+            println!(
+                "{} {}",
+                "Copied register".green(),
+                name.to_ascii_lowercase().to_string().bold()
+            );
+        }
+        RegCommand::Ls => {
+            let registers = storage.list_registers()?;
+            if registers.is_empty() {
+                println!("{}", "No registers set".dimmed());
+            }
+            for register in registers {
+                println!(
+                    "{} {} {}",
+                    format!("\"{}", register.name).bold(),
+                    format_type(register.entry.content_type),
+                    register.entry.display_preview(70)
+                );
+            }
+        }
+        RegCommand::Rm { name } => {
+            storage.clear_register(name)?;
+            println!(
+                "{} {}",
+                "Emptied register".yellow(),
+                name.to_ascii_lowercase().to_string().bold()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn cmd_queue(cmd: QueueCommand) -> sticky_one::Result<()> {
+    let queue = QueueFile::open();
+    match cmd {
+        QueueCommand::Start { lifo } => {
+            let mode = if lifo {
+                QueueMode::Lifo
+            } else {
+                QueueMode::Fifo
+            };
+            queue.start(mode)?;
+            println!("{} ({mode})", "Queueing copies".green());
+            if is_running().is_none() {
+                println!(
+                    "{}",
+                    "The daemon isn't running: start it to capture copies".yellow()
+                );
+            }
+        }
+        QueueCommand::Stop => match queue.stop()? {
+            Some(q) => println!("{} ({} left)", "Stopped queueing".yellow(), q.items.len()),
+            None => println!("{}", "Queue mode is off".dimmed()),
+        },
+        QueueCommand::Next => {
+            let storage = storage::open()?;
+            match queue.next(&*storage)? {
+                Some(entry) => {
+                    write_entry(&entry)?;
+                    println!("{} {}", "Copied entry".green(), entry.id.to_string().bold());
+                }
+                None => println!("{}", "Queue is empty".dimmed()),
+            }
+        }
+        QueueCommand::Status => match queue.status()? {
+            Some(q) => println!(
+                "{} ({}, {} queued)",
+                "Queueing".green(),
+                q.mode,
+                q.items.len()
+            ),
+            None => println!("{}", "Queue mode is off".dimmed()),
+        },
+    }
+    Ok(())
+}
+
+fn cmd_collect(cmd: CollectCommand) -> sticky_one::Result<()> {
+    let collector = CollectorFile::open();
+    match cmd {
+        CollectCommand::Start { sep } => {
+            collector.start(collect::separator(&sep))?;
+            println!("{}", "Collecting copies".green());
+            if is_running().is_none() {
+                println!(
+                    "{}",
+                    "The daemon isn't running: start it to capture copies".yellow()
+                );
+            }
+        }
+        CollectCommand::Stop => match collector.stop()? {
+            Some(Collector {
+                entry: Some(id), ..
+            }) => {
+                let storage = storage::open()?;
+                let entry = match storage.get_by_id(id) {
+                    Err(StickyError::NotFound(_)) => {
+                        println!("{}", "The collected entry was deleted".yellow());
+                        return Ok(());
+                    }
+                    result => result?,
+                };
+                write_entry(&entry)?;
+                println!(
+                    "{} {}",
+                    "Collected into entry".green(),
+                    id.to_string().bold()
+                );
+            }
+            Some(_) => println!("{}", "Stopped collecting (nothing collected)".yellow()),
+            None => println!("{}", "Not collecting".dimmed()),
+        },
+        CollectCommand::Status => match collector.status()? {
+            Some(c) => match c.entry {
+                Some(id) => println!(
+                    "{} ({} copies, entry {})",
+                    "Collecting".green(),
+                    c.count,
+                    id
+                ),
+                None => println!("{} (nothing yet)", "Collecting".green()),
+            },
+            None => println!("{}", "Not collecting".dimmed()),
+        },
+    }
+    Ok(())
+}
+
+fn cmd_merge(ids: &[i64], sep: &str) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    let id = collect::merge(&*storage, ids, &collect::separator(sep))?;
+    println!("{} {}", "Merged into entry".green(), id.to_string().bold());
+    Ok(())
+}
+
+fn cmd_profile(cmd: ProfileCommand) -> sticky_one::Result<()> {
+    match cmd {
+        ProfileCommand::Ls => {
+            let active = profile_label();
+            for name in list_profiles() {
+                if name == active {
+                    println!("{} {}", "*".green(), name.bold());
+                } else {
+                    println!("  {name}");
+                }
+            }
+        }
+        ProfileCommand::Use { name } => {
+            let profile = profile_name(&name)?;
+            save_current_profile(profile.as_deref())?;
+            set_profile(profile);
+            println!("{} {}", "Using profile".green(), profile_label().bold());
+        }
+    }
+    Ok(())
+}
+
+fn cmd_snippet(cmd: SnippetCommand) -> sticky_one::Result<()> {
+    let mut snippets = Snippets::load()?;
+    match cmd {
+        SnippetCommand::Add { name, text } => {
+            if snippets.contains(&name) {
+                return Err(StickyError::Snippet(format!(
+                    "{name:?} already exists (use `syo snippet edit`)"
+                )));
+            }
+            let text = match text {
+                Some(text) => text,
+                None => editor::edit_text("", false)?,
+            };
+            snippets.set(&name, text)?;
+            snippets.save()?;
+            println!("{} {}", "Added snippet".green(), name.bold());
+        }
+        SnippetCommand::Ls => {
+            if snippets.is_empty() {
+                println!("{}", "No snippets".dimmed());
+            }
+            for (name, body) in snippets.iter() {
+                println!("{} {}", name.bold(), preview_text(body, 60).dimmed());
+            }
+        }
+        SnippetCommand::Rm { name } => {
+            snippets.remove(&name)?;
+            snippets.save()?;
+            println!("{} {}", "Removed snippet".yellow(), name.bold());
+        }
+        SnippetCommand::Edit { name } => {
+            let body = snippets.get(&name)?.to_string();
+            let edited = editor::edit_text(&body, false)?;
+            if edited == body {
+                println!("{}", "No changes".dimmed());
+                return Ok(());
+            }
+            snippets.set(&name, edited)?;
+            snippets.save()?;
+            println!("{} {}", "Updated snippet".green(), name.bold());
+        }
+        SnippetCommand::Get { name, print } => {
+            let template = snippets.get(&name)?;
+            let mut answers = VecDeque::new();
+            for prompt in snippet::prompts(template) {
+                eprint!("{prompt}: ");
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                answers.push_back(answer.trim_end_matches(['\r', '\n']).to_string());
+            }
+            let text = snippet::expand(template, &mut Answers(answers))?;
+            if print {
+                println!("{text}");
+            } else {
+                write_entry(&Entry::new_text(text))?;
+                println!("{} {}", "Copied snippet".green(), name.bold());
+            }
+        }
+    }
     Ok(())
 }
 
+fn prompt_passphrase(confirm: bool) -> sticky_one::Result<String> {
+    let passphrase = rpassword::prompt_password("Passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(StickyError::Crypto("empty passphrase".into()));
+    }
+    if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+        return Err(StickyError::Crypto("passphrases do not match".into()));
+    }
+    Ok(passphrase)
+}
+
+fn cmd_unlock() -> sticky_one::Result<()> {
+    let encryption = Config::load().encryption;
+    let mut storage = Storage::open_at(&db_path())?;
+    // With encryption turned off, unlocking decrypts what's still encrypted
+    if !encryption.enabled && !storage.has_encrypted()? {
+        return Err(StickyError::Crypto(
+            "encryption is not enabled (set [encryption] enabled = true)".into(),
+        ));
+    }
+    if encryption.enabled && encryption.key_file.is_none() {
+        crypto::session_key_path()?;
+    }
+
+    let key = match &encryption.key_file {
+        Some(path) => {
+            if !path.exists() && encryption.enabled {
+                crypto::write_key_file(path, &crypto::generate_key())?;
+                println!("{} {}", "Created key file".green(), path.display());
+            }
+            crypto::read_key_file(path)?
+        }
+        None => {
+            let first_use = encryption.enabled && !storage.has_key()?;
+            if first_use {
+                println!(
+                    "{}",
+                    "Choose a passphrase for your clipboard history".bold()
+                );
+            }
+            let passphrase = prompt_passphrase(first_use)?;
+            crypto::derive_key(&passphrase, &storage.kdf_salt()?)?
+        }
+    };
+
+    if !encryption.enabled {
+        let count = storage.decrypt(Cipher::new(&key))?;
+        println!(
+            "{} {count} {}",
+            "Decrypted".green(),
+            "entries (encryption is off)".dimmed()
+        );
+        return Ok(());
+    }
+
+    // Verifies the key and encrypts anything still stored in plaintext
+    storage.unlock(Cipher::new(&key))?;
+    match encryption.key_file {
+        Some(_) => crypto::clear_lock()?,
+        None => crypto::save_session_key(&key)?,
+    }
+    println!("{}", "History unlocked".green());
+    Ok(())
+}
+
+fn cmd_lock() -> sticky_one::Result<()> {
+    if crypto::lock(&Config::load().encryption)? {
+        println!("{}", "History locked".yellow());
+    } else {
+        println!("{}", "Already locked".dimmed());
+    }
+    Ok(())
+}
+
+fn cmd_doctor() -> sticky_one::Result<()> {
+    let findings = doctor::run();
+    for finding in &findings {
+        let mark = match finding.status {
+            Status::Ok => "ok".green(),
+            Status::Warn => "!!".yellow(),
+            Status::Fail => "XX".red().bold(),
+        };
+        println!("[{mark}] {}: {}", finding.check.bold(), finding.detail);
+        if let Some(fix) = &finding.fix {
+            println!("     {} {fix}", "fix:".cyan());
+        }
+    }
+
+    let failed = findings.iter().filter(|f| f.status == Status::Fail).count();
+    if failed > 0 {
+        println!("\n{}", format!("{failed} problem(s) found").red());
+        std::process::exit(1);
+    }
+    println!("\n{}", "No problems found".green());
+    Ok(())
+}
+
+fn cmd_stats(json: bool, histogram: bool, top: usize) -> sticky_one::Result<()> {
+    let storage = storage::open()?;
+    let retention = Config::load().retention;
+    let now = chrono::Utc::now().timestamp();
+    // Copies are charted over the longest any entry is kept
+    let window = retention.longest_age().0;
+    let stats = storage.stats(now - window, top)?;
+
+    let entries: usize = stats.types.iter().map(|t| t.count).sum();
+    let bytes: i64 = stats.types.iter().map(|t| t.bytes).sum();
+    let captures: usize = stats.hourly.iter().map(|(_, n)| n).sum();
+    let per_hour = captures as f64 / (window as f64 / 3600.0).max(1.0);
+    let oldest = stats.types.iter().map(|t| t.oldest).min();
+    // Only by age: count and size limits depend on what's copied next
+    let next_expiry = stats
+        .types
+        .iter()
+        .filter_map(|t| Some(t.oldest_expiring? + retention.max_age_for(t.content_type).0))
+        .min();
+
+    if json {
+        let report = serde_json::json!({
+            "entries": entries,
+            "bytes": bytes,
+            "types": stats.types.iter().map(|t| serde_json::json!({
+                "type": t.content_type.as_str(),
+                "count": t.count,
+                "bytes": t.bytes,
+                "oldest": t.oldest,
+            })).collect::<Vec<_>>(),
+            "window": window,
+            "captures": captures,
+            "captures_per_hour": per_hour,
+            "hourly": stats.hourly.iter().map(|(hour, count)| serde_json::json!({
+                "hour": hour,
+                "count": count,
+            })).collect::<Vec<_>>(),
+            "top": stats.top.iter().map(|e| serde_json::json!({
+                "id": e.id,
+                "type": e.content_type.as_str(),
+                "preview": e.display_preview(60),
+                "copies": e.copy_count,
+                "uses": e.use_count,
+            })).collect::<Vec<_>>(),
+            "disk": stats.disk.iter().map(|d| (d.name, d.bytes)).collect::<BTreeMap<_, _>>(),
+            "oldest": oldest,
+            "next_expiry": next_expiry,
+        });
+        println!("{report:#}");
+        return Ok(());
+    }
+
+    println!(
+        "{} {} ({})",
+        "Entries:".bold(),
+        entries,
+        format_size(bytes as u64)
+    );
+    for t in &stats.types {
+        println!(
+            "  {:<6} {:>6}  {}",
+            t.content_type.as_str(),
+            t.count,
+            format_size(t.bytes as u64)
+        );
+    }
+    println!(
+        "{} {} in the last {} ({:.1}/hour)",
+        "Copies:".bold(),
+        captures,
+        Age(window),
+        per_hour
+    );
+    if let Some(oldest) = oldest {
+        println!(
+            "{} {} ({} ago)",
+            "Oldest:".bold(),
+            format_timestamp(oldest),
+            format_span(now - oldest)
+        );
+    }
+    if let Some(expiry) = next_expiry {
+        let when = match expiry - now {
+            left if left > 0 => format!("in {}", format_span(left)),
+            _ => "at the next cleanup".to_string(),
+        };
+        println!(
+            "{} {} ({when})",
+            "Next expiry:".bold(),
+            format_timestamp(expiry)
+        );
+    }
+    let disk: Vec<String> = stats
+        .disk
+        .iter()
+        .map(|d| format!("{} {}", d.name, format_size(d.bytes)))
+        .collect();
+    if !disk.is_empty() {
+        println!("{} {}", "Disk:".bold(), disk.join(", "));
+    }
+    if !stats.top.is_empty() {
+        println!("{}", "Most reused:".bold());
+        for e in &stats.top {
+            println!(
+                "  {:>5}  {} copies, {} uses  {}",
+                e.id,
+                e.copy_count,
+                e.use_count,
+                e.display_preview(50).dimmed()
+            );
+        }
+    }
+    if histogram {
+        print_histogram(&stats.hourly, now - window, now);
+    }
+    Ok(())
+}
+
+/// Copies per hour from `start` to `end`, or per day over more than two
+/// days, as a bar chart
+fn print_histogram(hourly: &[(i64, usize)], start: i64, end: i64) {
+    let (bucket, label) = match end - start {
+        span if span <= 2 * 86_400 => (3600, "%H:00"),
+        _ => (86_400, "%m-%d"),
+    };
+    let local = |ts: i64| {
+        chrono::DateTime::from_timestamp(ts, 0).map(|dt| dt.with_timezone(&chrono::Local))
+    };
+    // Buckets follow local midnight; hours are the same everywhere
+    let offset = local(start).map_or(0, |dt| dt.offset().local_minus_utc() as i64);
+    let bucket_of = |ts: i64| (ts + offset).div_euclid(bucket) * bucket - offset;
+
+    let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+    let mut at = bucket_of(start);
+    while at <= end {
+        counts.insert(at, 0);
+        at += bucket;
+    }
+    for &(hour, count) in hourly {
+        *counts.entry(bucket_of(hour)).or_default() += count;
+    }
+    let max = counts.values().copied().max().unwrap_or(0).max(1);
+    for (at, count) in counts {
+        let label = local(at)
+            .map(|dt| dt.format(label).to_string())
+            .unwrap_or_default();
+        let bar = "█".repeat(count * 40 / max);
+        println!("{label:>6} {} {count}", bar.cyan());
+    }
+}
+
+/// A byte count with a binary unit, like `1.5 MiB`
+fn format_size(bytes: u64) -> String {
+    let units = [("GiB", 1 << 30), ("MiB", 1 << 20), ("KiB", 1 << 10)];
+    match units.iter().find(|&&(_, size)| bytes >= size) {
+        Some(&(name, size)) => format!("{:.1} {name}", bytes as f64 / size as f64),
+        None => format!("{bytes} B"),
+    }
+}
+
+/// A duration in its two largest units, like `3d 4h`
+fn format_span(secs: i64) -> String {
+    let units = [("d", 86_400), ("h", 3600), ("m", 60), ("s", 1)];
+    let Some(i) = units.iter().position(|&(_, unit)| secs >= unit) else {
+        return "0s".to_string();
+    };
+    let (name, unit) = units[i];
+    let mut span = format!("{}{name}", secs / unit);
+    if let Some(&(next, next_unit)) = units.get(i + 1) {
+        let rest = secs % unit / next_unit;
+        if rest > 0 {
+            span += &format!(" {rest}{next}");
+        }
+    }
+    span
+}
+
+fn format_timestamp(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .map(|dt| {
+            dt.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
 fn cmd_popup() -> sticky_one::Result<()> {
     run_popup().map_err(|e| StickyError::Daemon(e.to_string()))
 }
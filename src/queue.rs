@@ -0,0 +1,196 @@
+use crate::config::queue_path;
+use crate::entry::Entry;
+use crate::error::{Result, StickyError};
+use crate::lockfile;
+use crate::storage::HistoryStore;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// Which end of the queue `next` takes from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueMode {
+    /// Oldest copy first
+    #[default]
+    Fifo,
+    /// Newest copy first
+    Lifo,
+}
+
+impl std::fmt::Display for QueueMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            QueueMode::Fifo => "fifo",
+            QueueMode::Lifo => "lifo",
+        })
+    }
+}
+
+/// Copies collected while queue mode is on, by entry id
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Queue {
+    pub mode: QueueMode,
+    pub items: VecDeque<i64>,
+    /// Hash of the item `next` just put on the clipboard. The daemon skips
+    /// the clipboard change it causes instead of recording it again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    written: Option<String>,
+}
+
+/// The queue state shared by the daemon and the CLI: a JSON file in the
+/// runtime dir, empty or missing while queue mode is off. Every access
+/// holds a lock on it.
+pub struct QueueFile {
+    path: PathBuf,
+}
+
+impl QueueFile {
+    pub fn open() -> Self {
+        Self::at(&queue_path())
+    }
+
+    pub fn at(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Turn queue mode on with an empty queue, dropping any earlier one
+    pub fn start(&self, mode: QueueMode) -> Result<()> {
+        self.update(|queue| {
+            *queue = Some(Queue {
+                mode,
+                ..Default::default()
+            });
+            Ok(())
+        })
+    }
+
+    /// Turn queue mode off. Returns the queue as it was, if it was on.
+    pub fn stop(&self) -> Result<Option<Queue>> {
+        self.update(|queue| Ok(queue.take()))
+    }
+
+    pub fn status(&self) -> Result<Option<Queue>> {
+        self.update(|queue| Ok(queue.clone()))
+    }
+
+    /// Handle a new clipboard entry: `record` it, and queue its id if queue
+    /// mode is on. Returns the id, or `None` without recording when it's
+    /// the item `next` just put on the clipboard.
+    pub fn capture(&self, hash: &str, record: impl FnOnce() -> Result<i64>) -> Result<Option<i64>> {
+        self.update(|queue| {
+            let Some(queue) = queue else {
+                return record().map(Some);
+            };
+            // Only the change right after `next` can be its own
+            if queue.written.take().as_deref() == Some(hash) {
+                return Ok(None);
+            }
+            let id = record()?;
+            queue.items.push_back(id);
+            Ok(Some(id))
+        })
+    }
+
+    /// Take the next item off the queue, skipping entries deleted since
+    /// they were queued. The caller puts it on the clipboard.
+    pub fn next(&self, store: &dyn HistoryStore) -> Result<Option<Entry>> {
+        self.update(|queue| {
+            let queue = queue.as_mut().ok_or(StickyError::NotQueueing)?;
+            loop {
+                let id = match queue.mode {
+                    QueueMode::Fifo => queue.items.pop_front(),
+                    QueueMode::Lifo => queue.items.pop_back(),
+                };
+                let Some(id) = id else {
+                    return Ok(None);
+                };
+                match store.get_by_id(id) {
+                    Ok(entry) => {
+                        queue.written = Some(entry.hash.clone());
+                        return Ok(Some(entry));
+                    }
+                    Err(StickyError::NotFound(_)) => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        })
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut Option<Queue>) -> Result<T>) -> Result<T> {
+        lockfile::update(&self.path, StickyError::Queue, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+    use std::fs;
+
+    fn temp_queue(name: &str) -> (QueueFile, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("syo-test-queue-{name}-{}", std::process::id()));
+        (QueueFile::at(&dir.join("queue.json")), dir)
+    }
+
+    fn copy(queue: &QueueFile, store: &MemoryStore, text: &str) -> bool {
+        let entry = Entry::new_text(text.into());
+        queue
+            .capture(&entry.hash, || store.insert(&entry))
+            .unwrap()
+            .is_some()
+    }
+
+    fn next_text(queue: &QueueFile, store: &MemoryStore) -> Option<String> {
+        queue.next(store).unwrap().and_then(|e| e.content)
+    }
+
+    #[test]
+    fn fifo_queue_skips_its_own_writes() {
+        let (queue, dir) = temp_queue("fifo");
+        let store = MemoryStore::new();
+        assert!(copy(&queue, &store, "before"));
+        assert!(matches!(queue.next(&store), Err(StickyError::NotQueueing)));
+
+        queue.start(QueueMode::Fifo).unwrap();
+        copy(&queue, &store, "one");
+        copy(&queue, &store, "two");
+        let three = Entry::new_text("three".into());
+        copy(&queue, &store, "three");
+        store
+            .delete(store.find_by_hash(&three.hash).unwrap().unwrap())
+            .unwrap();
+
+        assert_eq!(next_text(&queue, &store).as_deref(), Some("one"));
+        assert!(
+            !copy(&queue, &store, "one"),
+            "the pasted item isn't queued again"
+        );
+        assert_eq!(next_text(&queue, &store).as_deref(), Some("two"));
+        assert_eq!(next_text(&queue, &store), None);
+        assert_eq!(store.count().unwrap(), 3);
+
+        assert!(queue.stop().unwrap().is_some());
+        assert!(queue.status().unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lifo_queue_pastes_newest_first() {
+        let (queue, dir) = temp_queue("lifo");
+        let store = MemoryStore::new();
+        queue.start(QueueMode::Lifo).unwrap();
+        copy(&queue, &store, "one");
+        copy(&queue, &store, "two");
+        assert_eq!(queue.status().unwrap().unwrap().items.len(), 2);
+        assert_eq!(next_text(&queue, &store).as_deref(), Some("two"));
+        // A copy other than the pasted item is queued as usual
+        assert!(copy(&queue, &store, "three"));
+        assert_eq!(next_text(&queue, &store).as_deref(), Some("three"));
+        assert_eq!(next_text(&queue, &store).as_deref(), Some("one"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
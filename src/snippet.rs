@@ -0,0 +1,316 @@
+use crate::clipboard::{self, ClipboardContent};
+use crate::config::snippets_path;
+use crate::entry::normalize_tag;
+use crate::error::{Result, StickyError};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Named text templates that never expire, kept in `snippets.toml` as
+/// `name = "body"` pairs so they can also be edited by hand. Names follow
+/// the same rules as tags.
+#[derive(Debug)]
+pub struct Snippets {
+    path: PathBuf,
+    items: BTreeMap<String, String>,
+}
+
+impl Snippets {
+    pub fn load() -> Result<Self> {
+        Self::load_from(&snippets_path())
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let items = match fs::read_to_string(path) {
+            Ok(s) => toml::from_str(&s)
+                .map_err(|e| StickyError::Snippet(format!("{}: {e}", path.display())))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            items,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content =
+            toml::to_string_pretty(&self.items).map_err(|e| StickyError::Snippet(e.to_string()))?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&self.path)?;
+        // Also tighten a file created before snippets were kept private
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<&str> {
+        let name = normalize_name(name)?;
+        self.items
+            .get(&name)
+            .map(String::as_str)
+            .ok_or_else(|| StickyError::Snippet(format!("no snippet named {name:?}")))
+    }
+
+    /// Add a snippet, or replace the body of an existing one
+    pub fn set(&mut self, name: &str, body: String) -> Result<()> {
+        self.items.insert(normalize_name(name)?, body);
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        normalize_name(name).is_ok_and(|name| self.items.contains_key(&name))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        let name = normalize_name(name)?;
+        self.items
+            .remove(&name)
+            .map(|_| ())
+            .ok_or_else(|| StickyError::Snippet(format!("no snippet named {name:?}")))
+    }
+
+    /// Name and body of every snippet, by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.items.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Snippets whose name or body contains `query`, ignoring case
+    pub fn search<'a>(&'a self, query: &str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        let query = query.to_lowercase();
+        self.iter().filter(move |(name, body)| {
+            name.contains(&query) || body.to_lowercase().contains(&query)
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+fn normalize_name(name: &str) -> Result<String> {
+    normalize_tag(name).ok_or_else(|| StickyError::Snippet(format!("invalid name: {name:?}")))
+}
+
+/// Where placeholder values that depend on the caller come from
+pub trait Inputs {
+    /// Current clipboard text, for `{clipboard}`
+    fn clipboard(&mut self) -> Result<String>;
+    /// The user's answer to `{input:Prompt}`
+    fn input(&mut self, prompt: &str) -> Result<String>;
+}
+
+/// Reads `{clipboard}` from the system clipboard and answers prompts from
+/// a list collected beforehand, in order
+pub struct Answers(pub std::collections::VecDeque<String>);
+
+impl Inputs for Answers {
+    fn clipboard(&mut self) -> Result<String> {
+        match clipboard::read()? {
+            ClipboardContent::Text(text) => Ok(text),
+            ClipboardContent::Image(_) | ClipboardContent::Empty => Ok(String::new()),
+        }
+    }
+
+    fn input(&mut self, prompt: &str) -> Result<String> {
+        self.0
+            .pop_front()
+            .ok_or_else(|| StickyError::Snippet(format!("no answer for {prompt:?}")))
+    }
+}
+
+/// Fill in a template's placeholders: `{date}`/`{date:FORMAT}` and
+/// `{time}`/`{time:FORMAT}` (strftime, local time), `{clipboard}`,
+/// `{uuid}`, `{env:NAME}` and `{input:Prompt}`. `{{` and `}}` stand for
+/// literal braces; anything else in braces is left as it is.
+pub fn expand(template: &str, inputs: &mut dyn Inputs) -> Result<String> {
+    render(template, |name, arg| {
+        let value = match (name, arg) {
+            ("date", format) => format_now(format.unwrap_or("%F"))?,
+            ("time", format) => format_now(format.unwrap_or("%T"))?,
+            ("clipboard", None) => inputs.clipboard()?,
+            ("uuid", None) => uuid_v4(),
+            ("env", Some(var)) => std::env::var(var)
+                .map_err(|_| StickyError::Snippet(format!("${var} is not set")))?,
+            ("input", Some(prompt)) => inputs.input(prompt)?,
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    })
+}
+
+/// The `{input:Prompt}` prompts of a template, in order
+pub fn prompts(template: &str) -> Vec<String> {
+    let mut prompts = Vec::new();
+    let _ = render(template, |name, arg| {
+        if let ("input", Some(prompt)) = (name, arg) {
+            prompts.push(prompt.to_string());
+        }
+        Ok(None)
+    });
+    prompts
+}
+
+/// Copy `template`, replacing each `{name}` or `{name:arg}` with what
+/// `placeholder` returns for it, or keeping it when that's `None`
+fn render(
+    template: &str,
+    mut placeholder: impl FnMut(&str, Option<&str>) -> Result<Option<String>>,
+) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        // The shortest `{...}` with no braces inside
+        let close = tail[1..].find(['{', '}']).map(|j| j + 1);
+        match close {
+            Some(j) if tail[1..].starts_with(|c: char| c != '}') && &tail[j..=j] == "}" => {
+                let inner = &tail[1..j];
+                let (name, arg) = match inner.split_once(':') {
+                    Some((name, arg)) => (name, Some(arg)),
+                    None => (inner, None),
+                };
+                match placeholder(name, arg)? {
+                    Some(value) => out.push_str(&value),
+                    None => out.push_str(&tail[..=j]),
+                }
+                rest = &tail[j + 1..];
+            }
+            _ => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn format_now(format: &str) -> Result<String> {
+    use chrono::format::{Item, StrftimeItems};
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.contains(&Item::Error) {
+        return Err(StickyError::Snippet(format!(
+            "invalid time format: {format:?}"
+        )));
+    }
+    Ok(chrono::Local::now()
+        .format_with_items(items.into_iter())
+        .to_string())
+}
+
+/// A random (version 4) UUID
+fn uuid_v4() -> String {
+    let mut b = [0u8; 16];
+    OsRng.fill_bytes(&mut b);
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let hex: String = b.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed;
+
+    impl Inputs for Fixed {
+        fn clipboard(&mut self) -> Result<String> {
+            Ok("clip".into())
+        }
+
+        fn input(&mut self, prompt: &str) -> Result<String> {
+            Ok(format!("<{prompt}>"))
+        }
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        std::env::set_var("SYO_TEST_SNIPPET_VAR", "val");
+        let out = expand(
+            "{clipboard} {input:Name} {env:SYO_TEST_SNIPPET_VAR} {date:%Y}",
+            &mut Fixed,
+        )
+        .unwrap();
+        let year = chrono::Local::now().format("%Y").to_string();
+        assert_eq!(out, format!("clip <Name> val {year}"));
+        assert_eq!(expand("{date}", &mut Fixed).unwrap().len(), 10);
+
+        let uuid = expand("{uuid}", &mut Fixed).unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        let code = r#"fn main() { println!("{}", x); } {{date}} {unknown} {date"#;
+        assert_eq!(
+            expand(code, &mut Fixed).unwrap(),
+            r#"fn main() { println!("{}", x); } {date} {unknown} {date"#
+        );
+        assert!(expand("{env:SYO_TEST_UNSET_VAR}", &mut Fixed).is_err());
+        assert!(expand("{date:%Q}", &mut Fixed).is_err());
+    }
+
+    #[test]
+    fn lists_prompts_in_order() {
+        assert_eq!(
+            prompts("Dear {input:Name}, re {input:Subject} on {date}"),
+            vec!["Name", "Subject"]
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("syo-test-snippets-{}", std::process::id()));
+        let path = dir.join("snippets.toml");
+        let mut snippets = Snippets::load_from(&path).unwrap();
+        assert!(snippets.is_empty());
+        snippets.set("Sig", "Best,\n{env:USER}".into()).unwrap();
+        assert!(snippets.set("bad name", String::new()).is_err());
+        snippets.save().unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let mut snippets = Snippets::load_from(&path).unwrap();
+        assert_eq!(snippets.get("sig").unwrap(), "Best,\n{env:USER}");
+        assert_eq!(snippets.search("BEST").count(), 1);
+        snippets.remove("sig").unwrap();
+        assert!(snippets.remove("sig").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
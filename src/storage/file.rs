@@ -0,0 +1,230 @@
+use super::memory::{Op, State, StateAccess};
+use super::DiskUsage;
+use crate::config::TrashConfig;
+use crate::error::{Result, StickyError};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// History as an append-only log of changes, one JSON object per line,
+/// replayed into memory on open. Nothing is rewritten, so the file grows
+/// with every change; meant for small histories and minimal setups.
+/// Processes share the log through file locks and pick up each other's
+/// changes before every read or write.
+#[derive(Debug)]
+pub struct FileStore {
+    replica: Mutex<Replica>,
+    trash: TrashConfig,
+}
+
+#[derive(Debug)]
+struct Replica {
+    file: File,
+    state: State,
+    /// Bytes of the log applied to `state` so far
+    offset: u64,
+}
+
+impl FileStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut options = OpenOptions::new();
+        options.read(true).append(true).create(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let store = Self {
+            replica: Mutex::new(Replica {
+                file: options.open(path)?,
+                state: State::default(),
+                offset: 0,
+            }),
+            trash: TrashConfig::off(),
+        };
+        store.read(|_| Ok(()))?;
+        Ok(store)
+    }
+}
+
+impl StateAccess for FileStore {
+    fn read<T>(&self, f: impl FnOnce(&State) -> Result<T>) -> Result<T> {
+        let mut replica = self.replica.lock().unwrap();
+        replica.file.lock_shared()?;
+        let result = replica.catch_up().and_then(|()| f(&replica.state));
+        replica.file.unlock()?;
+        result
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&State) -> Result<(Op, T)>) -> Result<T> {
+        let mut replica = self.replica.lock().unwrap();
+        replica.file.lock()?;
+        let result = replica.append(f);
+        replica.file.unlock()?;
+        result
+    }
+
+    fn trash(&self) -> TrashConfig {
+        self.trash
+    }
+
+    fn trash_mut(&mut self) -> &mut TrashConfig {
+        &mut self.trash
+    }
+
+    fn disk_usage(&self) -> Result<Vec<DiskUsage>> {
+        let replica = self.replica.lock().unwrap();
+        Ok(vec![DiskUsage {
+            name: "history log",
+            bytes: replica.file.metadata()?.len(),
+        }])
+    }
+}
+
+impl Replica {
+    /// Apply lines appended since the last call, by any process
+    fn catch_up(&mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let mut new = Vec::new();
+        self.file.read_to_end(&mut new)?;
+
+        // Writers hold an exclusive lock, so a line without its newline was
+        // cut short by a crash. It's dropped by the next append.
+        let complete = new.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let mut pos = self.offset;
+        for line in new[..complete].split_inclusive(|&b| b == b'\n') {
+            let op: Op = serde_json::from_slice(line)
+                .map_err(|e| StickyError::Journal(format!("at byte {pos}: {e}")))?;
+            self.state.apply(op)?;
+            pos += line.len() as u64;
+        }
+        self.offset = pos;
+        Ok(())
+    }
+
+    fn append<T>(&mut self, f: impl FnOnce(&State) -> Result<(Op, T)>) -> Result<T> {
+        self.catch_up()?;
+        if self.file.metadata()?.len() > self.offset {
+            self.file.set_len(self.offset)?;
+        }
+
+        let (op, value) = f(&self.state)?;
+        if op.is_noop() {
+            return Ok(value);
+        }
+        let mut line = serde_json::to_vec(&op).map_err(|e| StickyError::Journal(e.to_string()))?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+        self.offset += line.len() as u64;
+        self.state.apply(op)?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Age;
+    use crate::entry::Entry;
+    use crate::storage::{HistoryStore, SortOrder};
+
+    fn temp_log(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("syo-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("history.jsonl")
+    }
+
+    #[test]
+    fn reopen_replays_log() {
+        let path = temp_log("file-replay");
+        let store = FileStore::open(&path).unwrap();
+        let a = store.insert(&Entry::new_text("a".into())).unwrap();
+        let b = store.insert(&Entry::new_text("b".into())).unwrap();
+        store.set_pinned(a, true).unwrap();
+        store.add_tags(b, &["work".into()]).unwrap();
+        store.delete(b).unwrap();
+        let c = store.insert(&Entry::new_text("c".into())).unwrap();
+        drop(store);
+
+        let store = FileStore::open(&path).unwrap();
+        let ids: Vec<_> = store.list(10).unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![c, a]);
+        assert_eq!(c, 3, "ids aren't reused after a delete");
+        assert!(store.get_by_id(a).unwrap().pinned);
+        assert!(store.list_tags().unwrap().is_empty());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn stores_see_each_others_changes() {
+        let path = temp_log("file-shared");
+        let first = FileStore::open(&path).unwrap();
+        let second = FileStore::open(&path).unwrap();
+
+        let id = first.insert(&Entry::new_text("shared".into())).unwrap();
+        second.record_use(id).unwrap();
+        assert_eq!(
+            second.insert(&Entry::new_text("other".into())).unwrap(),
+            id + 1
+        );
+
+        let top = first.list_sorted(1, SortOrder::Frecency).unwrap();
+        assert_eq!(top[0].id, id);
+        assert_eq!(first.get_by_id(id).unwrap().use_count, 1);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn trash_survives_reopen() {
+        let path = temp_log("file-trash");
+        let mut store = FileStore::open(&path).unwrap();
+        store.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        let id = store.insert(&Entry::new_text("trashed".into())).unwrap();
+        store.delete(id).unwrap();
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        store.cleanup_old(&Default::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap().lines().count(),
+            lines,
+            "no-op cleanups aren't logged"
+        );
+        drop(store);
+
+        let store = FileStore::open(&path).unwrap();
+        assert_eq!(store.list_trash().unwrap()[0].entry.id, id);
+        assert_eq!(store.undo().unwrap(), vec![id]);
+        assert_eq!(store.count().unwrap(), 1);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn drops_partial_line() {
+        let path = temp_log("file-partial");
+        let store = FileStore::open(&path).unwrap();
+        store.insert(&Entry::new_text("kept".into())).unwrap();
+        drop(store);
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"op\":\"ins")
+            .unwrap();
+
+        let store = FileStore::open(&path).unwrap();
+        assert_eq!(store.count().unwrap(), 1);
+        store.insert(&Entry::new_text("next".into())).unwrap();
+
+        let store = FileStore::open(&path).unwrap();
+        assert_eq!(store.count().unwrap(), 2);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
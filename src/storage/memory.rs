@@ -0,0 +1,1117 @@
+use super::{
+    normalize_tags, register_copy, register_name, CleanupReport, DiskUsage, EntryFilter,
+    HistoryStore, RegisterItem, Revision, SortOrder, Stats, TagInfo, TrashItem, TypeStats,
+};
+use crate::archive::entry_record;
+use crate::config::{RetentionConfig, TrashConfig};
+use crate::entry::{hash_content, normalize_tag, ContentType, Entry, EntrySummary};
+use crate::error::{Result, StickyError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+
+/// History kept in memory only, for embedders and tests. Behaves like the
+/// SQLite store, minus encryption and the blob store.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: Mutex<State>,
+    trash: TrashConfig,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A change to the history, planned against the current `State` and then
+/// applied. The file backend logs these, one per line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(super) enum Op {
+    Insert {
+        #[serde(with = "entry_record")]
+        entry: Entry,
+    },
+    /// Insert a copy from the clipboard, logging when it was copied
+    Capture {
+        #[serde(with = "entry_record")]
+        entry: Entry,
+    },
+    /// Forget copies logged before `before`
+    ForgetCaptures {
+        before: i64,
+    },
+    Use {
+        id: i64,
+        at: i64,
+    },
+    Pin {
+        id: i64,
+        pinned: bool,
+    },
+    /// Replace a text entry's content, keeping the old as a revision
+    Edit {
+        id: i64,
+        content: String,
+        at: i64,
+    },
+    /// Replace a text entry's content without keeping the old
+    Replace {
+        id: i64,
+        content: String,
+    },
+    Tag {
+        id: i64,
+        tags: Vec<String>,
+    },
+    Untag {
+        id: i64,
+        tags: Vec<String>,
+    },
+    Keep {
+        tag: String,
+        keep: bool,
+    },
+    Delete {
+        ids: Vec<i64>,
+    },
+    /// Move entries to the trash as one deletion
+    Trash {
+        ids: Vec<i64>,
+        at: i64,
+    },
+    /// Move trashed entries back
+    Restore {
+        ids: Vec<i64>,
+    },
+    /// Drop entries from the trash
+    Purge {
+        ids: Vec<i64>,
+    },
+    SetRegister {
+        name: char,
+        #[serde(with = "entry_record")]
+        entry: Entry,
+    },
+    ClearRegister {
+        name: char,
+    },
+}
+
+impl Op {
+    /// Changes nothing, so there's no need to log it
+    pub(super) fn is_noop(&self) -> bool {
+        match self {
+            Op::Delete { ids }
+            | Op::Trash { ids, .. }
+            | Op::Restore { ids }
+            | Op::Purge { ids } => ids.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+/// Entries and tags of a history held in memory
+#[derive(Debug, Default)]
+pub(super) struct State {
+    entries: BTreeMap<i64, Entry>,
+    by_hash: HashMap<String, i64>,
+    /// Ids are never reused, even after deletes
+    last_id: i64,
+    /// Tags marked as kept. They outlive their entries.
+    kept: BTreeSet<String>,
+    trash: BTreeMap<i64, Trashed>,
+    /// Number of the latest `Op::Trash`, so `undo` can find its entries
+    last_batch: i64,
+    /// Earlier content of edited entries, oldest first
+    revisions: HashMap<i64, Vec<Revision>>,
+    /// Register contents, outside the history
+    registers: BTreeMap<char, Entry>,
+    /// When each captured copy was made, oldest first
+    captures: Vec<i64>,
+}
+
+#[derive(Debug)]
+struct Trashed {
+    entry: Entry,
+    deleted_at: i64,
+    batch: i64,
+}
+
+/// Access to the `State` behind a store. Reads see every change made so
+/// far; updates plan an `Op` and its result, then apply the op.
+pub(super) trait StateAccess {
+    fn read<T>(&self, f: impl FnOnce(&State) -> Result<T>) -> Result<T>;
+    fn update<T>(&self, f: impl FnOnce(&State) -> Result<(Op, T)>) -> Result<T>;
+    fn trash(&self) -> TrashConfig;
+    fn trash_mut(&mut self) -> &mut TrashConfig;
+
+    /// Files the state is kept in
+    fn disk_usage(&self) -> Result<Vec<DiskUsage>> {
+        Ok(Vec::new())
+    }
+}
+
+impl StateAccess for MemoryStore {
+    fn trash(&self) -> TrashConfig {
+        self.trash
+    }
+
+    fn trash_mut(&mut self) -> &mut TrashConfig {
+        &mut self.trash
+    }
+
+    fn read<T>(&self, f: impl FnOnce(&State) -> Result<T>) -> Result<T> {
+        f(&self.state.lock().unwrap())
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&State) -> Result<(Op, T)>) -> Result<T> {
+        let mut state = self.state.lock().unwrap();
+        let (op, value) = f(&state)?;
+        state.apply(op)?;
+        Ok(value)
+    }
+}
+
+impl State {
+    fn insert(&mut self, mut entry: Entry) {
+        if !self.merge(&entry) {
+            self.last_id += 1;
+            entry.id = self.last_id;
+            self.add(entry);
+        }
+    }
+
+    pub(super) fn apply(&mut self, op: Op) -> Result<()> {
+        match op {
+            Op::Insert { entry } => self.insert(entry),
+            Op::Capture { entry } => {
+                self.captures.push(entry.last_seen);
+                self.insert(entry);
+            }
+            Op::ForgetCaptures { before } => self.captures.retain(|&at| at >= before),
+            Op::Use { id, at } => {
+                let entry = self.entry_mut(id)?;
+                entry.use_count += 1;
+                entry.last_used = Some(at);
+            }
+            Op::Pin { id, pinned } => self.entry_mut(id)?.pinned = pinned,
+            Op::Edit { id, content, at } => self.rewrite(id, content, Some(at))?,
+            Op::Replace { id, content } => self.rewrite(id, content, None)?,
+            Op::Tag { id, tags } => add_tags(self.entry_mut(id)?, &tags),
+            Op::Untag { id, tags } => self.entry_mut(id)?.tags.retain(|t| !tags.contains(t)),
+            Op::Keep { tag, keep } => {
+                if keep {
+                    self.kept.insert(tag);
+                } else {
+                    self.kept.remove(&tag);
+                }
+            }
+            Op::Delete { ids } => {
+                for id in ids {
+                    self.remove(id);
+                    self.revisions.remove(&id);
+                }
+            }
+            Op::Trash { ids, at } => {
+                if !ids.is_empty() {
+                    self.last_batch += 1;
+                }
+                for id in ids {
+                    if let Some(entry) = self.remove(id) {
+                        let trashed = Trashed {
+                            entry,
+                            deleted_at: at,
+                            batch: self.last_batch,
+                        };
+                        self.trash.insert(id, trashed);
+                    }
+                }
+            }
+            Op::Restore { ids } => {
+                for id in ids {
+                    if let Some(trashed) = self.trash.remove(&id) {
+                        if self.merge(&trashed.entry) {
+                            let target = self.by_hash[&trashed.entry.hash];
+                            if let Some(revisions) = self.revisions.remove(&id) {
+                                self.revisions.entry(target).or_default().extend(revisions);
+                            }
+                        } else {
+                            self.add(trashed.entry);
+                        }
+                    }
+                }
+            }
+            Op::Purge { ids } => {
+                for id in ids {
+                    self.trash.remove(&id);
+                    self.revisions.remove(&id);
+                }
+            }
+            Op::SetRegister { name, entry } => {
+                self.registers.insert(name, entry);
+            }
+            Op::ClearRegister { name } => {
+                self.registers.remove(&name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fold `entry` into the stored entry with the same content, if any
+    /// Replace a text entry's content, keeping the old as a revision
+    /// replaced `at` if given, and merging it into an entry already holding
+    /// the new content
+    fn rewrite(&mut self, id: i64, content: String, at: Option<i64>) -> Result<()> {
+        let mut revisions = self.revisions.remove(&id).unwrap_or_default();
+        let mut entry = self.remove(id).ok_or(StickyError::NotFound(id))?;
+        if let Some(at) = at {
+            revisions.push(Revision {
+                content_type: entry.content_type,
+                content: entry.content.take().unwrap_or_default(),
+                replaced_at: at,
+            });
+        }
+
+        let edited = Entry::new_text(content);
+        entry.content_type = edited.content_type;
+        entry.content = edited.content;
+        entry.hash = edited.hash;
+        let target = if self.merge(&entry) {
+            self.by_hash[&entry.hash]
+        } else {
+            self.add(entry);
+            id
+        };
+        self.revisions.entry(target).or_default().extend(revisions);
+        Ok(())
+    }
+
+    fn merge(&mut self, entry: &Entry) -> bool {
+        let Some(id) = self.by_hash.get(&entry.hash) else {
+            return false;
+        };
+        let stored = self.entries.get_mut(id).expect("hash index out of sync");
+        stored.last_seen = stored.last_seen.max(entry.last_seen);
+        stored.copy_count += entry.copy_count;
+        stored.pinned |= entry.pinned;
+        stored.use_count += entry.use_count;
+        stored.last_used = stored.last_used.max(entry.last_used);
+        add_tags(stored, &entry.tags);
+        true
+    }
+
+    fn add(&mut self, mut entry: Entry) {
+        let tags = std::mem::take(&mut entry.tags);
+        add_tags(&mut entry, &tags);
+        self.by_hash.insert(entry.hash.clone(), entry.id);
+        self.entries.insert(entry.id, entry);
+    }
+
+    /// Take an entry out of the history. Its revisions stay, in case it's
+    /// only being trashed.
+    fn remove(&mut self, id: i64) -> Option<Entry> {
+        let entry = self.entries.remove(&id)?;
+        self.by_hash.remove(&entry.hash);
+        Some(entry)
+    }
+
+    /// Restore trashed entries, returning the id each ends up with
+    fn plan_restore(&self, ids: Vec<i64>) -> (Op, Vec<i64>) {
+        let mut restored: HashMap<&str, i64> = HashMap::new();
+        let mut live = Vec::with_capacity(ids.len());
+        for id in &ids {
+            let entry = &self.trash[id].entry;
+            let hash = entry.hash.as_str();
+            let target = self
+                .by_hash
+                .get(hash)
+                .or(restored.get(hash))
+                .copied()
+                .unwrap_or(entry.id);
+            restored.entry(hash).or_insert(target);
+            live.push(target);
+        }
+        (Op::Restore { ids }, live)
+    }
+
+    fn entry(&self, id: i64) -> Result<&Entry> {
+        self.entries.get(&id).ok_or(StickyError::NotFound(id))
+    }
+
+    fn entry_mut(&mut self, id: i64) -> Result<&mut Entry> {
+        self.entries.get_mut(&id).ok_or(StickyError::NotFound(id))
+    }
+
+    fn tag_exists(&self, tag: &str) -> bool {
+        self.kept.contains(tag)
+            || self
+                .entries
+                .values()
+                .any(|e| e.tags.iter().any(|t| t == tag))
+    }
+
+    /// Pinned, or in a kept collection
+    fn exempt(&self, entry: &Entry) -> bool {
+        entry.pinned || entry.tags.iter().any(|t| self.kept.contains(t))
+    }
+
+    /// Entries in listing order, filtered
+    fn sorted(&self, order: SortOrder, mut keep: impl FnMut(&Entry) -> bool) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self.entries.values().filter(|e| keep(e)).collect();
+        match order {
+            SortOrder::Recent => entries.sort_by_key(|e| std::cmp::Reverse((e.last_seen, e.id))),
+            SortOrder::Frecency => {
+                let now = chrono::Utc::now().timestamp();
+                entries.sort_by_key(|e| std::cmp::Reverse((frecency(e, now), e.last_seen, e.id)))
+            }
+        }
+        entries
+    }
+
+    fn summaries<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> Vec<EntrySummary> {
+        entries.into_iter().map(Entry::summary).collect()
+    }
+
+    /// Ids of the entries the policy removes, and why
+    fn cleanup_plan(&self, policy: &RetentionConfig, now: i64) -> (Vec<i64>, CleanupReport) {
+        let mut report = CleanupReport::default();
+        let mut kept = Vec::new();
+        let mut doomed = Vec::new();
+        for entry in self.sorted(SortOrder::Recent, |e| !self.exempt(e)) {
+            if entry.last_seen >= now - policy.max_age_for(entry.content_type).0 {
+                kept.push(entry);
+                continue;
+            }
+            doomed.push(entry.id);
+            if policy.type_override(entry.content_type).is_some() {
+                report.expired_by_type += 1;
+            } else {
+                report.expired += 1;
+            }
+        }
+
+        if let Some(max) = policy.max_entries {
+            for entry in kept.drain(max.min(kept.len())..) {
+                doomed.push(entry.id);
+                report.over_count += 1;
+            }
+        }
+
+        if let Some(max) = policy.max_bytes {
+            let mut running = 0;
+            for entry in kept {
+                running += entry.size() as u64;
+                if running > max.0 {
+                    doomed.push(entry.id);
+                    report.over_size += 1;
+                }
+            }
+        }
+        (doomed, report)
+    }
+}
+
+impl<S: StateAccess + Send> HistoryStore for S {
+    fn insert(&self, entry: &Entry) -> Result<i64> {
+        insert(self, entry, |entry| Op::Insert { entry })
+    }
+
+    fn capture(&self, entry: &Entry) -> Result<i64> {
+        insert(self, entry, |entry| Op::Capture { entry })
+    }
+
+    fn find_by_hash(&self, hash: &str) -> Result<Option<i64>> {
+        self.read(|state| Ok(state.by_hash.get(hash).copied()))
+    }
+
+    fn get_latest_hash(&self) -> Result<Option<String>> {
+        self.read(|state| {
+            let latest = state.entries.values().max_by_key(|e| (e.last_seen, e.id));
+            Ok(latest.map(|e| e.hash.clone()))
+        })
+    }
+
+    fn get_by_id(&self, id: i64) -> Result<Entry> {
+        self.read(|state| state.entry(id).cloned())
+    }
+
+    fn delete_ids(&self, ids: &[i64]) -> Result<()> {
+        let to_trash = self.trash().enabled();
+        let mut ids = ids.to_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        self.update(|state| {
+            for &id in &ids {
+                state.entry(id)?;
+            }
+            Ok((removal(ids, to_trash), ()))
+        })
+    }
+
+    fn list_sorted(&self, limit: usize, order: SortOrder) -> Result<Vec<EntrySummary>> {
+        self.read(|state| {
+            Ok(State::summaries(
+                state.sorted(order, |_| true).into_iter().take(limit),
+            ))
+        })
+    }
+
+    fn list_pinned(&self) -> Result<Vec<EntrySummary>> {
+        self.read(|state| {
+            Ok(State::summaries(
+                state.sorted(SortOrder::Recent, |e| e.pinned),
+            ))
+        })
+    }
+
+    fn list_tagged(&self, tag: &str, limit: usize, order: SortOrder) -> Result<Vec<EntrySummary>> {
+        let tag = normalize_tag(tag).ok_or_else(|| StickyError::InvalidTag(tag.to_string()))?;
+        self.read(|state| {
+            let entries = state.sorted(order, |e| e.tags.contains(&tag));
+            Ok(State::summaries(entries.into_iter().take(limit)))
+        })
+    }
+
+    fn list_filtered(&self, filter: &EntryFilter) -> Result<Vec<EntrySummary>> {
+        let matcher = Matcher::new(filter)?;
+        self.read(|state| {
+            Ok(State::summaries(
+                state.sorted(SortOrder::Recent, |e| matcher.matches(e)),
+            ))
+        })
+    }
+
+    fn delete_where(&self, filter: &EntryFilter) -> Result<usize> {
+        let matcher = Matcher::new(filter)?;
+        let to_trash = self.trash().enabled();
+        self.update(|state| {
+            let ids: Vec<i64> = state
+                .sorted(SortOrder::Recent, |e| !e.pinned && matcher.matches(e))
+                .iter()
+                .map(|e| e.id)
+                .collect();
+            let count = ids.len();
+            Ok((removal(ids, to_trash), count))
+        })
+    }
+
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<EntrySummary>> {
+        let query = query.to_lowercase();
+        self.read(|state| {
+            let entries = state.sorted(SortOrder::Recent, |e| {
+                e.content_type != ContentType::Image
+                    && e.content
+                        .as_ref()
+                        .is_some_and(|c| c.to_lowercase().contains(&query))
+            });
+            Ok(State::summaries(entries.into_iter().take(limit)))
+        })
+    }
+
+    fn record_use(&self, id: i64) -> Result<()> {
+        let at = chrono::Utc::now().timestamp();
+        self.update(|state| {
+            state.entry(id)?;
+            Ok((Op::Use { id, at }, ()))
+        })
+    }
+
+    fn set_pinned(&self, id: i64, pinned: bool) -> Result<()> {
+        self.update(|state| {
+            state.entry(id)?;
+            Ok((Op::Pin { id, pinned }, ()))
+        })
+    }
+
+    fn edit(&self, id: i64, content: &str) -> Result<i64> {
+        let at = chrono::Utc::now().timestamp();
+        rewrite(self, id, content, |content| Op::Edit { id, content, at })
+    }
+
+    fn replace(&self, id: i64, content: &str) -> Result<i64> {
+        rewrite(self, id, content, |content| Op::Replace { id, content })
+    }
+
+    fn list_revisions(&self, id: i64) -> Result<Vec<Revision>> {
+        self.read(|state| {
+            state.entry(id)?;
+            Ok(state.revisions.get(&id).cloned().unwrap_or_default())
+        })
+    }
+
+    fn add_tags(&self, id: i64, tags: &[String]) -> Result<()> {
+        let tags = normalize_tags(tags)?;
+        self.update(|state| {
+            state.entry(id)?;
+            Ok((Op::Tag { id, tags }, ()))
+        })
+    }
+
+    fn remove_tags(&self, id: i64, tags: &[String]) -> Result<()> {
+        let tags = normalize_tags(tags)?;
+        self.update(|state| {
+            state.entry(id)?;
+            Ok((Op::Untag { id, tags }, ()))
+        })
+    }
+
+    fn list_tags(&self) -> Result<Vec<TagInfo>> {
+        self.read(|state| {
+            let mut counts: BTreeMap<&str, usize> =
+                state.kept.iter().map(|t| (t.as_str(), 0)).collect();
+            for tag in state.entries.values().flat_map(|e| &e.tags) {
+                *counts.entry(tag).or_default() += 1;
+            }
+            Ok(counts
+                .into_iter()
+                .map(|(name, count)| TagInfo {
+                    name: name.to_string(),
+                    count,
+                    keep: state.kept.contains(name),
+                })
+                .collect())
+        })
+    }
+
+    fn set_tag_keep(&self, tag: &str, keep: bool) -> Result<()> {
+        let name = normalize_tag(tag).ok_or_else(|| StickyError::InvalidTag(tag.to_string()))?;
+        self.update(|state| {
+            if !state.tag_exists(&name) {
+                return Err(StickyError::TagNotFound(name));
+            }
+            Ok((Op::Keep { tag: name, keep }, ()))
+        })
+    }
+
+    fn cleanup_old(&self, policy: &RetentionConfig) -> Result<CleanupReport> {
+        let now = chrono::Utc::now().timestamp();
+        let to_trash = self.trash().enabled() && self.trash().expired;
+        let mut report = self.update(|state| {
+            let (ids, report) = state.cleanup_plan(policy, now);
+            Ok((removal(ids, to_trash), report))
+        })?;
+        // Copies are only charted over the retention window
+        let before = now - policy.longest_age().0;
+        if self.read(|state| Ok(state.captures.iter().any(|&at| at < before)))? {
+            self.update(|_| Ok((Op::ForgetCaptures { before }, ())))?;
+        }
+        report.purged = self.purge_trash(false)?;
+        Ok(report)
+    }
+
+    fn clear(&self) -> Result<usize> {
+        let to_trash = self.trash().enabled();
+        self.update(|state| {
+            let ids: Vec<i64> = state
+                .sorted(SortOrder::Recent, |e| !e.pinned)
+                .iter()
+                .map(|e| e.id)
+                .collect();
+            let count = ids.len();
+            Ok((removal(ids, to_trash), count))
+        })
+    }
+
+    fn clear_all(&self) -> Result<usize> {
+        let to_trash = self.trash().enabled();
+        self.update(|state| {
+            let ids: Vec<i64> = state.entries.keys().copied().collect();
+            let count = ids.len();
+            Ok((removal(ids, to_trash), count))
+        })
+    }
+
+    fn set_trash(&mut self, trash: TrashConfig) {
+        *self.trash_mut() = trash;
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashItem>> {
+        self.read(|state| {
+            let mut trashed: Vec<&Trashed> = state.trash.values().collect();
+            trashed.sort_by_key(|t| std::cmp::Reverse((t.deleted_at, t.batch, t.entry.id)));
+            Ok(trashed
+                .into_iter()
+                .map(|t| TrashItem {
+                    entry: t.entry.summary(),
+                    deleted_at: t.deleted_at,
+                })
+                .collect())
+        })
+    }
+
+    fn restore(&self, id: i64) -> Result<i64> {
+        self.update(|state| {
+            if !state.trash.contains_key(&id) {
+                return Err(StickyError::NotInTrash(id));
+            }
+            let (op, live) = state.plan_restore(vec![id]);
+            Ok((op, live[0]))
+        })
+    }
+
+    fn undo(&self) -> Result<Vec<i64>> {
+        self.update(|state| {
+            let last = state.trash.values().map(|t| t.batch).max();
+            let ids = state
+                .trash
+                .values()
+                .filter(|t| Some(t.batch) == last)
+                .map(|t| t.entry.id)
+                .collect();
+            Ok(state.plan_restore(ids))
+        })
+    }
+
+    fn purge_trash(&self, all: bool) -> Result<usize> {
+        let trash = self.trash();
+        let cutoff = chrono::Utc::now().timestamp() - trash.grace.0;
+        self.update(|state| {
+            let ids: Vec<i64> = state
+                .trash
+                .values()
+                .filter(|t| all || !trash.enabled() || t.deleted_at < cutoff)
+                .map(|t| t.entry.id)
+                .collect();
+            let count = ids.len();
+            Ok((Op::Purge { ids }, count))
+        })
+    }
+
+    fn set_register(&self, name: char, entry: &Entry) -> Result<()> {
+        let name = register_name(name)?;
+        let entry = register_copy(entry, chrono::Utc::now().timestamp());
+        self.update(|_| Ok((Op::SetRegister { name, entry }, ())))
+    }
+
+    fn get_register(&self, name: char) -> Result<Entry> {
+        let name = register_name(name)?;
+        self.read(|state| {
+            state
+                .registers
+                .get(&name)
+                .cloned()
+                .ok_or(StickyError::EmptyRegister(name))
+        })
+    }
+
+    fn list_registers(&self) -> Result<Vec<RegisterItem>> {
+        self.read(|state| {
+            Ok(state
+                .registers
+                .iter()
+                .map(|(&name, entry)| RegisterItem {
+                    name,
+                    entry: entry.summary(),
+                    set_at: entry.last_seen,
+                })
+                .collect())
+        })
+    }
+
+    fn clear_register(&self, name: char) -> Result<()> {
+        let name = register_name(name)?;
+        self.update(|state| {
+            if !state.registers.contains_key(&name) {
+                return Err(StickyError::EmptyRegister(name));
+            }
+            Ok((Op::ClearRegister { name }, ()))
+        })
+    }
+
+    fn count(&self) -> Result<usize> {
+        self.read(|state| Ok(state.entries.len()))
+    }
+
+    fn stats(&self, since: i64, top: usize) -> Result<Stats> {
+        let mut stats = self.read(|state| {
+            let mut types: BTreeMap<&str, TypeStats> = BTreeMap::new();
+            let mut hourly: BTreeMap<i64, usize> = BTreeMap::new();
+            for entry in state.entries.values() {
+                let expiring = (!state.exempt(entry)).then_some(entry.last_seen);
+                let stats = types
+                    .entry(entry.content_type.as_str())
+                    .or_insert(TypeStats {
+                        content_type: entry.content_type,
+                        count: 0,
+                        bytes: 0,
+                        oldest: entry.last_seen,
+                        oldest_expiring: None,
+                    });
+                stats.count += 1;
+                stats.bytes += entry.size() as i64;
+                stats.oldest = stats.oldest.min(entry.last_seen);
+                stats.oldest_expiring = match (stats.oldest_expiring, expiring) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+            }
+            for &at in state.captures.iter().filter(|&&at| at >= since) {
+                *hourly.entry(at / 3600 * 3600).or_default() += 1;
+            }
+
+            let mut reused: Vec<&Entry> = state
+                .entries
+                .values()
+                .filter(|e| e.copy_count + e.use_count > 1)
+                .collect();
+            reused.sort_by_key(|e| {
+                std::cmp::Reverse((e.copy_count + e.use_count, e.last_seen, e.id))
+            });
+            Ok(Stats {
+                types: types.into_values().collect(),
+                hourly: hourly.into_iter().collect(),
+                top: State::summaries(reused.into_iter().take(top)),
+                disk: Vec::new(),
+            })
+        })?;
+        stats.disk = self.disk_usage()?;
+        Ok(stats)
+    }
+}
+
+/// An `EntryFilter` with its tag and query normalized
+struct Matcher<'a> {
+    filter: &'a EntryFilter,
+    tag: Option<String>,
+    query: Option<String>,
+}
+
+/// Insert an entry with the op `insert` makes. Returns the id it ends up
+/// under.
+fn insert<S: StateAccess>(
+    store: &S,
+    entry: &Entry,
+    insert: impl FnOnce(Entry) -> Op,
+) -> Result<i64> {
+    let mut entry = entry.clone();
+    entry.tags = normalize_tags(&entry.tags)?;
+    store.update(|state| {
+        let id = match state.by_hash.get(&entry.hash) {
+            Some(&id) => id,
+            None => state.last_id + 1,
+        };
+        Ok((insert(entry), id))
+    })
+}
+
+/// Replace a text entry's content with the op `edit` makes. Returns the
+/// id it ends up under.
+fn rewrite<S: StateAccess>(
+    store: &S,
+    id: i64,
+    content: &str,
+    edit: impl FnOnce(String) -> Op,
+) -> Result<i64> {
+    let hash = hash_content(content.as_bytes());
+    let unchanged = store.read(|state| {
+        let entry = state.entry(id)?;
+        if entry.content_type == ContentType::Image {
+            return Err(StickyError::NotText(id));
+        }
+        Ok(entry.hash == hash)
+    })?;
+    if unchanged {
+        return Ok(id);
+    }
+    store.update(|state| {
+        state.entry(id)?;
+        let target = state.by_hash.get(&hash).copied().unwrap_or(id);
+        Ok((edit(content.to_string()), target))
+    })
+}
+
+impl<'a> Matcher<'a> {
+    fn new(filter: &'a EntryFilter) -> Result<Self> {
+        let tag = match &filter.tag {
+            Some(tag) => {
+                Some(normalize_tag(tag).ok_or_else(|| StickyError::InvalidTag(tag.to_string()))?)
+            }
+            None => None,
+        };
+        Ok(Self {
+            filter,
+            tag,
+            query: filter.query.as_ref().map(|q| q.to_lowercase()),
+        })
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        let filter = self.filter;
+        filter.since.is_none_or(|since| entry.last_seen >= since)
+            && filter.until.is_none_or(|until| entry.last_seen < until)
+            && filter.content_type.is_none_or(|t| entry.content_type == t)
+            && (!filter.pinned || entry.pinned)
+            && self.tag.as_ref().is_none_or(|tag| entry.tags.contains(tag))
+            && self.query.as_ref().is_none_or(|query| {
+                entry.content_type != ContentType::Image
+                    && entry
+                        .content
+                        .as_ref()
+                        .is_some_and(|c| c.to_lowercase().contains(query))
+            })
+    }
+}
+
+/// Op removing `ids` from the history: into the trash if `to_trash`
+fn removal(ids: Vec<i64>, to_trash: bool) -> Op {
+    if to_trash {
+        Op::Trash {
+            ids,
+            at: chrono::Utc::now().timestamp(),
+        }
+    } else {
+        Op::Delete { ids }
+    }
+}
+
+/// Same weighting as the SQLite store's frecency ranking
+fn frecency(entry: &Entry, now: i64) -> i64 {
+    let weight = match now - entry.last_used.unwrap_or(entry.last_seen) {
+        age if age < 14_400 => 100,
+        age if age < 86_400 => 70,
+        age if age < 604_800 => 50,
+        age if age < 2_592_000 => 30,
+        _ => 10,
+    };
+    (entry.use_count + 1) * weight
+}
+
+fn add_tags(entry: &mut Entry, tags: &[String]) {
+    for tag in tags {
+        if !entry.tags.contains(tag) {
+            entry.tags.push(tag.clone());
+        }
+    }
+    entry.tags.sort();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Age;
+
+    fn text(s: &str, last_seen: i64) -> Entry {
+        let mut entry = Entry::new_text(s.to_string());
+        entry.created_at = last_seen;
+        entry.last_seen = last_seen;
+        entry
+    }
+
+    #[test]
+    fn insert_merges_duplicates() {
+        let store = MemoryStore::new();
+        let id = store.insert(&text("same", 10)).unwrap();
+        let mut again = text("same", 20);
+        again.tags = vec!["Work".into()];
+        assert_eq!(store.insert(&again).unwrap(), id);
+
+        let entry = store.get_by_id(id).unwrap();
+        assert_eq!(
+            (entry.created_at, entry.last_seen, entry.copy_count),
+            (10, 20, 2)
+        );
+        assert_eq!(entry.tags, vec!["work"]);
+        assert_eq!(store.get_latest_hash().unwrap(), Some(entry.hash));
+
+        let mut bad = text("bad", 1);
+        bad.tags = vec!["no spaces".into()];
+        assert!(store.insert(&bad).is_err());
+        assert_eq!(store.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn cleanup_spares_pinned_and_kept() {
+        let store = MemoryStore::new();
+        let now = chrono::Utc::now().timestamp();
+        let old = store.insert(&text("old", now - 100_000)).unwrap();
+        let pinned = store.insert(&text("pinned", now - 100_000)).unwrap();
+        let kept = store.insert(&text("kept", now - 100_000)).unwrap();
+        for i in 0..3 {
+            store.insert(&text(&format!("new {i}"), now - i)).unwrap();
+        }
+        store.set_pinned(pinned, true).unwrap();
+        store.add_tags(kept, &["keep".into()]).unwrap();
+        store.set_tag_keep("keep", true).unwrap();
+
+        let policy = RetentionConfig {
+            max_age: Age(3600),
+            max_entries: Some(2),
+            ..Default::default()
+        };
+        let report = store.cleanup_old(&policy).unwrap();
+        assert_eq!((report.expired, report.over_count), (1, 1));
+        assert!(store.get_by_id(old).is_err());
+        assert_eq!(store.count().unwrap(), 4);
+
+        store.delete(kept).unwrap();
+        let tags = store.list_tags().unwrap();
+        assert_eq!(
+            (tags[0].name.as_str(), tags[0].count, tags[0].keep),
+            ("keep", 0, true)
+        );
+    }
+
+    #[test]
+    fn search_and_filter() {
+        let store = MemoryStore::new();
+        store.insert(&text("Hello world", 1)).unwrap();
+        let link = store.insert(&text("https://example.com", 2)).unwrap();
+        store
+            .insert(&Entry::new_image(vec![0x89, b'P', b'N', b'G']))
+            .unwrap();
+
+        assert_eq!(store.search("HELLO", 10).unwrap().len(), 1);
+        let links = store
+            .list_filtered(&EntryFilter {
+                content_type: Some(ContentType::Link),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].id, link);
+        assert_eq!(store.clear().unwrap(), 3);
+    }
+
+    #[test]
+    fn stats_match_the_entries() {
+        let store = MemoryStore::new();
+        let id = store.capture(&Entry::new_text("one".into())).unwrap();
+        store.capture(&Entry::new_text("one".into())).unwrap();
+        store
+            .insert(&Entry::new_text("https://a.example".into()))
+            .unwrap();
+        store.set_pinned(id, true).unwrap();
+
+        let stats = store.stats(0, 5).unwrap();
+        let counts: Vec<_> = stats
+            .types
+            .iter()
+            .map(|t| (t.content_type, t.count, t.oldest_expiring.is_some()))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![(ContentType::Link, 1, true), (ContentType::Text, 1, false)]
+        );
+        // Both copies of "one" count, the inserted link doesn't
+        assert_eq!(stats.hourly.iter().map(|(_, n)| n).sum::<usize>(), 2);
+        assert_eq!(stats.top.len(), 1);
+        assert!(stats.disk.is_empty());
+
+        // Copies older than the retention window are forgotten
+        store.capture(&text("ancient", 1)).unwrap();
+        store.cleanup_old(&RetentionConfig::default()).unwrap();
+        let copies: usize = store
+            .stats(0, 5)
+            .unwrap()
+            .hourly
+            .iter()
+            .map(|(_, n)| n)
+            .sum();
+        assert_eq!(copies, 2);
+    }
+
+    #[test]
+    fn delete_where_spares_pinned() {
+        let store = MemoryStore::new();
+        let old = store.insert(&text("old note", 100)).unwrap();
+        let pinned = store.insert(&text("old pinned note", 100)).unwrap();
+        let new = store.insert(&text("new note", 1_000)).unwrap();
+        store.set_pinned(pinned, true).unwrap();
+
+        let filter = EntryFilter {
+            until: Some(500),
+            query: Some("NOTE".into()),
+            ..Default::default()
+        };
+        assert_eq!(store.delete_where(&filter).unwrap(), 1);
+        assert!(store.get_by_id(old).is_err());
+        assert!(store.get_by_id(pinned).is_ok());
+        assert!(store.get_by_id(new).is_ok());
+    }
+
+    #[test]
+    fn edit_keeps_revisions() {
+        let store = MemoryStore::new();
+        let id = store.insert(&text("draft", 1)).unwrap();
+        let other = store.insert(&text("final", 2)).unwrap();
+
+        assert_eq!(store.edit(id, "draft 2").unwrap(), id);
+        assert_eq!(store.find_by_hash(&hash_content(b"draft")).unwrap(), None);
+        assert_eq!(store.edit(id, "final").unwrap(), other);
+        assert_eq!(store.count().unwrap(), 1);
+        let revisions: Vec<String> = store
+            .list_revisions(other)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.content)
+            .collect();
+        assert_eq!(revisions, vec!["draft", "draft 2"]);
+    }
+
+    #[test]
+    fn revisions_survive_the_trash_until_purged() {
+        let mut store = MemoryStore::new();
+        store.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        let id = store.insert(&text("draft", 1)).unwrap();
+        store.edit(id, "final").unwrap();
+
+        store.delete(id).unwrap();
+        assert_eq!(store.undo().unwrap(), vec![id]);
+        assert_eq!(store.list_revisions(id).unwrap()[0].content, "draft");
+
+        store.delete(id).unwrap();
+        store.purge_trash(true).unwrap();
+        assert!(store.read(|state| Ok(state.revisions.is_empty())).unwrap());
+    }
+
+    #[test]
+    fn trash_undo_and_restore() {
+        let mut store = MemoryStore::new();
+        store.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        let a = store.insert(&text("a", 1)).unwrap();
+        let b = store.insert(&text("b", 2)).unwrap();
+        store.delete(a).unwrap();
+        store.clear().unwrap();
+        assert_eq!(store.count().unwrap(), 0);
+        assert_eq!(store.list_trash().unwrap().len(), 2);
+
+        assert_eq!(store.undo().unwrap(), vec![b]);
+        let again = store.insert(&text("a", 3)).unwrap();
+        assert_eq!(store.restore(a).unwrap(), again);
+        assert_eq!(store.get_by_id(again).unwrap().copy_count, 2);
+        assert_eq!(store.count().unwrap(), 2);
+
+        store.delete(b).unwrap();
+        assert_eq!(store.purge_trash(false).unwrap(), 0);
+        assert_eq!(store.purge_trash(true).unwrap(), 1);
+        assert!(matches!(store.restore(b), Err(StickyError::NotInTrash(_))));
+    }
+
+    #[test]
+    fn registers_outlive_the_history() {
+        let store = MemoryStore::new();
+        let id = store.insert(&text("kept in a", 1)).unwrap();
+        store
+            .set_register('A', &store.get_by_id(id).unwrap())
+            .unwrap();
+        assert!(store.set_register('1', &text("x", 1)).is_err());
+        store.clear_all().unwrap();
+
+        let entry = store.get_register('a').unwrap();
+        assert_eq!((entry.id, entry.content.as_deref()), (0, Some("kept in a")));
+        assert_eq!(store.list_registers().unwrap()[0].name, 'a');
+        store.clear_register('a').unwrap();
+        assert!(matches!(
+            store.get_register('a'),
+            Err(StickyError::EmptyRegister('a'))
+        ));
+    }
+}
@@ -0,0 +1,403 @@
+mod file;
+mod memory;
+mod sqlite;
+
+pub use file::FileStore;
+pub use memory::MemoryStore;
+pub use sqlite::Storage;
+
+use crate::config::{journal_path, Backend, Config, RetentionConfig, TrashConfig};
+use crate::entry::{normalize_tag, ContentType, Entry, EntrySummary};
+use crate::error::{Result, StickyError};
+
+/// How listings are ordered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Most recently copied first
+    #[default]
+    Recent,
+    /// Most frequently and recently used first
+    Frecency,
+}
+
+/// What a retention pass removed, by the rule that removed it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// Older than the global `max_age`
+    pub expired: usize,
+    /// Older than a per-type override
+    pub expired_by_type: usize,
+    /// Beyond `max_entries`
+    pub over_count: usize,
+    /// Beyond `max_bytes`
+    pub over_size: usize,
+    /// Trashed entries past their grace period
+    pub purged: usize,
+    /// Blob files no longer referenced by any entry
+    pub orphan_blobs: usize,
+}
+
+impl CleanupReport {
+    pub fn total(&self) -> usize {
+        self.expired + self.expired_by_type + self.over_count + self.over_size
+    }
+}
+
+impl std::fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "removed {} (age: {}, type age: {}, count: {}, size: {}), {} purged from trash, \
+             {} orphaned blobs",
+            self.total(),
+            self.expired,
+            self.expired_by_type,
+            self.over_count,
+            self.over_size,
+            self.purged,
+            self.orphan_blobs
+        )
+    }
+}
+
+/// A named collection and how many entries it holds
+#[derive(Debug, Clone)]
+pub struct TagInfo {
+    pub name: String,
+    pub count: usize,
+    pub keep: bool,
+}
+
+/// A deleted entry waiting in the trash
+#[derive(Debug, Clone)]
+pub struct TrashItem {
+    pub entry: EntrySummary,
+    /// Unix timestamp of the deletion
+    pub deleted_at: i64,
+}
+
+/// Earlier content of an edited entry
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub content_type: ContentType,
+    pub content: String,
+    /// Unix timestamp of the edit that replaced it
+    pub replaced_at: i64,
+}
+
+/// Content held in a named register
+#[derive(Debug, Clone)]
+pub struct RegisterItem {
+    pub name: char,
+    pub entry: EntrySummary,
+    /// Unix timestamp of when the register was set
+    pub set_at: i64,
+}
+
+/// Aggregate figures about the history, see `HistoryStore::stats`
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// Entry counts and sizes, by type, for the types present
+    pub types: Vec<TypeStats>,
+    /// Copies captured in each hour since the given time, by the hour's
+    /// unix timestamp, oldest first. Hours without copies are left out.
+    pub hourly: Vec<(i64, usize)>,
+    /// Entries copied again or retrieved most often, most first
+    pub top: Vec<EntrySummary>,
+    /// Files the history takes up
+    pub disk: Vec<DiskUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeStats {
+    pub content_type: ContentType,
+    pub count: usize,
+    /// Payload bytes
+    pub bytes: i64,
+    /// `last_seen` of the oldest entry
+    pub oldest: i64,
+    /// `last_seen` of the oldest entry retention may remove: neither
+    /// pinned nor in a kept collection
+    pub oldest_expiring: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub name: &'static str,
+    pub bytes: u64,
+}
+
+/// Criteria for selecting entries, e.g. for export. Unset fields match
+/// everything.
+#[derive(Debug, Default, Clone)]
+pub struct EntryFilter {
+    /// Last copied at or after this unix timestamp
+    pub since: Option<i64>,
+    /// Last copied before this unix timestamp
+    pub until: Option<i64>,
+    pub content_type: Option<ContentType>,
+    /// Only pinned entries
+    pub pinned: bool,
+    pub tag: Option<String>,
+    /// Text containing this, ignoring case
+    pub query: Option<String>,
+}
+
+/// A position in the history, most recently copied first: `list_page`
+/// continues with the entries after it. Unlike an offset it stays put when
+/// new copies arrive between pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    /// `last_seen` of the last entry listed so far
+    pub last_seen: i64,
+    /// Its id, to order entries copied within the same second
+    pub id: i64,
+}
+
+impl Cursor {
+    pub fn at(entry: &EntrySummary) -> Self {
+        Self {
+            last_seen: entry.last_seen,
+            id: entry.id,
+        }
+    }
+
+    /// Whether an entry comes after this position
+    pub fn precedes(&self, entry: &EntrySummary) -> bool {
+        (entry.last_seen, entry.id) < (self.last_seen, self.id)
+    }
+}
+
+/// One page of a listing
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    pub entries: Vec<EntrySummary>,
+    /// Where the next page starts, `None` on the last one
+    pub next: Option<Cursor>,
+}
+
+impl Page {
+    /// The page of `listing` (most recently copied first) after `after`
+    pub(crate) fn of(
+        listing: impl IntoIterator<Item = EntrySummary>,
+        after: Option<Cursor>,
+        limit: usize,
+    ) -> Self {
+        let mut entries: Vec<EntrySummary> = listing
+            .into_iter()
+            .filter(|e| after.is_none_or(|c| c.precedes(e)))
+            .take(limit + 1)
+            .collect();
+        // The extra entry only tells whether there's another page
+        let next = if entries.len() > limit {
+            entries.truncate(limit);
+            entries.last().map(Cursor::at)
+        } else {
+            None
+        };
+        Self { entries, next }
+    }
+}
+
+/// Clipboard history, whatever it's kept in. Entries are identified by an
+/// id assigned on insert and deduplicated by content hash.
+pub trait HistoryStore: Send {
+    /// Insert an entry, or bump the stored entry with the same content to
+    /// the top of the history. Returns its id either way.
+    fn insert(&self, entry: &Entry) -> Result<i64>;
+
+    /// Insert an entry copied to the clipboard, and log the copy's time for
+    /// `stats`. Copies are logged even when they merge into a stored entry.
+    fn capture(&self, entry: &Entry) -> Result<i64>;
+
+    /// Find an entry by the plaintext hash of its content
+    fn find_by_hash(&self, hash: &str) -> Result<Option<i64>>;
+
+    /// Plaintext hash of the most recently copied entry
+    fn get_latest_hash(&self) -> Result<Option<String>>;
+
+    fn get_by_id(&self, id: i64) -> Result<Entry>;
+
+    fn delete(&self, id: i64) -> Result<()> {
+        self.delete_ids(&[id])
+    }
+
+    /// Delete the given entries as one batch for `undo`. Deletes nothing if
+    /// one of them doesn't exist.
+    fn delete_ids(&self, ids: &[i64]) -> Result<()>;
+
+    /// Delete the entries matching `filter`, except pinned ones. Returns
+    /// how many were deleted.
+    fn delete_where(&self, filter: &EntryFilter) -> Result<usize>;
+
+    fn list(&self, limit: usize) -> Result<Vec<EntrySummary>> {
+        self.list_sorted(limit, SortOrder::Recent)
+    }
+
+    fn list_sorted(&self, limit: usize, order: SortOrder) -> Result<Vec<EntrySummary>>;
+
+    fn list_pinned(&self) -> Result<Vec<EntrySummary>>;
+
+    fn list_tagged(&self, tag: &str, limit: usize, order: SortOrder) -> Result<Vec<EntrySummary>>;
+
+    /// Every entry matching `filter`, most recently copied first
+    fn list_filtered(&self, filter: &EntryFilter) -> Result<Vec<EntrySummary>>;
+
+    /// Up to `limit` entries matching `filter`, most recently copied first,
+    /// starting after `after` (or at the newest)
+    fn list_page(&self, after: Option<Cursor>, limit: usize, filter: &EntryFilter) -> Result<Page> {
+        Ok(Page::of(self.list_filtered(filter)?, after, limit))
+    }
+
+    /// Text entries containing `query`, ignoring case, most recent first
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<EntrySummary>>;
+
+    /// Record that an entry was retrieved (via `syo get` or the popup)
+    fn record_use(&self, id: i64) -> Result<()>;
+
+    fn set_pinned(&self, id: i64, pinned: bool) -> Result<()>;
+
+    /// Replace a text entry's content, keeping the old content as a
+    /// revision. Returns the entry's id, which changes when the new content
+    /// was already stored: the two entries are merged.
+    fn edit(&self, id: i64, content: &str) -> Result<i64>;
+
+    /// Like `edit`, without keeping the old content, for entries that are
+    /// still being built up
+    fn replace(&self, id: i64, content: &str) -> Result<i64>;
+
+    /// Earlier content of an entry, oldest first. Dropped along with the
+    /// entry.
+    fn list_revisions(&self, id: i64) -> Result<Vec<Revision>>;
+
+    /// Attach tags to an entry, creating any that don't exist yet
+    fn add_tags(&self, id: i64, tags: &[String]) -> Result<()>;
+
+    /// Detach tags from an entry; tags left without entries are dropped
+    fn remove_tags(&self, id: i64, tags: &[String]) -> Result<()>;
+
+    fn list_tags(&self) -> Result<Vec<TagInfo>>;
+
+    /// Mark a tag as kept: its entries are exempt from retention cleanup
+    fn set_tag_keep(&self, tag: &str, keep: bool) -> Result<()>;
+
+    /// Enforce the retention policy. Pinned entries and entries in kept
+    /// collections are never removed.
+    fn cleanup_old(&self, policy: &RetentionConfig) -> Result<CleanupReport>;
+
+    /// Delete all unpinned entries
+    fn clear(&self) -> Result<usize>;
+
+    /// Delete every entry, pinned ones included
+    fn clear_all(&self) -> Result<usize>;
+
+    /// Move deleted entries to the trash instead of deleting them for good.
+    /// Stores start with the trash off; `open` applies the config.
+    fn set_trash(&mut self, trash: TrashConfig);
+
+    /// Entries in the trash, most recently deleted first
+    fn list_trash(&self) -> Result<Vec<TrashItem>>;
+
+    /// Put a trashed entry back into the history. Returns its id there,
+    /// which differs when the same content was copied again meanwhile.
+    fn restore(&self, id: i64) -> Result<i64>;
+
+    /// Restore every entry removed by the most recent deletion still in
+    /// the trash. Returns their ids in the history.
+    fn undo(&self) -> Result<Vec<i64>>;
+
+    /// Delete trashed entries past the grace period for good, or all of
+    /// them with `all`. Returns how many were purged.
+    fn purge_trash(&self, all: bool) -> Result<usize>;
+
+    /// Overwrite files holding deleted content instead of only unlinking
+    /// them, skipping the trash. Only the SQLite store's blob files can be
+    /// shredded.
+    fn set_shred(&mut self, shred: bool) -> Result<()> {
+        if shred {
+            return Err(StickyError::Unsupported("shredding deleted entries".into()));
+        }
+        Ok(())
+    }
+
+    /// Store a copy of `entry` in register `name` (`a`-`z`), replacing
+    /// what was there. Registers are independent of the history: deletions,
+    /// `clear` and retention leave them alone.
+    fn set_register(&self, name: char, entry: &Entry) -> Result<()>;
+
+    /// Content of a register, with id 0 and the time it was set
+    fn get_register(&self, name: char) -> Result<Entry>;
+
+    /// Registers that hold something, by name
+    fn list_registers(&self) -> Result<Vec<RegisterItem>>;
+
+    fn clear_register(&self, name: char) -> Result<()>;
+
+    fn count(&self) -> Result<usize>;
+
+    /// Counts and sizes by type, captured copies per hour since `since`,
+    /// the `top` most reused entries and disk usage
+    fn stats(&self, since: i64, top: usize) -> Result<Stats>;
+}
+
+/// Open the history with the backend chosen in the config
+pub fn open() -> Result<Box<dyn HistoryStore>> {
+    let config = Config::load();
+    let mut store: Box<dyn HistoryStore> = match config.storage.backend {
+        Backend::Sqlite => Box::new(Storage::open()?),
+        Backend::File => {
+            if config.encryption.enabled {
+                return Err(StickyError::Crypto(
+                    "encryption needs the sqlite storage backend".into(),
+                ));
+            }
+            Box::new(FileStore::open(&journal_path())?)
+        }
+    };
+    store.set_trash(config.trash);
+    Ok(store)
+}
+
+/// Total size of the files under `dir`
+fn dir_size(dir: &std::path::Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Check a register name, folding it to lowercase
+pub fn register_name(name: char) -> Result<char> {
+    match name.to_ascii_lowercase() {
+        name @ 'a'..='z' => Ok(name),
+        _ => Err(StickyError::InvalidRegister(name.to_string())),
+    }
+}
+
+/// What a register keeps of an entry: its content, as of `at`
+fn register_copy(entry: &Entry, at: i64) -> Entry {
+    Entry {
+        id: 0,
+        created_at: at,
+        last_seen: at,
+        copy_count: 1,
+        pinned: false,
+        use_count: 0,
+        last_used: None,
+        tags: Vec::new(),
+        ..entry.clone()
+    }
+}
+
+fn normalize_tags(tags: &[String]) -> Result<Vec<String>> {
+    tags.iter()
+        .map(|t| normalize_tag(t).ok_or_else(|| StickyError::InvalidTag(t.clone())))
+        .collect()
+}
@@ -0,0 +1,3274 @@
+use super::{
+    dir_size, normalize_tags, register_copy, register_name, CleanupReport, Cursor, DiskUsage,
+    EntryFilter, HistoryStore, Page, RegisterItem, Revision, SortOrder, Stats, TagInfo, TrashItem,
+    TypeStats,
+};
+use crate::blob::BlobStore;
+use crate::config::{db_path, Config, RetentionConfig, TrashConfig, BLOB_THRESHOLD_BYTES};
+use crate::crypto::{self, Cipher};
+use crate::entry::{hash_content, normalize_tag, ContentType, Entry, EntrySummary, PREVIEW_CHARS};
+use crate::error::{Result, StickyError};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rusqlite::{params, Connection, ErrorCode, OpenFlags, Params};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long a statement waits for another process's lock before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Write transactions still busy after the timeout are retried this many
+/// times, doubling the delay from `BUSY_RETRY_DELAY` each time
+const BUSY_RETRIES: u32 = 5;
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+const ENTRY_COLUMNS: &str = "id, content_type, content, image_data, hash, created_at,
+    last_seen, copy_count, pinned, use_count, last_used, encrypted,
+    (SELECT group_concat(t.name, ',') FROM entry_tags et JOIN tags t ON t.id = et.tag_id
+     WHERE et.entry_id = entries.id) AS tags, blob_ref";
+
+/// Listing columns: no image data, and only the start of inline plaintext
+/// (the substr length matches `PREVIEW_CHARS`). Encrypted text is selected
+/// whole since it has to be decrypted before it can be cut.
+const SUMMARY_COLUMNS: &str = "id, content_type,
+    CASE WHEN encrypted = 1 THEN content ELSE substr(content, 1, 1024) END,
+    size, created_at, last_seen, copy_count, pinned, use_count, last_used, encrypted,
+    (SELECT group_concat(t.name, ',') FROM entry_tags et JOIN tags t ON t.id = et.tag_id
+     WHERE et.entry_id = entries.id) AS tags, blob_ref";
+
+/// Columns an entry is stored with, shared by `entries` and `trash`
+const STORED_COLUMNS: &str = "id, content_type, content, image_data, hash, created_at,
+    last_seen, copy_count, pinned, use_count, last_used, encrypted, blob_ref, size";
+
+/// Trash listing columns, laid out like `SUMMARY_COLUMNS`
+const TRASH_SUMMARY_COLUMNS: &str = "id, content_type,
+    CASE WHEN encrypted = 1 THEN content ELSE substr(content, 1, 1024) END,
+    size, created_at, last_seen, copy_count, pinned, use_count, last_used, encrypted,
+    tags, blob_ref, deleted_at";
+
+/// A register laid out like `ENTRY_COLUMNS`
+const REGISTER_COLUMNS: &str = "0, content_type, content, image_data, hash, set_at, set_at,
+    1, 0, 0, NULL, encrypted, NULL, blob_ref";
+
+/// Register listing columns, laid out like `SUMMARY_COLUMNS`
+const REGISTER_SUMMARY_COLUMNS: &str = "0, content_type,
+    CASE WHEN encrypted = 1 THEN content ELSE substr(content, 1, 1024) END,
+    size, set_at, set_at, 1, 0, 0, NULL, encrypted, NULL, blob_ref, name";
+
+/// Tables besides `entries` that hold sealed payloads, and whether they
+/// store image data and a hash
+const PAYLOAD_TABLES: [(&str, bool); 3] =
+    [("trash", true), ("revisions", false), ("registers", true)];
+
+/// Fold a row inserted with an already stored hash into the stored row
+const MERGE_DUPLICATE: &str = "ON CONFLICT(hash) DO UPDATE SET
+    last_seen = MAX(last_seen, excluded.last_seen),
+    copy_count = copy_count + excluded.copy_count,
+    pinned = MAX(pinned, excluded.pinned),
+    use_count = use_count + excluded.use_count,
+    last_used = COALESCE(MAX(last_used, excluded.last_used), last_used, excluded.last_used)
+    RETURNING id";
+
+/// Entries carrying at least one tag marked as kept never expire
+const KEPT_BY_TAG: &str = "SELECT et.entry_id FROM entry_tags et
+    JOIN tags t ON t.id = et.tag_id WHERE t.keep = 1";
+
+/// Frequency of use weighted by how recently the entry was last used (or
+/// copied, if never used): 4h, 1 day, 1 week and 30 day buckets.
+const FRECENCY_SCORE: &str = "(use_count + 1) * (
+    CASE
+        WHEN unixepoch() - COALESCE(last_used, last_seen) < 14400 THEN 100
+        WHEN unixepoch() - COALESCE(last_used, last_seen) < 86400 THEN 70
+        WHEN unixepoch() - COALESCE(last_used, last_seen) < 604800 THEN 50
+        WHEN unixepoch() - COALESCE(last_used, last_seen) < 2592000 THEN 30
+        ELSE 10
+    END)";
+
+/// SQL conditions for `filter` and the values they refer to. A text query
+/// only narrows it down to candidates: encrypted and blob text can't be
+/// matched in SQL.
+fn filter_conditions(filter: &EntryFilter) -> Result<(Vec<String>, Vec<rusqlite::types::Value>)> {
+    let mut conditions = vec!["1".to_string()];
+    let mut values: Vec<rusqlite::types::Value> = Vec::new();
+    if let Some(since) = filter.since {
+        values.push(since.into());
+        conditions.push(format!("last_seen >= ?{}", values.len()));
+    }
+    if let Some(until) = filter.until {
+        values.push(until.into());
+        conditions.push(format!("last_seen < ?{}", values.len()));
+    }
+    if let Some(content_type) = filter.content_type {
+        values.push(content_type.as_str().to_string().into());
+        conditions.push(format!("content_type = ?{}", values.len()));
+    }
+    if filter.pinned {
+        conditions.push("pinned = 1".to_string());
+    }
+    if let Some(tag) = &filter.tag {
+        let tag = normalize_tag(tag).ok_or_else(|| StickyError::InvalidTag(tag.to_string()))?;
+        values.push(tag.into());
+        conditions.push(format!(
+            "id IN (SELECT et.entry_id FROM entry_tags et
+                    JOIN tags t ON t.id = et.tag_id WHERE t.name = ?{})",
+            values.len()
+        ));
+    }
+    if let Some(query) = &filter.query {
+        conditions.push("content_type != 'image'".to_string());
+        if let Some(pattern) = like_pattern(query) {
+            values.push(pattern.into());
+            conditions.push(format!(
+                "(content LIKE ?{} ESCAPE '\\' OR encrypted = 1 OR blob_ref IS NOT NULL)",
+                values.len()
+            ));
+        }
+    }
+    Ok((conditions, values))
+}
+
+/// A LIKE pattern matching `query` anywhere, or None if SQL can't narrow
+/// the search: LIKE only ignores ASCII case, so other queries are matched
+/// after loading
+fn like_pattern(query: &str) -> Option<String> {
+    if !query.is_ascii() {
+        return None;
+    }
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    Some(format!("%{escaped}%"))
+}
+
+fn order_by(order: SortOrder) -> String {
+    match order {
+        SortOrder::Recent => "last_seen DESC, id DESC".to_string(),
+        SortOrder::Frecency => format!("{FRECENCY_SCORE} DESC, last_seen DESC, id DESC"),
+    }
+}
+
+/// Known plaintext stored encrypted in `meta`, to verify a key on unlock
+const KEY_CHECK: &[u8] = b"sticky_one key check";
+
+/// An entry's columns as written to the database
+struct Sealed {
+    content: Option<String>,
+    image_data: Option<Vec<u8>>,
+    blob_ref: Option<String>,
+    hash: String,
+    size: i64,
+    encrypted: bool,
+}
+
+pub struct Storage {
+    conn: Connection,
+    cipher: Option<Cipher>,
+    /// Where images and large text live. Without one, payloads stay inline.
+    blobs: Option<BlobStore>,
+    /// Overwrite blob files before deleting them
+    shred: bool,
+    trash: TrashConfig,
+}
+
+impl Storage {
+    /// Open the default database, unlocking it when encryption is enabled
+    pub fn open() -> Result<Self> {
+        let mut storage = Self::open_at(&db_path())?;
+        let config = Config::load().encryption;
+        if config.enabled {
+            let key = crypto::load_key(&config)?;
+            storage.unlock(Cipher::new(&key))?;
+        } else if storage.has_encrypted()? {
+            // Encryption was turned off: decrypt with the key at hand, or
+            // leave it to `syo unlock`
+            match crypto::load_key(&config) {
+                Ok(key) => {
+                    storage.decrypt(Cipher::new(&key))?;
+                }
+                Err(StickyError::Locked) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(storage)
+    }
+
+    /// Problems `PRAGMA integrity_check` finds in a database file; none when
+    /// it's sound. The file is opened read-only and as it is: no schema
+    /// migration or anything else that would write to it.
+    pub fn integrity_check(path: &Path) -> Result<Vec<String>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows.into_iter().filter(|row| row != "ok").collect())
+    }
+
+    /// Open a database file without a key. Encrypted entries can't be read
+    /// until `unlock` is called. Blobs are kept in `blobs/` next to the file.
+    pub fn open_at(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // Deleted rows are zeroed instead of left in free pages
+        conn.execute_batch("PRAGMA secure_delete = ON;")?;
+        // WAL lets the CLI and popup read while the daemon writes
+        conn.query_row("PRAGMA journal_mode = WAL", [], |row| {
+            row.get::<_, String>(0)
+        })?;
+        conn.execute_batch("PRAGMA synchronous = NORMAL;")?;
+
+        let blob_dir = path.parent().unwrap_or(Path::new(".")).join("blobs");
+        let storage = Self {
+            conn,
+            cipher: None,
+            blobs: Some(BlobStore::new(blob_dir)),
+            shred: false,
+            trash: TrashConfig::off(),
+        };
+        storage.init_schema()?;
+
+        // The WAL holds recent entries too, so it gets the same mode
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for suffix in ["", "-wal", "-shm"] {
+                let mut file = path.as_os_str().to_owned();
+                file.push(suffix);
+                if Path::new(&file).exists() {
+                    fs::set_permissions(&file, fs::Permissions::from_mode(0o600))?;
+                }
+            }
+        }
+
+        storage.move_inline_payloads()?;
+        Ok(storage)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        let storage = Self {
+            conn,
+            cipher: None,
+            blobs: None,
+            shred: false,
+            trash: TrashConfig::off(),
+        };
+        storage.init_schema()?;
+        Ok(storage)
+    }
+
+    /// Run `f` in a write transaction, taking the lock up front so it can't
+    /// fail halfway. If another process holds the lock past the busy timeout,
+    /// retry with backoff. Nested calls join the outer transaction.
+    fn write<T>(&self, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        if !self.conn.is_autocommit() {
+            return f();
+        }
+        let mut delay = BUSY_RETRY_DELAY;
+        let mut retries = 0;
+        loop {
+            let result = self
+                .conn
+                .execute_batch("BEGIN IMMEDIATE")
+                .map_err(StickyError::from)
+                .and_then(|()| {
+                    let value = f()?;
+                    self.conn.execute_batch("COMMIT")?;
+                    Ok(value)
+                });
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    if !self.conn.is_autocommit() {
+                        self.conn.execute_batch("ROLLBACK")?;
+                    }
+                    if !is_busy(&e) || retries == BUSY_RETRIES {
+                        return Err(e);
+                    }
+                    retries += 1;
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+            }
+        }
+    }
+
+    fn init_schema(&self) -> Result<()> {
+        // Pragmas are no-ops inside a transaction, so this goes first
+        self.conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        // One transaction, so processes opening a fresh or old database at
+        // the same time don't both migrate it
+        self.write(|| self.create_schema())
+    }
+
+    fn create_schema(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS entries (
+                id INTEGER PRIMARY KEY,
+                content_type TEXT NOT NULL,
+                content TEXT,
+                image_data BLOB,
+                hash TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL DEFAULT 0,
+                copy_count INTEGER NOT NULL DEFAULT 1,
+                pinned INTEGER NOT NULL DEFAULT 0,
+                use_count INTEGER NOT NULL DEFAULT 0,
+                last_used INTEGER,
+                encrypted INTEGER NOT NULL DEFAULT 0,
+                blob_ref TEXT,
+                size INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS idx_created_at ON entries(created_at);
+            CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                keep INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS entry_tags (
+                entry_id INTEGER NOT NULL REFERENCES entries(id) ON DELETE CASCADE,
+                tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                PRIMARY KEY (entry_id, tag_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_entry_tags_tag ON entry_tags(tag_id);
+            CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS trash (
+                id INTEGER PRIMARY KEY,
+                content_type TEXT NOT NULL,
+                content TEXT,
+                image_data BLOB,
+                hash TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                copy_count INTEGER NOT NULL,
+                pinned INTEGER NOT NULL,
+                use_count INTEGER NOT NULL,
+                last_used INTEGER,
+                encrypted INTEGER NOT NULL,
+                blob_ref TEXT,
+                size INTEGER NOT NULL,
+                tags TEXT,
+                deleted_at INTEGER NOT NULL,
+                batch INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_trash_batch ON trash(batch);
+            CREATE TABLE IF NOT EXISTS revisions (
+                id INTEGER PRIMARY KEY,
+                entry_id INTEGER NOT NULL,
+                content_type TEXT NOT NULL,
+                content TEXT,
+                blob_ref TEXT,
+                encrypted INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                replaced_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_revisions_entry ON revisions(entry_id);
+            CREATE TABLE IF NOT EXISTS registers (
+                name TEXT PRIMARY KEY,
+                content_type TEXT NOT NULL,
+                content TEXT,
+                image_data BLOB,
+                hash TEXT NOT NULL,
+                encrypted INTEGER NOT NULL,
+                blob_ref TEXT,
+                size INTEGER NOT NULL,
+                set_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS captures (at INTEGER NOT NULL);
+            CREATE INDEX IF NOT EXISTS idx_captures_at ON captures(at);",
+        )?;
+
+        // Databases created before a column existed get it added in place
+        self.ensure_column("entries", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+        if self.ensure_column("entries", "last_seen", "INTEGER NOT NULL DEFAULT 0")? {
+            self.conn
+                .execute("UPDATE entries SET last_seen = created_at", [])?;
+        }
+        self.ensure_column("entries", "copy_count", "INTEGER NOT NULL DEFAULT 1")?;
+        self.ensure_column("entries", "use_count", "INTEGER NOT NULL DEFAULT 0")?;
+        self.ensure_column("entries", "last_used", "INTEGER")?;
+        self.ensure_column("entries", "encrypted", "INTEGER NOT NULL DEFAULT 0")?;
+        self.ensure_column("entries", "blob_ref", "TEXT")?;
+        if self.ensure_column("entries", "size", "INTEGER NOT NULL DEFAULT 0")? {
+            // Ciphertext length for encrypted rows, which is close enough
+            self.conn.execute(
+                "UPDATE entries SET size =
+                    COALESCE(length(CAST(content AS BLOB)), 0) + COALESCE(length(image_data), 0)",
+                [],
+            )?;
+        }
+        self.conn
+            .execute_batch("CREATE INDEX IF NOT EXISTS idx_last_seen ON entries(last_seen);")?;
+        self.migrate_unique_hash()?;
+        self.migrate_revisions()?;
+        Ok(())
+    }
+
+    /// Revisions used to be deleted along with their entry, so trashing an
+    /// entry lost them for good. They're now kept until it's purged:
+    /// rebuild the table without the cascading reference.
+    fn migrate_revisions(&self) -> Result<()> {
+        let cascades = || -> Result<bool> {
+            Ok(self.conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM pragma_foreign_key_list('revisions'))",
+                [],
+                |row| row.get(0),
+            )?)
+        };
+        if !cascades()? {
+            return Ok(());
+        }
+        // Checked again under the lock, in case another process got there first
+        self.write(|| {
+            if !cascades()? {
+                return Ok(());
+            }
+            self.conn.execute_batch(
+                "CREATE TABLE revisions_new (
+                id INTEGER PRIMARY KEY,
+                entry_id INTEGER NOT NULL,
+                content_type TEXT NOT NULL,
+                content TEXT,
+                blob_ref TEXT,
+                encrypted INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                replaced_at INTEGER NOT NULL
+            );
+            INSERT INTO revisions_new SELECT * FROM revisions;
+            DROP TABLE revisions;
+            ALTER TABLE revisions_new RENAME TO revisions;
+            CREATE INDEX idx_revisions_entry ON revisions(entry_id);",
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Older databases stored one row per copy. Fold duplicate hashes into
+    /// the oldest row, then enforce one row per hash.
+    fn migrate_unique_hash(&self) -> Result<()> {
+        let migrated: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'idx_hash_unique')",
+            [],
+            |row| row.get(0),
+        )?;
+        if migrated {
+            return Ok(());
+        }
+
+        self.conn.execute_batch(
+            "CREATE TEMP TABLE keep_ids AS
+                SELECT hash, MIN(id) AS id, MIN(created_at) AS created_at,
+                       MAX(last_seen) AS last_seen, SUM(copy_count) AS copy_count,
+                       MAX(pinned) AS pinned
+                FROM entries GROUP BY hash HAVING COUNT(*) > 1;
+            UPDATE entries SET
+                created_at = (SELECT k.created_at FROM keep_ids k WHERE k.id = entries.id),
+                last_seen = (SELECT k.last_seen FROM keep_ids k WHERE k.id = entries.id),
+                copy_count = (SELECT k.copy_count FROM keep_ids k WHERE k.id = entries.id),
+                pinned = (SELECT k.pinned FROM keep_ids k WHERE k.id = entries.id)
+            WHERE id IN (SELECT id FROM keep_ids);
+            INSERT OR IGNORE INTO entry_tags (entry_id, tag_id)
+                SELECT k.id, et.tag_id FROM entry_tags et
+                JOIN entries e ON e.id = et.entry_id
+                JOIN keep_ids k ON k.hash = e.hash;
+            DELETE FROM entries WHERE hash IN (SELECT hash FROM keep_ids)
+                AND id NOT IN (SELECT id FROM keep_ids);
+            DROP TABLE keep_ids;
+            DROP INDEX IF EXISTS idx_hash;
+            CREATE UNIQUE INDEX idx_hash_unique ON entries(hash);",
+        )?;
+        Ok(())
+    }
+
+    /// Add a column if missing. Returns true when the column was added.
+    fn ensure_column(&self, table: &str, column: &str, decl: &str) -> Result<bool> {
+        let exists = self
+            .conn
+            .prepare(&format!("PRAGMA table_info({table})"))?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|name| name.ok())
+            .any(|name| name == column);
+
+        if !exists {
+            self.conn
+                .execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+        }
+        Ok(!exists)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Whether a key has been registered for this database
+    pub fn has_key(&self) -> Result<bool> {
+        Ok(self.get_meta("key_check")?.is_some())
+    }
+
+    /// Salt for deriving the key from a passphrase, created on first use
+    pub fn kdf_salt(&self) -> Result<Vec<u8>> {
+        self.write(|| {
+            if let Some(salt) = self.get_meta("kdf_salt")? {
+                return Ok(salt);
+            }
+            let salt = crypto::generate_salt().to_vec();
+            self.set_meta("kdf_salt", &salt)?;
+            Ok(salt)
+        })
+    }
+
+    /// Verify a key (registering it if the database has none yet), then
+    /// encrypt any entries still stored in plaintext.
+    pub fn unlock(&mut self, cipher: Cipher) -> Result<()> {
+        self.check_key(&cipher)?;
+        self.cipher = Some(cipher);
+        self.reseal_rows("encrypted = 0", self.cipher.as_ref())?;
+        self.reseal_tables("encrypted = 0", self.cipher.as_ref())?;
+        self.move_inline_payloads()
+    }
+
+    /// Whether anything is stored encrypted: entries, trash, revisions or
+    /// registers
+    pub fn has_encrypted(&self) -> Result<bool> {
+        let tables = std::iter::once("entries").chain(PAYLOAD_TABLES.map(|(table, _)| table));
+        for table in tables {
+            let found: bool = self.conn.query_row(
+                &format!("SELECT EXISTS(SELECT 1 FROM {table} WHERE encrypted = 1)"),
+                [],
+                |row| row.get(0),
+            )?;
+            if found {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Verify a key and store every encrypted entry in plaintext again, for
+    /// history whose encryption has been turned off. Returns how many were
+    /// decrypted.
+    pub fn decrypt(&mut self, cipher: Cipher) -> Result<usize> {
+        self.check_key(&cipher)?;
+        self.cipher = Some(cipher);
+        let count: usize = self.conn.query_row(
+            "SELECT COUNT(*) FROM entries WHERE encrypted = 1",
+            [],
+            |row| row.get(0),
+        )?;
+        let resealed = self
+            .reseal_rows("encrypted = 1", None)
+            .and_then(|()| self.reseal_tables("encrypted = 1", None));
+        self.cipher = None;
+        resealed?;
+        Ok(count)
+    }
+
+    fn check_key(&self, cipher: &Cipher) -> Result<()> {
+        self.write(|| match self.get_meta("key_check")? {
+            Some(check) if cipher.decrypt(&check).ok().as_deref() == Some(KEY_CHECK) => Ok(()),
+            Some(_) => Err(StickyError::Crypto("wrong passphrase or key".into())),
+            None => self.set_meta("key_check", &cipher.encrypt(KEY_CHECK)),
+        })
+    }
+
+    /// Move payloads that older versions stored inline into the blob store.
+    /// Encrypted rows wait until the database is unlocked.
+    fn move_inline_payloads(&self) -> Result<()> {
+        if self.blobs.is_none() {
+            return Ok(());
+        }
+        let locked = if self.cipher.is_none() {
+            " AND encrypted = 0"
+        } else {
+            ""
+        };
+        self.reseal_rows(
+            &format!(
+                "blob_ref IS NULL AND (image_data IS NOT NULL OR size > {BLOB_THRESHOLD_BYTES}){locked}"
+            ),
+            self.cipher.as_ref(),
+        )
+    }
+
+    /// Rewrite the rows matching `condition` under `cipher` (plaintext with
+    /// none) and the blob policy. Rows are read with the current key.
+    fn reseal_rows(&self, condition: &str, cipher: Option<&Cipher>) -> Result<()> {
+        let replaced = self.write(|| {
+            let rows = self.query_entries(
+                &format!("SELECT {ENTRY_COLUMNS} FROM entries WHERE {condition}"),
+                [],
+            )?;
+            if rows.is_empty() {
+                return Ok(HashSet::new());
+            }
+            let before = self.live_blobs()?;
+            for entry in rows {
+                self.reseal(&entry, cipher)?;
+            }
+            Ok(&before - &self.live_blobs()?)
+        })?;
+
+        // Don't leave plaintext copies of now-encrypted blobs for gc to find
+        self.remove_blobs(&replaced)
+    }
+
+    /// Like `reseal_rows`, for the trash, revisions and registers
+    fn reseal_tables(&self, condition: &str, cipher: Option<&Cipher>) -> Result<()> {
+        let replaced = self.write(|| {
+            let before = self.live_blobs()?;
+            for (table, images) in PAYLOAD_TABLES {
+                let image_data = if images { "image_data" } else { "NULL" };
+                let mut stmt = self.conn.prepare(&format!(
+                    "SELECT rowid, content_type, content, {image_data}, encrypted, blob_ref
+                     FROM {table} WHERE {condition}"
+                ))?;
+                let rows = stmt
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<_, i64>(0)?,
+                            row.get::<_, String>(1)?,
+                            row.get::<_, Option<String>>(2)?,
+                            row.get::<_, Option<Vec<u8>>>(3)?,
+                            row.get::<_, bool>(4)?,
+                            row.get::<_, Option<String>>(5)?,
+                        ))
+                    })?
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+
+                for (rowid, content_type, content, image, encrypted, blob_ref) in rows {
+                    let mut entry = Entry::new_text(String::new());
+                    entry.content_type =
+                        ContentType::parse(&content_type).unwrap_or(ContentType::Text);
+                    entry.content = content;
+                    entry.image_data = image;
+                    self.open_entry(&mut entry, encrypted, blob_ref.as_deref())?;
+                    entry.hash = match (&entry.image_data, &entry.content) {
+                        (Some(data), _) => hash_content(data),
+                        (None, text) => {
+                            hash_content(text.as_deref().unwrap_or_default().as_bytes())
+                        }
+                    };
+
+                    let sealed = self.seal_with(&entry, cipher)?;
+                    self.conn.execute(
+                        &format!(
+                            "UPDATE {table} SET content = ?1, blob_ref = ?2, size = ?3,
+                                 encrypted = ?4
+                             WHERE rowid = ?5"
+                        ),
+                        params![
+                            sealed.content,
+                            sealed.blob_ref,
+                            sealed.size,
+                            sealed.encrypted,
+                            rowid
+                        ],
+                    )?;
+                    if images {
+                        self.conn.execute(
+                            &format!(
+                                "UPDATE {table} SET image_data = ?1, hash = ?2 WHERE rowid = ?3"
+                            ),
+                            params![sealed.image_data, sealed.hash, rowid],
+                        )?;
+                    }
+                }
+            }
+            Ok(&before - &self.live_blobs()?)
+        })?;
+        self.remove_blobs(&replaced)
+    }
+
+    fn reseal(&self, entry: &Entry, cipher: Option<&Cipher>) -> Result<()> {
+        let sealed = self.seal_with(entry, cipher)?;
+        let updated = self.conn.execute(
+            "UPDATE OR IGNORE entries
+             SET content = ?1, image_data = ?2, blob_ref = ?3, hash = ?4, size = ?5,
+                 encrypted = ?6
+             WHERE id = ?7",
+            params![
+                sealed.content,
+                sealed.image_data,
+                sealed.blob_ref,
+                sealed.hash,
+                sealed.size,
+                sealed.encrypted,
+                entry.id
+            ],
+        )?;
+        // Same content already stored under the new hash
+        if updated == 0 {
+            self.conn
+                .execute("DELETE FROM entries WHERE id = ?1", [entry.id])?;
+        }
+        Ok(())
+    }
+
+    /// Delete blobs whose references were dropped. Holds the write lock and
+    /// skips any a concurrent insert has referenced again in the meantime.
+    fn remove_blobs(&self, keys: &HashSet<String>) -> Result<()> {
+        let Some(blobs) = &self.blobs else {
+            return Ok(());
+        };
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.write(|| {
+            for key in keys - &self.live_blobs()? {
+                if self.shred {
+                    blobs.shred(&key)?;
+                } else {
+                    blobs.remove(&key)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Rebuild the file without free pages after a bulk delete, so it
+    /// shrinks and keeps no trace of the deleted rows' layout
+    fn scrub(&self) -> Result<()> {
+        self.conn.execute_batch("VACUUM")?;
+        self.checkpoint()
+    }
+
+    /// Fold the WAL into the database and truncate it. `secure_delete`
+    /// zeroes deleted rows in the database pages, but the WAL still holds
+    /// earlier page images of them. A reader in another process can keep
+    /// the checkpoint from finishing: retry with backoff, then report it.
+    fn checkpoint(&self) -> Result<()> {
+        let mut delay = BUSY_RETRY_DELAY;
+        for retry in 0..=BUSY_RETRIES {
+            let busy: bool = self
+                .conn
+                .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+            if !busy {
+                return Ok(());
+            }
+            if retry < BUSY_RETRIES {
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+        }
+        Err(StickyError::WalBusy)
+    }
+
+    /// Payload and hash as stored: encrypted and keyed when unlocked, and
+    /// moved to the blob store if it's an image or large text
+    fn seal(&self, entry: &Entry) -> Result<Sealed> {
+        self.seal_with(entry, self.cipher.as_ref())
+    }
+
+    fn seal_with(&self, entry: &Entry, cipher: Option<&Cipher>) -> Result<Sealed> {
+        let payload = match (&entry.image_data, &entry.content) {
+            (Some(data), _) => data.as_slice(),
+            (None, Some(text)) => text.as_bytes(),
+            (None, None) => &[],
+        };
+        let hash = match cipher {
+            Some(cipher) => cipher.keyed_hash(&entry.hash),
+            None => entry.hash.clone(),
+        };
+        let mut sealed = Sealed {
+            content: None,
+            image_data: None,
+            blob_ref: None,
+            hash,
+            size: payload.len() as i64,
+            encrypted: cipher.is_some(),
+        };
+
+        if let Some(blobs) = &self.blobs {
+            if entry.image_data.is_some() || payload.len() > BLOB_THRESHOLD_BYTES {
+                blobs.put(&sealed.hash, payload, cipher)?;
+                sealed.blob_ref = Some(sealed.hash.clone());
+                return Ok(sealed);
+            }
+        }
+
+        match cipher {
+            Some(cipher) => {
+                sealed.content = entry
+                    .content
+                    .as_ref()
+                    .map(|text| BASE64.encode(cipher.encrypt(text.as_bytes())));
+                sealed.image_data = entry.image_data.as_ref().map(|data| cipher.encrypt(data));
+            }
+            None => {
+                sealed.content = entry.content.clone();
+                sealed.image_data = entry.image_data.clone();
+            }
+        }
+        Ok(sealed)
+    }
+
+    /// Load a stored entry's payload from the blob store and/or decrypt it,
+    /// restoring its plaintext hash
+    fn open_entry(&self, entry: &mut Entry, encrypted: bool, blob_ref: Option<&str>) -> Result<()> {
+        let cipher = if encrypted {
+            Some(self.cipher.as_ref().ok_or(StickyError::Locked)?)
+        } else {
+            None
+        };
+
+        if let Some(key) = blob_ref {
+            let data = self.blob_store(key)?.get(key, cipher)?;
+            entry.hash = hash_content(&data);
+            match entry.content_type {
+                ContentType::Image => entry.image_data = Some(data),
+                ContentType::Text | ContentType::Link => {
+                    let text =
+                        String::from_utf8(data).map_err(|e| StickyError::Blob(e.to_string()))?;
+                    entry.content = Some(text);
+                }
+            }
+            return Ok(());
+        }
+
+        let Some(cipher) = cipher else {
+            return Ok(());
+        };
+        if let Some(content) = entry.content.take() {
+            let text = decrypt_text(cipher, &content)?;
+            entry.hash = hash_content(text.as_bytes());
+            entry.content = Some(text);
+        }
+        if let Some(data) = entry.image_data.take() {
+            let data = cipher.decrypt(&data)?;
+            entry.hash = hash_content(&data);
+            entry.image_data = Some(data);
+        }
+        Ok(())
+    }
+
+    /// Fill in the preview of text that isn't stored inline as plaintext
+    fn open_summary(
+        &self,
+        summary: &mut EntrySummary,
+        encrypted: bool,
+        blob_ref: Option<&str>,
+    ) -> Result<()> {
+        let cipher = if encrypted {
+            Some(self.cipher.as_ref().ok_or(StickyError::Locked)?)
+        } else {
+            None
+        };
+        if summary.content_type == ContentType::Image {
+            return Ok(());
+        }
+
+        let text = if let Some(key) = blob_ref {
+            // A char is at most 4 bytes, so this covers PREVIEW_CHARS
+            let mut head = Vec::new();
+            self.blob_store(key)?
+                .reader(key, cipher)?
+                .take(PREVIEW_CHARS as u64 * 4)
+                .read_to_end(&mut head)?;
+            String::from_utf8_lossy(&head).into_owned()
+        } else if let Some(cipher) = cipher {
+            decrypt_text(cipher, &summary.preview)?
+        } else {
+            return Ok(());
+        };
+        summary.preview = text.chars().take(PREVIEW_CHARS).collect();
+        Ok(())
+    }
+
+    fn blob_store(&self, key: &str) -> Result<&BlobStore> {
+        self.blobs
+            .as_ref()
+            .ok_or_else(|| StickyError::Blob(format!("no blob store for {key}")))
+    }
+
+    fn stored_hash(&self, hash: &str) -> String {
+        match &self.cipher {
+            Some(cipher) => cipher.keyed_hash(hash),
+            None => hash.to_string(),
+        }
+    }
+
+    fn query_entries<P: Params>(&self, sql: &str, params: P) -> Result<Vec<Entry>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt
+            .query_map(params, |row| {
+                Ok((
+                    row_to_entry(row),
+                    row.get::<_, bool>(11)?,
+                    row.get::<_, Option<String>>(13)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut entries = Vec::with_capacity(rows.len());
+        for (mut entry, encrypted, blob_ref) in rows {
+            match self.open_entry(&mut entry, encrypted, blob_ref.as_deref()) {
+                Ok(()) => entries.push(entry),
+                Err(_) if self.vanished(entry.id, blob_ref.as_deref())? => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn query_summaries<P: Params>(&self, sql: &str, params: P) -> Result<Vec<EntrySummary>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt
+            .query_map(params, |row| {
+                Ok((
+                    row_to_summary(row),
+                    row.get::<_, bool>(10)?,
+                    row.get::<_, Option<String>>(12)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut summaries = Vec::with_capacity(rows.len());
+        for (mut summary, encrypted, blob_ref) in rows {
+            match self.open_summary(&mut summary, encrypted, blob_ref.as_deref()) {
+                Ok(()) => summaries.push(summary),
+                Err(_) if self.vanished(summary.id, blob_ref.as_deref())? => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(summaries)
+    }
+
+    /// Whether another process deleted or resealed a blob-backed row after
+    /// it was read, taking the blob with it. Such rows are left out.
+    fn vanished(&self, id: i64, blob_ref: Option<&str>) -> Result<bool> {
+        let Some(key) = blob_ref else {
+            return Ok(false);
+        };
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM entries WHERE id = ?1 AND blob_ref = ?2)",
+            params![id, key],
+            |row| row.get(0),
+        )?;
+        Ok(!exists)
+    }
+
+    fn live_blobs(&self) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT blob_ref FROM entries WHERE blob_ref IS NOT NULL
+             UNION SELECT blob_ref FROM trash WHERE blob_ref IS NOT NULL
+             UNION SELECT blob_ref FROM revisions WHERE blob_ref IS NOT NULL
+             UNION SELECT blob_ref FROM registers WHERE blob_ref IS NOT NULL",
+        )?;
+        let refs = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<HashSet<_>, _>>()?;
+        Ok(refs)
+    }
+
+    /// Remove blob files no entry refers to. Returns how many were removed.
+    fn collect_blobs(&self) -> Result<usize> {
+        match &self.blobs {
+            Some(blobs) => blobs.gc(&self.live_blobs()?),
+            None => Ok(0),
+        }
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let result = self
+            .conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            });
+
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set_meta(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    fn prune_tags(&self) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM tags WHERE keep = 0
+             AND id NOT IN (SELECT DISTINCT tag_id FROM entry_tags)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Drop the revisions of entries that are gone for good. A trashed
+    /// entry keeps its revisions, for when it's restored.
+    fn prune_revisions(&self) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM revisions WHERE entry_id NOT IN (SELECT id FROM entries)
+             AND entry_id NOT IN (SELECT id FROM trash)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn ensure_exists(&self, id: i64) -> Result<()> {
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM entries WHERE id = ?1)",
+            [id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(StickyError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    fn summaries_by_ids(&self, ids: &[i64]) -> Result<Vec<EntrySummary>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids = ids.iter().map(i64::to_string).collect::<Vec<_>>().join(",");
+        self.query_summaries(
+            &format!(
+                "SELECT {SUMMARY_COLUMNS} FROM entries WHERE id IN ({ids})
+                 ORDER BY last_seen DESC, id DESC"
+            ),
+            [],
+        )
+    }
+
+    /// Delete the rows matching `condition`: into the trash when it's on
+    /// and not shredding, otherwise for good
+    fn delete_rows(&self, condition: &str) -> Result<usize> {
+        if !self.trash.enabled() || self.shred {
+            return self.discard(&format!("DELETE FROM entries WHERE {condition}"));
+        }
+        self.write(|| {
+            let batch = self.next_batch()?;
+            let deleted = self.move_to_trash(condition, batch)?;
+            self.prune_tags()?;
+            Ok(deleted)
+        })
+    }
+
+    /// `delete_rows` for clearing, which vacuums too when the rows are gone
+    /// for good
+    fn clear_rows(&self, condition: &str) -> Result<usize> {
+        let deleted = self.delete_rows(condition)?;
+        if deleted > 0 && (!self.trash.enabled() || self.shred) {
+            self.scrub()?;
+        }
+        Ok(deleted)
+    }
+
+    /// Run a DELETE statement, then remove the blobs it left unreferenced
+    /// and checkpoint the deleted rows out of the WAL
+    fn discard(&self, sql: &str) -> Result<usize> {
+        let (deleted, removed) = self.write(|| {
+            let before = self.live_blobs()?;
+            let deleted = self.conn.execute(sql, [])?;
+            self.prune_tags()?;
+            self.prune_revisions()?;
+            Ok((deleted, &before - &self.live_blobs()?))
+        })?;
+        self.remove_blobs(&removed)?;
+        if deleted > 0 {
+            self.checkpoint()?;
+        }
+        Ok(deleted)
+    }
+
+    /// Number for the next deletion, so `undo` can restore it as a whole
+    fn next_batch(&self) -> Result<i64> {
+        Ok(self
+            .conn
+            .query_row("SELECT COALESCE(MAX(batch), 0) + 1 FROM trash", [], |row| {
+                row.get(0)
+            })?)
+    }
+
+    /// Move the entries matching `condition` to the trash, tags and all.
+    /// Their blobs stay until they're purged. Call inside `write`.
+    fn move_to_trash(&self, condition: &str, batch: i64) -> Result<usize> {
+        self.conn.execute(
+            &format!(
+                "INSERT INTO trash ({STORED_COLUMNS}, tags, deleted_at, batch)
+                 SELECT {STORED_COLUMNS},
+                    (SELECT group_concat(t.name, ',') FROM entry_tags et
+                     JOIN tags t ON t.id = et.tag_id WHERE et.entry_id = entries.id),
+                    unixepoch(), ?1
+                 FROM entries WHERE {condition}"
+            ),
+            [batch],
+        )?;
+        Ok(self
+            .conn
+            .execute(&format!("DELETE FROM entries WHERE {condition}"), [])?)
+    }
+
+    /// Replace a text entry's content, keeping the old as a revision if
+    /// `keep`, and merging it into an entry already holding the new content
+    fn rewrite(&self, id: i64, content: &str, keep: bool) -> Result<i64> {
+        let edited = Entry::new_text(content.to_string());
+        self.write(|| {
+            let entry = self.get_by_id(id)?;
+            if entry.content_type == ContentType::Image {
+                return Err(StickyError::NotText(id));
+            }
+            if entry.hash == edited.hash {
+                return Ok(id);
+            }
+            if keep {
+                self.conn.execute(
+                    "INSERT INTO revisions
+                        (entry_id, content_type, content, blob_ref, encrypted, size, replaced_at)
+                     SELECT id, content_type, content, blob_ref, encrypted, size, unixepoch()
+                     FROM entries WHERE id = ?1",
+                    [id],
+                )?;
+            }
+
+            let sealed = self.seal(&edited)?;
+            if let Some(other) = self.find_by_hash(&edited.hash)? {
+                // Already stored: fold this entry into that one
+                self.conn.execute(
+                    "UPDATE entries AS o SET
+                        last_seen = MAX(o.last_seen, e.last_seen),
+                        copy_count = o.copy_count + e.copy_count,
+                        pinned = MAX(o.pinned, e.pinned),
+                        use_count = o.use_count + e.use_count,
+                        last_used = COALESCE(MAX(o.last_used, e.last_used),
+                                             o.last_used, e.last_used)
+                     FROM entries AS e WHERE o.id = ?1 AND e.id = ?2",
+                    [other, id],
+                )?;
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO entry_tags (entry_id, tag_id)
+                     SELECT ?1, tag_id FROM entry_tags WHERE entry_id = ?2",
+                    [other, id],
+                )?;
+                self.conn.execute(
+                    "UPDATE revisions SET entry_id = ?1 WHERE entry_id = ?2",
+                    [other, id],
+                )?;
+                self.conn
+                    .execute("DELETE FROM entries WHERE id = ?1", [id])?;
+                return Ok(other);
+            }
+
+            self.conn.execute(
+                "UPDATE entries
+                 SET content_type = ?1, content = ?2, image_data = NULL, blob_ref = ?3,
+                     hash = ?4, size = ?5, encrypted = ?6
+                 WHERE id = ?7",
+                params![
+                    edited.content_type.as_str(),
+                    sealed.content,
+                    sealed.blob_ref,
+                    sealed.hash,
+                    sealed.size,
+                    sealed.encrypted,
+                    id
+                ],
+            )?;
+            Ok(id)
+        })
+    }
+
+    /// Move the trashed entries matching `condition` back, merging any
+    /// whose content was copied again meanwhile. Returns their ids.
+    fn restore_rows(&self, condition: &str) -> Result<Vec<i64>> {
+        self.write(|| {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT id, tags FROM trash WHERE {condition} ORDER BY id"
+            ))?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            let mut ids = Vec::with_capacity(rows.len());
+            for (id, tags) in rows {
+                let restored: i64 = self.conn.query_row(
+                    &format!(
+                        "INSERT INTO entries ({STORED_COLUMNS})
+                         SELECT {STORED_COLUMNS} FROM trash WHERE id = ?1
+                         {MERGE_DUPLICATE}"
+                    ),
+                    [id],
+                    |row| row.get(0),
+                )?;
+                self.conn.execute("DELETE FROM trash WHERE id = ?1", [id])?;
+                if restored != id {
+                    self.conn.execute(
+                        "UPDATE revisions SET entry_id = ?1 WHERE entry_id = ?2",
+                        [restored, id],
+                    )?;
+                }
+                let tags = split_tags(tags);
+                if !tags.is_empty() {
+                    self.add_tags(restored, &tags)?;
+                }
+                ids.push(restored);
+            }
+            Ok(ids)
+        })
+    }
+}
+
+impl HistoryStore for Storage {
+    /// Insert an entry, or bump the existing row with the same hash to the
+    /// top of the history. Returns the row id either way.
+    fn insert(&self, entry: &Entry) -> Result<i64> {
+        // The blob is written under the lock too, so a concurrent delete
+        // can't remove it between the write and the upsert
+        self.write(|| {
+            let sealed = self.seal(entry)?;
+            // Ids of trashed entries aren't reused, so they can be restored
+            let id: i64 = self.conn.query_row(
+                &format!(
+                    "INSERT INTO entries ({STORED_COLUMNS})
+                     VALUES (MAX((SELECT COALESCE(MAX(id), 0) FROM entries),
+                                 (SELECT COALESCE(MAX(id), 0) FROM trash)) + 1,
+                             ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                     {MERGE_DUPLICATE}"
+                ),
+                params![
+                    entry.content_type.as_str(),
+                    sealed.content,
+                    sealed.image_data,
+                    sealed.hash,
+                    entry.created_at,
+                    entry.last_seen,
+                    entry.copy_count,
+                    entry.pinned,
+                    entry.use_count,
+                    entry.last_used,
+                    sealed.encrypted,
+                    sealed.blob_ref,
+                    sealed.size,
+                ],
+                |row| row.get(0),
+            )?;
+            if !entry.tags.is_empty() {
+                self.add_tags(id, &entry.tags)?;
+            }
+            Ok(id)
+        })
+    }
+
+    fn capture(&self, entry: &Entry) -> Result<i64> {
+        self.write(|| {
+            let id = self.insert(entry)?;
+            self.conn
+                .execute("INSERT INTO captures (at) VALUES (?1)", [entry.last_seen])?;
+            Ok(id)
+        })
+    }
+
+    /// Find an entry by the plaintext hash of its content
+    fn find_by_hash(&self, hash: &str) -> Result<Option<i64>> {
+        let result = self.conn.query_row(
+            "SELECT id FROM entries WHERE hash = ?1",
+            [self.stored_hash(hash)],
+            |row| row.get(0),
+        );
+
+        match result {
+            Ok(id) => Ok(Some(id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Plaintext hash of the most recently copied entry
+    fn get_latest_hash(&self) -> Result<Option<String>> {
+        let latest = self.query_entries(
+            &format!(
+                "SELECT {ENTRY_COLUMNS} FROM entries ORDER BY last_seen DESC, id DESC LIMIT 1"
+            ),
+            [],
+        )?;
+        Ok(latest.into_iter().next().map(|e| e.hash))
+    }
+
+    fn get_by_id(&self, id: i64) -> Result<Entry> {
+        self.query_entries(
+            &format!("SELECT {ENTRY_COLUMNS} FROM entries WHERE id = ?1"),
+            [id],
+        )?
+        .pop()
+        .ok_or(StickyError::NotFound(id))
+    }
+
+    fn delete_ids(&self, ids: &[i64]) -> Result<()> {
+        for &id in ids {
+            self.ensure_exists(id)?;
+        }
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<String> = ids.iter().map(i64::to_string).collect();
+        self.delete_rows(&format!("id IN ({})", ids.join(",")))?;
+        Ok(())
+    }
+
+    fn list_sorted(&self, limit: usize, order: SortOrder) -> Result<Vec<EntrySummary>> {
+        self.query_summaries(
+            &format!(
+                "SELECT {SUMMARY_COLUMNS} FROM entries ORDER BY {} LIMIT ?1",
+                order_by(order)
+            ),
+            [limit],
+        )
+    }
+
+    fn list_pinned(&self) -> Result<Vec<EntrySummary>> {
+        self.query_summaries(
+            &format!(
+                "SELECT {SUMMARY_COLUMNS} FROM entries WHERE pinned = 1
+                 ORDER BY last_seen DESC, id DESC"
+            ),
+            [],
+        )
+    }
+
+    /// Record that an entry was retrieved (via `syo get` or the popup)
+    fn record_use(&self, id: i64) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE entries SET use_count = use_count + 1, last_used = unixepoch()
+             WHERE id = ?1",
+            [id],
+        )?;
+        if updated == 0 {
+            return Err(StickyError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    fn set_pinned(&self, id: i64, pinned: bool) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE entries SET pinned = ?1 WHERE id = ?2",
+            params![pinned, id],
+        )?;
+        if updated == 0 {
+            return Err(StickyError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    /// Replace a text entry's content in place. The old row's stored
+    /// columns become a revision, so an old blob stays referenced by it.
+    fn edit(&self, id: i64, content: &str) -> Result<i64> {
+        self.rewrite(id, content, true)
+    }
+
+    fn replace(&self, id: i64, content: &str) -> Result<i64> {
+        let (id, removed) = self.write(|| {
+            let before = self.live_blobs()?;
+            let id = self.rewrite(id, content, false)?;
+            Ok((id, &before - &self.live_blobs()?))
+        })?;
+        self.remove_blobs(&removed)?;
+        Ok(id)
+    }
+
+    fn list_revisions(&self, id: i64) -> Result<Vec<Revision>> {
+        self.ensure_exists(id)?;
+        let mut stmt = self.conn.prepare(
+            "SELECT content_type, content, blob_ref, encrypted, replaced_at FROM revisions
+             WHERE entry_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map([id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, bool>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut revisions = Vec::with_capacity(rows.len());
+        for (content_type, content, blob_ref, encrypted, replaced_at) in rows {
+            let mut entry = Entry::new_text(String::new());
+            entry.content_type = ContentType::parse(&content_type).unwrap_or(ContentType::Text);
+            entry.content = content;
+            self.open_entry(&mut entry, encrypted, blob_ref.as_deref())?;
+            revisions.push(Revision {
+                content_type: entry.content_type,
+                content: entry.content.unwrap_or_default(),
+                replaced_at,
+            });
+        }
+        Ok(revisions)
+    }
+
+    fn list_tagged(&self, tag: &str, limit: usize, order: SortOrder) -> Result<Vec<EntrySummary>> {
+        let tag = normalize_tag(tag).ok_or_else(|| StickyError::InvalidTag(tag.to_string()))?;
+        self.query_summaries(
+            &format!(
+                "SELECT {SUMMARY_COLUMNS} FROM entries
+                 WHERE id IN (SELECT et.entry_id FROM entry_tags et
+                              JOIN tags t ON t.id = et.tag_id WHERE t.name = ?1)
+                 ORDER BY {} LIMIT ?2",
+                order_by(order)
+            ),
+            params![tag, limit],
+        )
+    }
+
+    /// Every entry matching `filter`, most recently copied first
+    fn list_filtered(&self, filter: &EntryFilter) -> Result<Vec<EntrySummary>> {
+        let (conditions, values) = filter_conditions(filter)?;
+        let summaries = self.query_summaries(
+            &format!(
+                "SELECT {SUMMARY_COLUMNS} FROM entries WHERE {}
+                 ORDER BY last_seen DESC, id DESC",
+                conditions.join(" AND ")
+            ),
+            rusqlite::params_from_iter(values),
+        )?;
+        let Some(query) = &filter.query else {
+            return Ok(summaries);
+        };
+        let query = query.to_lowercase();
+        let mut matched = Vec::new();
+        for summary in summaries {
+            // Only load the full text when the preview was cut short
+            let found = summary.preview.to_lowercase().contains(&query)
+                || (summary.size as usize > summary.preview.len()
+                    && self
+                        .get_by_id(summary.id)?
+                        .content
+                        .is_some_and(|c| c.to_lowercase().contains(&query)));
+            if found {
+                matched.push(summary);
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Pages come straight from SQL, except with a text query, which is
+    /// matched after loading like in `list_filtered`
+    fn list_page(&self, after: Option<Cursor>, limit: usize, filter: &EntryFilter) -> Result<Page> {
+        if filter.query.is_some() {
+            return Ok(Page::of(self.list_filtered(filter)?, after, limit));
+        }
+        let (mut conditions, mut values) = filter_conditions(filter)?;
+        if let Some(after) = after {
+            values.push(after.last_seen.into());
+            values.push(after.id.into());
+            let n = values.len();
+            conditions.push(format!(
+                "(last_seen < ?{0} OR (last_seen = ?{0} AND id < ?{n}))",
+                n - 1
+            ));
+        }
+        // One more than asked for, to know whether there's another page
+        values.push((limit as i64 + 1).into());
+        let entries = self.query_summaries(
+            &format!(
+                "SELECT {SUMMARY_COLUMNS} FROM entries WHERE {}
+                 ORDER BY last_seen DESC, id DESC LIMIT ?{}",
+                conditions.join(" AND "),
+                values.len()
+            ),
+            rusqlite::params_from_iter(values),
+        )?;
+        Ok(Page::of(entries, None, limit))
+    }
+
+    fn delete_where(&self, filter: &EntryFilter) -> Result<usize> {
+        let ids: Vec<String> = self
+            .list_filtered(filter)?
+            .iter()
+            .filter(|s| !s.pinned)
+            .map(|s| s.id.to_string())
+            .collect();
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        self.delete_rows(&format!("pinned = 0 AND id IN ({})", ids.join(",")))
+    }
+
+    /// Attach tags to an entry, creating any that don't exist yet
+    fn add_tags(&self, id: i64, tags: &[String]) -> Result<()> {
+        let tags = normalize_tags(tags)?;
+        self.write(|| {
+            self.ensure_exists(id)?;
+            for tag in &tags {
+                self.conn
+                    .execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [&tag])?;
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO entry_tags (entry_id, tag_id)
+                     SELECT ?1, id FROM tags WHERE name = ?2",
+                    params![id, tag],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Detach tags from an entry; tags left without entries are dropped
+    fn remove_tags(&self, id: i64, tags: &[String]) -> Result<()> {
+        let tags = normalize_tags(tags)?;
+        self.write(|| {
+            self.ensure_exists(id)?;
+            for tag in &tags {
+                self.conn.execute(
+                    "DELETE FROM entry_tags
+                     WHERE entry_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+                    params![id, tag],
+                )?;
+            }
+            self.prune_tags()?;
+            Ok(())
+        })
+    }
+
+    fn list_tags(&self) -> Result<Vec<TagInfo>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.name, COUNT(et.entry_id), t.keep FROM tags t
+             LEFT JOIN entry_tags et ON et.tag_id = t.id
+             GROUP BY t.id ORDER BY t.name",
+        )?;
+
+        let tags = stmt
+            .query_map([], |row| {
+                Ok(TagInfo {
+                    name: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                    keep: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(tags)
+    }
+
+    /// Mark a tag as kept: its entries are exempt from retention cleanup
+    fn set_tag_keep(&self, tag: &str, keep: bool) -> Result<()> {
+        let name = normalize_tag(tag).ok_or_else(|| StickyError::InvalidTag(tag.to_string()))?;
+        let updated = self.conn.execute(
+            "UPDATE tags SET keep = ?1 WHERE name = ?2",
+            params![keep, name],
+        )?;
+        if updated == 0 {
+            return Err(StickyError::TagNotFound(name));
+        }
+        Ok(())
+    }
+
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<EntrySummary>> {
+        // Inline plaintext is matched in SQL. Encrypted text and text in
+        // blobs can't be, nor can queries LIKE doesn't fold, so those rows
+        // are loaded one at a time and matched here.
+        let mut stmt = self.conn.prepare(
+            "SELECT id, ?1 IS NOT NULL AND encrypted = 0 AND blob_ref IS NULL
+                    AND content LIKE ?1 ESCAPE '\\'
+             FROM entries
+             WHERE content_type != 'image'
+               AND (?1 IS NULL OR content LIKE ?1 ESCAPE '\\'
+                    OR encrypted = 1 OR blob_ref IS NOT NULL)
+             ORDER BY last_seen DESC, id DESC",
+        )?;
+        let candidates = stmt
+            .query_map([like_pattern(query)], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let query = query.to_lowercase();
+        let mut ids = Vec::new();
+        for (id, matched) in candidates {
+            if ids.len() == limit {
+                break;
+            }
+            if matched
+                || self
+                    .get_by_id(id)?
+                    .content
+                    .is_some_and(|c| c.to_lowercase().contains(&query))
+            {
+                ids.push(id);
+            }
+        }
+        self.summaries_by_ids(&ids)
+    }
+
+    /// Enforce the retention policy in a single transaction, then purge
+    /// the trash. Pinned entries and entries in kept collections are never
+    /// removed.
+    fn cleanup_old(&self, policy: &RetentionConfig) -> Result<CleanupReport> {
+        let exempt = format!("pinned = 1 OR id IN ({KEPT_BY_TAG})");
+        let now = chrono::Utc::now().timestamp();
+        let to_trash = self.trash.enabled() && self.trash.expired;
+        let mut report = CleanupReport::default();
+        self.write(|| {
+            // Start over if a busy retry runs this again
+            report = CleanupReport::default();
+            let batch = self.next_batch()?;
+            let remove = |condition: String| {
+                if to_trash {
+                    self.move_to_trash(&condition, batch)
+                } else {
+                    Ok(self
+                        .conn
+                        .execute(&format!("DELETE FROM entries WHERE {condition}"), [])?)
+                }
+            };
+
+            for content_type in [ContentType::Text, ContentType::Link, ContentType::Image] {
+                let cutoff = now - policy.max_age_for(content_type).0;
+                let deleted = remove(format!(
+                    "content_type = '{}' AND last_seen < {cutoff} AND NOT ({exempt})",
+                    content_type.as_str()
+                ))?;
+                if policy.type_override(content_type).is_some() {
+                    report.expired_by_type += deleted;
+                } else {
+                    report.expired += deleted;
+                }
+            }
+
+            if let Some(max) = policy.max_entries {
+                report.over_count = remove(format!(
+                    "id IN (SELECT id FROM entries WHERE NOT ({exempt})
+                            ORDER BY last_seen DESC, id DESC LIMIT -1 OFFSET {max})"
+                ))?;
+            }
+
+            if let Some(max) = policy.max_bytes {
+                report.over_size = remove(format!(
+                    "id IN (SELECT id FROM (
+                                SELECT id, SUM(size)
+                                    OVER (ORDER BY last_seen DESC, id DESC) AS running
+                                FROM entries WHERE NOT ({exempt}))
+                            WHERE running > {})",
+                    max.0
+                ))?;
+            }
+
+            // Copies are only charted over the retention window
+            self.conn.execute(
+                "DELETE FROM captures WHERE at < ?1",
+                [now - policy.longest_age().0],
+            )?;
+            self.prune_tags()?;
+            self.prune_revisions()
+        })?;
+        report.purged = self.purge_trash(false)?;
+        report.orphan_blobs = self.collect_blobs()?;
+        Ok(report)
+    }
+
+    /// Delete all unpinned entries
+    fn clear(&self) -> Result<usize> {
+        self.clear_rows("pinned = 0")
+    }
+
+    /// Delete every entry, pinned ones included
+    fn clear_all(&self) -> Result<usize> {
+        self.clear_rows("1")
+    }
+
+    fn set_trash(&mut self, trash: TrashConfig) {
+        self.trash = trash;
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashItem>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {TRASH_SUMMARY_COLUMNS} FROM trash ORDER BY deleted_at DESC, batch DESC, id DESC"
+        ))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row_to_summary(row),
+                    row.get::<_, bool>(10)?,
+                    row.get::<_, Option<String>>(12)?,
+                    row.get::<_, i64>(13)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut items = Vec::with_capacity(rows.len());
+        for (mut entry, encrypted, blob_ref, deleted_at) in rows {
+            self.open_summary(&mut entry, encrypted, blob_ref.as_deref())?;
+            items.push(TrashItem { entry, deleted_at });
+        }
+        Ok(items)
+    }
+
+    fn restore(&self, id: i64) -> Result<i64> {
+        self.restore_rows(&format!("id = {id}"))?
+            .pop()
+            .ok_or(StickyError::NotInTrash(id))
+    }
+
+    fn undo(&self) -> Result<Vec<i64>> {
+        self.restore_rows("batch = (SELECT MAX(batch) FROM trash)")
+    }
+
+    fn purge_trash(&self, all: bool) -> Result<usize> {
+        let condition = if all || !self.trash.enabled() {
+            "1".to_string()
+        } else {
+            format!("deleted_at < unixepoch() - {}", self.trash.grace.0)
+        };
+        let purged = self.discard(&format!("DELETE FROM trash WHERE {condition}"))?;
+        if purged > 0 {
+            self.scrub()?;
+        }
+        Ok(purged)
+    }
+
+    fn set_shred(&mut self, shred: bool) -> Result<()> {
+        self.shred = shred;
+        Ok(())
+    }
+
+    fn set_register(&self, name: char, entry: &Entry) -> Result<()> {
+        let name = register_name(name)?;
+        let entry = register_copy(entry, chrono::Utc::now().timestamp());
+        let replaced = self.write(|| {
+            let before = self.live_blobs()?;
+            let sealed = self.seal(&entry)?;
+            self.conn.execute(
+                "INSERT OR REPLACE INTO registers
+                    (name, content_type, content, image_data, hash, encrypted, blob_ref, size,
+                     set_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    name.to_string(),
+                    entry.content_type.as_str(),
+                    sealed.content,
+                    sealed.image_data,
+                    sealed.hash,
+                    sealed.encrypted,
+                    sealed.blob_ref,
+                    sealed.size,
+                    entry.created_at
+                ],
+            )?;
+            Ok(&before - &self.live_blobs()?)
+        })?;
+        self.remove_blobs(&replaced)
+    }
+
+    fn get_register(&self, name: char) -> Result<Entry> {
+        let name = register_name(name)?;
+        let result = self.conn.query_row(
+            &format!("SELECT {REGISTER_COLUMNS} FROM registers WHERE name = ?1"),
+            [name.to_string()],
+            |row| {
+                Ok((
+                    row_to_entry(row),
+                    row.get::<_, bool>(11)?,
+                    row.get::<_, Option<String>>(13)?,
+                ))
+            },
+        );
+        let (mut entry, encrypted, blob_ref) = match result {
+            Ok(row) => row,
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                return Err(StickyError::EmptyRegister(name))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        self.open_entry(&mut entry, encrypted, blob_ref.as_deref())?;
+        Ok(entry)
+    }
+
+    fn list_registers(&self) -> Result<Vec<RegisterItem>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {REGISTER_SUMMARY_COLUMNS} FROM registers ORDER BY name"
+        ))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row_to_summary(row),
+                    row.get::<_, bool>(10)?,
+                    row.get::<_, Option<String>>(12)?,
+                    row.get::<_, String>(13)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut items = Vec::with_capacity(rows.len());
+        for (mut entry, encrypted, blob_ref, name) in rows {
+            self.open_summary(&mut entry, encrypted, blob_ref.as_deref())?;
+            items.push(RegisterItem {
+                name: name.chars().next().unwrap_or('?'),
+                set_at: entry.last_seen,
+                entry,
+            });
+        }
+        Ok(items)
+    }
+
+    fn clear_register(&self, name: char) -> Result<()> {
+        let name = register_name(name)?;
+        if self.discard(&format!("DELETE FROM registers WHERE name = '{name}'"))? == 0 {
+            return Err(StickyError::EmptyRegister(name));
+        }
+        Ok(())
+    }
+
+    fn count(&self) -> Result<usize> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM entries", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    fn stats(&self, since: i64, top: usize) -> Result<Stats> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT content_type, COUNT(*), SUM(size), MIN(last_seen),
+                    MIN(CASE WHEN pinned = 0 AND id NOT IN ({KEPT_BY_TAG})
+                        THEN last_seen END)
+             FROM entries GROUP BY content_type ORDER BY content_type"
+        ))?;
+        let types = stmt
+            .query_map([], |row| {
+                Ok(TypeStats {
+                    content_type: ContentType::parse(&row.get::<_, String>(0)?)
+                        .unwrap_or(ContentType::Text),
+                    count: row.get::<_, i64>(1)? as usize,
+                    bytes: row.get(2)?,
+                    oldest: row.get(3)?,
+                    oldest_expiring: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT at / 3600 * 3600 AS hour, COUNT(*) FROM captures
+             WHERE at >= ?1 GROUP BY hour ORDER BY hour",
+        )?;
+        let hourly = stmt
+            .query_map([since], |row| {
+                Ok((row.get(0)?, row.get::<_, i64>(1)? as usize))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let top = self.query_summaries(
+            &format!(
+                "SELECT {SUMMARY_COLUMNS} FROM entries WHERE copy_count + use_count > 1
+                 ORDER BY copy_count + use_count DESC, last_seen DESC, id DESC LIMIT ?1"
+            ),
+            [top],
+        )?;
+
+        let mut disk = Vec::new();
+        if let Some(path) = self.conn.path().filter(|p| !p.is_empty()) {
+            // With its WAL and shared memory files
+            let bytes = ["", "-wal", "-shm"]
+                .iter()
+                .filter_map(|suffix| fs::metadata(format!("{path}{suffix}")).ok())
+                .map(|meta| meta.len())
+                .sum();
+            disk.push(DiskUsage {
+                name: "database",
+                bytes,
+            });
+        }
+        if let Some(blobs) = &self.blobs {
+            disk.push(DiskUsage {
+                name: "blobs",
+                bytes: dir_size(blobs.dir()),
+            });
+        }
+
+        Ok(Stats {
+            types,
+            hourly,
+            top,
+            disk,
+        })
+    }
+}
+
+/// Another connection held the lock for longer than the busy timeout
+fn is_busy(e: &StickyError) -> bool {
+    matches!(
+        e,
+        StickyError::Database(rusqlite::Error::SqliteFailure(err, _))
+            if matches!(err.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+fn row_to_entry(row: &rusqlite::Row) -> Entry {
+    Entry {
+        id: row.get(0).unwrap_or(0),
+        content_type: ContentType::parse(row.get::<_, String>(1).unwrap_or_default().as_str())
+            .unwrap_or(ContentType::Text),
+        content: row.get(2).ok(),
+        image_data: row.get(3).ok(),
+        hash: row.get(4).unwrap_or_default(),
+        created_at: row.get(5).unwrap_or(0),
+        last_seen: row.get(6).unwrap_or(0),
+        copy_count: row.get(7).unwrap_or(1),
+        pinned: row.get(8).unwrap_or(false),
+        use_count: row.get(9).unwrap_or(0),
+        last_used: row.get(10).unwrap_or(None),
+        tags: split_tags(row.get(12).ok().flatten()),
+    }
+}
+
+fn row_to_summary(row: &rusqlite::Row) -> EntrySummary {
+    EntrySummary {
+        id: row.get(0).unwrap_or(0),
+        content_type: ContentType::parse(row.get::<_, String>(1).unwrap_or_default().as_str())
+            .unwrap_or(ContentType::Text),
+        preview: row
+            .get::<_, Option<String>>(2)
+            .ok()
+            .flatten()
+            .unwrap_or_default(),
+        size: row.get(3).unwrap_or(0),
+        created_at: row.get(4).unwrap_or(0),
+        last_seen: row.get(5).unwrap_or(0),
+        copy_count: row.get(6).unwrap_or(1),
+        pinned: row.get(7).unwrap_or(false),
+        use_count: row.get(8).unwrap_or(0),
+        last_used: row.get(9).unwrap_or(None),
+        tags: split_tags(row.get(11).ok().flatten()),
+    }
+}
+
+fn split_tags(tags: Option<String>) -> Vec<String> {
+    tags.map(|t| t.split(',').map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn decrypt_text(cipher: &Cipher, sealed: &str) -> Result<String> {
+    let sealed = BASE64
+        .decode(sealed)
+        .map_err(|e| StickyError::Crypto(e.to_string()))?;
+    String::from_utf8(cipher.decrypt(&sealed)?).map_err(|e| StickyError::Crypto(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Age, ByteSize, RETENTION_HOURS};
+    use crate::entry::Entry;
+
+    fn make_text_entry(text: &str) -> Entry {
+        Entry::new_text(text.to_string())
+    }
+
+    #[test]
+    fn insert_and_get_by_id() {
+        let s = Storage::open_in_memory().unwrap();
+        let entry = make_text_entry("hello");
+        let id = s.insert(&entry).unwrap();
+        let got = s.get_by_id(id).unwrap();
+        assert_eq!(got.content.as_deref(), Some("hello"));
+        assert_eq!(got.id, id);
+    }
+
+    #[test]
+    fn list_returns_newest_first() {
+        let s = Storage::open_in_memory().unwrap();
+        s.insert(&make_text_entry("first")).unwrap();
+        s.insert(&make_text_entry("second")).unwrap();
+        let entries = s.list(10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].preview, "second");
+    }
+
+    #[test]
+    fn search_matches_substring() {
+        let s = Storage::open_in_memory().unwrap();
+        s.insert(&make_text_entry("foo bar baz")).unwrap();
+        s.insert(&make_text_entry("unrelated")).unwrap();
+        let results = s.search("bar", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].preview, "foo bar baz");
+    }
+
+    #[test]
+    fn clear_removes_all() {
+        let s = Storage::open_in_memory().unwrap();
+        s.insert(&make_text_entry("a")).unwrap();
+        s.insert(&make_text_entry("b")).unwrap();
+        let deleted = s.clear().unwrap();
+        assert_eq!(deleted, 2);
+        assert_eq!(s.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn count_tracks_inserts() {
+        let s = Storage::open_in_memory().unwrap();
+        assert_eq!(s.count().unwrap(), 0);
+        s.insert(&make_text_entry("x")).unwrap();
+        assert_eq!(s.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn get_latest_hash() {
+        let s = Storage::open_in_memory().unwrap();
+        assert!(s.get_latest_hash().unwrap().is_none());
+        let entry = make_text_entry("test");
+        let expected_hash = entry.hash.clone();
+        s.insert(&entry).unwrap();
+        assert_eq!(s.get_latest_hash().unwrap().unwrap(), expected_hash);
+    }
+
+    #[test]
+    fn cleanup_old_removes_expired() {
+        let s = Storage::open_in_memory().unwrap();
+        let mut old_entry = make_text_entry("old");
+        old_entry.created_at = chrono::Utc::now().timestamp() - (RETENTION_HOURS * 3600) - 100;
+        old_entry.last_seen = old_entry.created_at;
+        s.capture(&old_entry).unwrap();
+        s.capture(&make_text_entry("new")).unwrap();
+        let report = s.cleanup_old(&RetentionConfig::default()).unwrap();
+        assert_eq!(report.expired, 1);
+        assert_eq!(report.total(), 1);
+        assert_eq!(s.count().unwrap(), 1);
+        // The old copy is forgotten too
+        let copies: usize = s.stats(0, 0).unwrap().hourly.iter().map(|(_, n)| n).sum();
+        assert_eq!(copies, 1);
+    }
+
+    #[test]
+    fn cleanup_applies_type_overrides() {
+        let s = Storage::open_in_memory().unwrap();
+        let two_hours_ago = chrono::Utc::now().timestamp() - 7200;
+        let mut image = Entry::new_image(vec![0x89, b'P', b'N', b'G']);
+        image.created_at = two_hours_ago;
+        image.last_seen = image.created_at;
+        s.insert(&image).unwrap();
+        let mut text = make_text_entry("two days old");
+        text.created_at = chrono::Utc::now().timestamp() - 2 * 86_400;
+        text.last_seen = text.created_at;
+        s.insert(&text).unwrap();
+
+        let mut policy = RetentionConfig::default();
+        policy.types.image = Some(Age(3600));
+        policy.types.text = Some(Age(7 * 86_400));
+
+        let report = s.cleanup_old(&policy).unwrap();
+        assert_eq!(report.expired_by_type, 1);
+        assert_eq!(report.expired, 0);
+        assert_eq!(s.list(10).unwrap()[0].preview, "two days old");
+    }
+
+    #[test]
+    fn cleanup_enforces_max_entries() {
+        let s = Storage::open_in_memory().unwrap();
+        for i in 0..5 {
+            let mut e = make_text_entry(&format!("entry {i}"));
+            e.created_at = chrono::Utc::now().timestamp() - 100 + i;
+            e.last_seen = e.created_at;
+            s.insert(&e).unwrap();
+        }
+        let policy = RetentionConfig {
+            max_entries: Some(3),
+            ..Default::default()
+        };
+        let report = s.cleanup_old(&policy).unwrap();
+        assert_eq!(report.over_count, 2);
+        let remaining: Vec<_> = s.list(10).unwrap().into_iter().map(|e| e.preview).collect();
+        assert_eq!(remaining, vec!["entry 4", "entry 3", "entry 2"]);
+    }
+
+    #[test]
+    fn cleanup_enforces_max_bytes() {
+        let s = Storage::open_in_memory().unwrap();
+        for i in 0..4 {
+            let mut e = make_text_entry(&format!("{i}").repeat(100));
+            e.created_at = chrono::Utc::now().timestamp() - 100 + i;
+            e.last_seen = e.created_at;
+            s.insert(&e).unwrap();
+        }
+        let policy = RetentionConfig {
+            max_bytes: Some(ByteSize(250)),
+            ..Default::default()
+        };
+        let report = s.cleanup_old(&policy).unwrap();
+        assert_eq!(report.over_size, 2);
+        assert_eq!(s.count().unwrap(), 2);
+    }
+
+    #[test]
+    fn cleanup_limits_skip_exempt_entries() {
+        let s = Storage::open_in_memory().unwrap();
+        let pinned = s.insert(&make_text_entry("pinned")).unwrap();
+        s.set_pinned(pinned, true).unwrap();
+        s.insert(&make_text_entry("loose")).unwrap();
+        let policy = RetentionConfig {
+            max_entries: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(s.cleanup_old(&policy).unwrap().over_count, 1);
+        assert_eq!(s.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn pinned_survives_cleanup_and_clear() {
+        let s = Storage::open_in_memory().unwrap();
+        let mut old_entry = make_text_entry("ssh-ed25519 AAAA");
+        old_entry.created_at = chrono::Utc::now().timestamp() - (RETENTION_HOURS * 3600) - 100;
+        old_entry.last_seen = old_entry.created_at;
+        let pinned_id = s.insert(&old_entry).unwrap();
+        s.set_pinned(pinned_id, true).unwrap();
+        s.insert(&make_text_entry("scratch")).unwrap();
+
+        assert_eq!(
+            s.cleanup_old(&RetentionConfig::default()).unwrap().total(),
+            0
+        );
+        assert_eq!(s.clear().unwrap(), 1);
+        assert_eq!(s.count().unwrap(), 1);
+        assert!(s.get_by_id(pinned_id).unwrap().pinned);
+
+        assert_eq!(s.clear_all().unwrap(), 1);
+        assert_eq!(s.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn list_pinned_and_unpin() {
+        let s = Storage::open_in_memory().unwrap();
+        let id = s.insert(&make_text_entry("keep me")).unwrap();
+        s.insert(&make_text_entry("other")).unwrap();
+        s.set_pinned(id, true).unwrap();
+        let pinned = s.list_pinned().unwrap();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].id, id);
+
+        s.set_pinned(id, false).unwrap();
+        assert!(s.list_pinned().unwrap().is_empty());
+    }
+
+    #[test]
+    fn set_pinned_missing_entry() {
+        let s = Storage::open_in_memory().unwrap();
+        assert!(matches!(
+            s.set_pinned(42, true),
+            Err(StickyError::NotFound(42))
+        ));
+    }
+
+    #[test]
+    fn tag_and_list_tagged() {
+        let s = Storage::open_in_memory().unwrap();
+        let id = s.insert(&make_text_entry("SELECT 1")).unwrap();
+        s.insert(&make_text_entry("untagged")).unwrap();
+        s.add_tags(id, &["sql".into(), "#Work".into()]).unwrap();
+
+        let tagged = s.list_tagged("work", 10, SortOrder::Recent).unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].id, id);
+        let mut tags = tagged[0].tags.clone();
+        tags.sort();
+        assert_eq!(tags, vec!["sql", "work"]);
+
+        let infos = s.list_tags().unwrap();
+        assert_eq!(infos.len(), 2);
+        assert!(infos.iter().all(|t| t.count == 1));
+    }
+
+    #[test]
+    fn stats_aggregate_by_type_and_hour() {
+        let s = Storage::open_in_memory().unwrap();
+        let hour = chrono::Utc::now().timestamp() / 3600 * 3600;
+        let mut old = make_text_entry("old");
+        old.last_seen = hour - 7200;
+        let old_id = s.insert(&old).unwrap();
+        s.set_pinned(old_id, true).unwrap();
+        let text_id = s.capture(&make_text_entry("hello")).unwrap();
+        s.capture(&make_text_entry("hello")).unwrap();
+        s.record_use(text_id).unwrap();
+        let mut image = Entry::new_image(vec![1, 2, 3, 4]);
+        image.last_seen = hour - 3600;
+        s.capture(&image).unwrap();
+
+        let stats = s.stats(hour - 3600, 5).unwrap();
+        assert_eq!(stats.types.len(), 2);
+        let (images, texts) = (&stats.types[0], &stats.types[1]);
+        assert_eq!(
+            (images.content_type, images.count, images.bytes),
+            (ContentType::Image, 1, 4)
+        );
+        assert_eq!(
+            (texts.content_type, texts.count, texts.bytes),
+            (ContentType::Text, 2, 8)
+        );
+        // The pinned entry is older, but never expires
+        assert_eq!(texts.oldest, hour - 7200);
+        assert!(texts.oldest_expiring.unwrap() >= hour);
+
+        // Both copies of "hello" count, though they're one entry
+        assert_eq!(stats.hourly, vec![(hour - 3600, 1), (hour, 2)]);
+        assert_eq!(stats.top.len(), 1);
+        assert_eq!(stats.top[0].id, text_id);
+    }
+
+    #[test]
+    fn list_page_walks_the_history_by_cursor() {
+        let s = Storage::open_in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        // Pairs copied within the same second
+        let ids: Vec<i64> = (0..5)
+            .map(|i| {
+                let mut e = make_text_entry(&format!("entry {i}"));
+                e.created_at = now - 100 + i / 2;
+                e.last_seen = e.created_at;
+                s.insert(&e).unwrap()
+            })
+            .collect();
+
+        let all = EntryFilter::default();
+        let first = s.list_page(None, 2, &all).unwrap();
+        let page_ids = |page: &Page| page.entries.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(page_ids(&first), vec![ids[4], ids[3]]);
+
+        // A new copy doesn't shift the pages after the first
+        s.insert(&make_text_entry("newer")).unwrap();
+        let second = s.list_page(first.next, 2, &all).unwrap();
+        assert_eq!(page_ids(&second), vec![ids[2], ids[1]]);
+        let last = s.list_page(second.next, 2, &all).unwrap();
+        assert_eq!(page_ids(&last), vec![ids[0]]);
+        assert_eq!(last.next, None);
+
+        let window = EntryFilter {
+            since: Some(now - 99),
+            until: Some(now - 98),
+            ..Default::default()
+        };
+        let page = s.list_page(None, 10, &window).unwrap();
+        assert_eq!(page_ids(&page), vec![ids[3], ids[2]]);
+        let matching = EntryFilter {
+            query: Some("ENTRY".into()),
+            ..Default::default()
+        };
+        let page = s.list_page(second.next, 10, &matching).unwrap();
+        assert_eq!(page_ids(&page), vec![ids[0]]);
+    }
+
+    #[test]
+    fn list_filtered_combines_criteria() {
+        let s = Storage::open_in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let mut old = make_text_entry("old pinned");
+        old.created_at = now - 10 * 86_400;
+        old.last_seen = old.created_at;
+        let old_id = s.insert(&old).unwrap();
+        s.set_pinned(old_id, true).unwrap();
+        let link_id = s.insert(&make_text_entry("https://example.com")).unwrap();
+        s.add_tags(link_id, &["work".into()]).unwrap();
+        let pinned_id = s.insert(&make_text_entry("new pinned")).unwrap();
+        s.set_pinned(pinned_id, true).unwrap();
+
+        let ids = |filter: EntryFilter| -> Vec<i64> {
+            s.list_filtered(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.id)
+                .collect()
+        };
+        assert_eq!(ids(EntryFilter::default()).len(), 3);
+        let recent_pinned = EntryFilter {
+            since: Some(now - 86_400),
+            pinned: true,
+            ..Default::default()
+        };
+        assert_eq!(ids(recent_pinned), vec![pinned_id]);
+        let links = EntryFilter {
+            content_type: Some(ContentType::Link),
+            ..Default::default()
+        };
+        assert_eq!(ids(links), vec![link_id]);
+        let tagged = EntryFilter {
+            tag: Some("#Work".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(tagged), vec![link_id]);
+        let old = EntryFilter {
+            until: Some(now - 86_400),
+            ..Default::default()
+        };
+        assert_eq!(ids(old), vec![old_id]);
+        let matching = EntryFilter {
+            query: Some("PINNED".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(matching), vec![pinned_id, old_id]);
+    }
+
+    #[test]
+    fn search_takes_wildcards_literally() {
+        let s = Storage::open_in_memory().unwrap();
+        let percent = s.insert(&make_text_entry("50% off")).unwrap();
+        s.insert(&make_text_entry("500 off")).unwrap();
+        let underscore = s.insert(&make_text_entry("snake_case")).unwrap();
+        s.insert(&make_text_entry("snakeXcase")).unwrap();
+        let umlaut = s.insert(&make_text_entry("Ärger")).unwrap();
+
+        let ids = |query: &str| -> Vec<i64> {
+            s.search(query, 10)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.id)
+                .collect()
+        };
+        assert_eq!(ids("0%"), vec![percent]);
+        assert_eq!(ids("e_c"), vec![underscore]);
+        assert_eq!(ids("ärg"), vec![umlaut]);
+
+        let filtered = |query: &str| -> Vec<i64> {
+            let filter = EntryFilter {
+                query: Some(query.into()),
+                ..Default::default()
+            };
+            s.list_filtered(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.id)
+                .collect()
+        };
+        assert_eq!(filtered("0%"), vec![percent]);
+        assert_eq!(filtered("e_c"), vec![underscore]);
+        assert_eq!(filtered("ärg"), vec![umlaut]);
+    }
+
+    #[test]
+    fn delete_ids_is_one_batch() {
+        let mut s = Storage::open_in_memory().unwrap();
+        s.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        let a = s.insert(&make_text_entry("a")).unwrap();
+        let b = s.insert(&make_text_entry("b")).unwrap();
+
+        assert!(matches!(
+            s.delete_ids(&[a, 999]),
+            Err(StickyError::NotFound(999))
+        ));
+        assert_eq!(s.count().unwrap(), 2);
+        s.delete_ids(&[a, b]).unwrap();
+        assert_eq!(s.count().unwrap(), 0);
+        let mut restored = s.undo().unwrap();
+        restored.sort_unstable();
+        assert_eq!(restored, vec![a, b]);
+    }
+
+    #[test]
+    fn delete_where_spares_pinned() {
+        let (s, dir) = temp_storage("delete-where");
+        let big = format!("needle {}", "x".repeat(BLOB_THRESHOLD_BYTES));
+        let in_blob = s.insert(&make_text_entry(&big)).unwrap();
+        let pinned = s.insert(&make_text_entry("pinned needle")).unwrap();
+        s.set_pinned(pinned, true).unwrap();
+        let other = s.insert(&make_text_entry("haystack")).unwrap();
+
+        let needle = EntryFilter {
+            query: Some("Needle".into()),
+            ..Default::default()
+        };
+        assert_eq!(s.delete_where(&needle).unwrap(), 1);
+        assert!(s.get_by_id(in_blob).is_err());
+        assert!(blob_files(&dir).is_empty());
+        assert_eq!(s.count().unwrap(), 2);
+
+        s.delete(other).unwrap();
+        assert!(matches!(s.delete(other), Err(StickyError::NotFound(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn untag_prunes_empty_tags() {
+        let s = Storage::open_in_memory().unwrap();
+        let id = s.insert(&make_text_entry("x")).unwrap();
+        s.add_tags(id, &["replies".into()]).unwrap();
+        s.remove_tags(id, &["replies".into()]).unwrap();
+        assert!(s.get_by_id(id).unwrap().tags.is_empty());
+        assert!(s.list_tags().unwrap().is_empty());
+    }
+
+    #[test]
+    fn add_tags_rejects_invalid_names() {
+        let s = Storage::open_in_memory().unwrap();
+        let id = s.insert(&make_text_entry("x")).unwrap();
+        assert!(matches!(
+            s.add_tags(id, &["no spaces".into()]),
+            Err(StickyError::InvalidTag(_))
+        ));
+        assert!(matches!(
+            s.add_tags(99, &["ok".into()]),
+            Err(StickyError::NotFound(99))
+        ));
+    }
+
+    #[test]
+    fn kept_tag_survives_cleanup() {
+        let s = Storage::open_in_memory().unwrap();
+        let expired = chrono::Utc::now().timestamp() - (RETENTION_HOURS * 3600) - 100;
+        let mut kept = make_text_entry("kept");
+        kept.created_at = expired;
+        kept.last_seen = kept.created_at;
+        let kept_id = s.insert(&kept).unwrap();
+        let mut tagged = make_text_entry("tagged");
+        tagged.created_at = expired;
+        tagged.last_seen = tagged.created_at;
+        let tagged_id = s.insert(&tagged).unwrap();
+
+        s.add_tags(kept_id, &["work".into()]).unwrap();
+        s.add_tags(tagged_id, &["misc".into()]).unwrap();
+        s.set_tag_keep("work", true).unwrap();
+        assert!(matches!(
+            s.set_tag_keep("Nope", true),
+            Err(StickyError::TagNotFound(name)) if name == "nope"
+        ));
+
+        let report = s.cleanup_old(&RetentionConfig::default()).unwrap();
+        assert_eq!(report.expired, 1);
+        assert!(s.get_by_id(kept_id).is_ok());
+        let names: Vec<_> = s.list_tags().unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["work"]);
+    }
+
+    #[test]
+    fn find_by_hash() {
+        let s = Storage::open_in_memory().unwrap();
+        let entry = make_text_entry("needle");
+        assert!(s.find_by_hash(&entry.hash).unwrap().is_none());
+        let id = s.insert(&entry).unwrap();
+        assert_eq!(s.find_by_hash(&entry.hash).unwrap(), Some(id));
+    }
+
+    #[test]
+    fn record_use_counts() {
+        let s = Storage::open_in_memory().unwrap();
+        let id = s.insert(&make_text_entry("x")).unwrap();
+        s.record_use(id).unwrap();
+        s.record_use(id).unwrap();
+        let e = s.get_by_id(id).unwrap();
+        assert_eq!(e.use_count, 2);
+        assert!(e.last_used.is_some());
+        assert!(matches!(s.record_use(99), Err(StickyError::NotFound(99))));
+    }
+
+    #[test]
+    fn frecency_ranks_reused_entries_first() {
+        let s = Storage::open_in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let mut reused = make_text_entry("reused");
+        reused.created_at = now - 3600;
+        reused.last_seen = reused.created_at;
+        let reused_id = s.insert(&reused).unwrap();
+        s.insert(&make_text_entry("fresh")).unwrap();
+        for _ in 0..3 {
+            s.record_use(reused_id).unwrap();
+        }
+
+        let recent = s.list_sorted(10, SortOrder::Recent).unwrap();
+        assert_eq!(recent[0].preview, "fresh");
+        let frecent = s.list_sorted(10, SortOrder::Frecency).unwrap();
+        assert_eq!(frecent[0].id, reused_id);
+    }
+
+    #[test]
+    fn frecency_decays_with_age() {
+        let s = Storage::open_in_memory().unwrap();
+        let old_id = s.insert(&make_text_entry("old favourite")).unwrap();
+        s.insert(&make_text_entry("new")).unwrap();
+        s.record_use(old_id).unwrap();
+        // Used twice, but a month ago
+        s.conn
+            .execute(
+                "UPDATE entries SET use_count = 2, last_used = unixepoch() - 40 * 86400
+                 WHERE id = ?1",
+                [old_id],
+            )
+            .unwrap();
+
+        let frecent = s.list_sorted(10, SortOrder::Frecency).unwrap();
+        assert_eq!(frecent[0].preview, "new");
+    }
+
+    fn unlocked_in_memory() -> Storage {
+        let mut s = Storage::open_in_memory().unwrap();
+        s.unlock(Cipher::new(&crypto::generate_key())).unwrap();
+        s
+    }
+
+    fn raw_column(s: &Storage, id: i64, column: &str) -> Vec<u8> {
+        s.conn
+            .query_row(
+                &format!("SELECT CAST({column} AS BLOB) FROM entries WHERE id = ?1"),
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn encrypted_roundtrip() {
+        let s = unlocked_in_memory();
+        let text = make_text_entry("hunter2");
+        let text_id = s.insert(&text).unwrap();
+        let png = vec![0x89, b'P', b'N', b'G', 1, 2, 3];
+        let image_id = s.insert(&Entry::new_image(png.clone())).unwrap();
+
+        let got = s.get_by_id(text_id).unwrap();
+        assert_eq!(got.content.as_deref(), Some("hunter2"));
+        assert_eq!(got.hash, text.hash);
+        assert_eq!(s.get_by_id(image_id).unwrap().image_data, Some(png.clone()));
+
+        let raw = raw_column(&s, text_id, "content");
+        assert!(!raw.windows(7).any(|w| w == b"hunter2"));
+        assert_ne!(raw_column(&s, image_id, "image_data"), png);
+        assert_ne!(raw_column(&s, text_id, "hash"), text.hash.as_bytes());
+    }
+
+    #[test]
+    fn encrypted_dedup_and_search() {
+        let s = unlocked_in_memory();
+        let id = s.insert(&make_text_entry("Secret Token")).unwrap();
+        assert_eq!(s.insert(&make_text_entry("Secret Token")).unwrap(), id);
+        s.insert(&make_text_entry("other")).unwrap();
+
+        let found = s.search("token", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, id);
+        assert_eq!(found[0].preview, "Secret Token");
+        let hash = s.get_by_id(id).unwrap().hash;
+        assert_eq!(s.find_by_hash(&hash).unwrap(), Some(id));
+        assert_eq!(
+            s.get_latest_hash().unwrap(),
+            Some(make_text_entry("other").hash)
+        );
+    }
+
+    #[test]
+    fn unlock_rejects_wrong_key() {
+        let mut s = unlocked_in_memory();
+        let wrong = Cipher::new(&crypto::generate_key());
+        assert!(matches!(s.unlock(wrong), Err(StickyError::Crypto(_))));
+    }
+
+    #[test]
+    fn unlock_encrypts_existing_rows() {
+        let mut s = Storage::open_in_memory().unwrap();
+        let id = s
+            .insert(&make_text_entry("written before encryption"))
+            .unwrap();
+        assert!(!s.has_key().unwrap());
+
+        s.unlock(Cipher::new(&crypto::generate_key())).unwrap();
+        assert!(s.has_key().unwrap());
+        assert_ne!(raw_column(&s, id, "content"), b"written before encryption");
+        assert_eq!(
+            s.get_by_id(id).unwrap().content.as_deref(),
+            Some("written before encryption")
+        );
+    }
+
+    #[test]
+    fn unlock_encrypts_registers_revisions_and_trash() {
+        let mut s = Storage::open_in_memory().unwrap();
+        s.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        s.set_register('a', &make_text_entry("register text"))
+            .unwrap();
+        let id = s.insert(&make_text_entry("first draft")).unwrap();
+        s.edit(id, "second draft").unwrap();
+        let trashed = s.insert(&make_text_entry("thrown away")).unwrap();
+        s.delete(trashed).unwrap();
+
+        let key = crypto::generate_key();
+        s.unlock(Cipher::new(&key)).unwrap();
+        let raw = |sql: &str| -> Vec<u8> { s.conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_ne!(
+            raw("SELECT CAST(content AS BLOB) FROM registers WHERE name = 'a'"),
+            b"register text"
+        );
+        assert_ne!(
+            raw("SELECT CAST(content AS BLOB) FROM revisions"),
+            b"first draft"
+        );
+        assert_ne!(
+            raw("SELECT CAST(content AS BLOB) FROM trash"),
+            b"thrown away"
+        );
+        assert_eq!(
+            s.get_register('a').unwrap().content.as_deref(),
+            Some("register text")
+        );
+        assert_eq!(s.list_revisions(id).unwrap()[0].content, "first draft");
+        assert_eq!(s.undo().unwrap(), vec![trashed]);
+        assert_eq!(
+            s.get_by_id(trashed).unwrap().content.as_deref(),
+            Some("thrown away")
+        );
+
+        // And decrypted again once encryption is off
+        let mut s = Storage { cipher: None, ..s };
+        s.decrypt(Cipher::new(&key)).unwrap();
+        assert!(!s.has_encrypted().unwrap());
+        assert_eq!(
+            s.get_register('a').unwrap().content.as_deref(),
+            Some("register text")
+        );
+    }
+
+    #[test]
+    fn integrity_check_finds_nothing_in_a_sound_database() {
+        let (s, dir) = temp_storage("integrity");
+        s.insert(&make_text_entry("fine")).unwrap();
+        let path = dir.join("history.db");
+        assert!(Storage::integrity_check(&path).unwrap().is_empty());
+        assert!(Storage::integrity_check(&dir.join("missing.db")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn decrypt_stores_plaintext_again() {
+        let key = crypto::generate_key();
+        let mut s = Storage::open_in_memory().unwrap();
+        s.unlock(Cipher::new(&key)).unwrap();
+        let id = s.insert(&make_text_entry("no longer secret")).unwrap();
+        assert!(s.has_encrypted().unwrap());
+
+        // Encryption turned off: the database is opened without a key
+        let mut s = Storage { cipher: None, ..s };
+        assert!(matches!(s.get_by_id(id), Err(StickyError::Locked)));
+        assert!(s.decrypt(Cipher::new(&crypto::generate_key())).is_err());
+        assert_eq!(s.decrypt(Cipher::new(&key)).unwrap(), 1);
+
+        assert!(!s.is_encrypted());
+        assert!(!s.has_encrypted().unwrap());
+        assert_eq!(raw_column(&s, id, "content"), b"no longer secret");
+        let entry = s.get_by_id(id).unwrap();
+        assert_eq!(entry.hash, hash_content(b"no longer secret"));
+        // New entries stay plaintext
+        let other = s.insert(&make_text_entry("plain")).unwrap();
+        assert_eq!(raw_column(&s, other, "content"), b"plain");
+    }
+
+    #[test]
+    fn locked_database_refuses_reads() {
+        let path = std::env::temp_dir().join(format!("syo-test-locked-{}.db", std::process::id()));
+        let key = crypto::generate_key();
+        {
+            let mut s = Storage::open_at(&path).unwrap();
+            s.unlock(Cipher::new(&key)).unwrap();
+            s.insert(&make_text_entry("secret")).unwrap();
+        }
+
+        let s = Storage::open_at(&path).unwrap();
+        assert!(matches!(s.list(10), Err(StickyError::Locked)));
+        assert_eq!(s.kdf_salt().unwrap(), s.kdf_salt().unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn temp_storage(name: &str) -> (Storage, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("syo-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        (Storage::open_at(&dir.join("history.db")).unwrap(), dir)
+    }
+
+    fn blob_files(dir: &Path) -> Vec<std::path::PathBuf> {
+        let Ok(shards) = fs::read_dir(dir.join("blobs")) else {
+            return Vec::new();
+        };
+        shards
+            .flat_map(|shard| fs::read_dir(shard.unwrap().path()).unwrap())
+            .map(|blob| blob.unwrap().path())
+            .collect()
+    }
+
+    #[test]
+    fn images_and_large_text_go_to_blobs() {
+        let (s, dir) = temp_storage("blobs");
+        let png = vec![0x89, b'P', b'N', b'G', 7, 7, 7];
+        let image_id = s.insert(&Entry::new_image(png.clone())).unwrap();
+        let big = format!("needle {}", "x".repeat(BLOB_THRESHOLD_BYTES));
+        let big_id = s.insert(&make_text_entry(&big)).unwrap();
+        let small_id = s.insert(&make_text_entry("small")).unwrap();
+
+        let in_blob = |id: i64| -> bool {
+            s.conn
+                .query_row(
+                    "SELECT content IS NULL AND image_data IS NULL AND blob_ref IS NOT NULL
+                     FROM entries WHERE id = ?1",
+                    [id],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+        assert!(in_blob(image_id) && in_blob(big_id) && !in_blob(small_id));
+        assert_eq!(raw_column(&s, small_id, "content"), b"small");
+        assert_eq!(blob_files(&dir).len(), 2);
+
+        assert_eq!(s.get_by_id(image_id).unwrap().image_data, Some(png));
+        let found = s.search("NEEDLE", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, big_id);
+        assert_eq!(found[0].preview.chars().count(), PREVIEW_CHARS);
+        assert!(found[0].preview.starts_with("needle x"));
+        let full = s.get_by_id(big_id).unwrap();
+        assert_eq!(full.content.as_deref(), Some(big.as_str()));
+        assert_eq!(s.find_by_hash(&full.hash).unwrap(), Some(big_id));
+
+        assert_eq!(s.clear().unwrap(), 3);
+        assert!(blob_files(&dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn listing_never_reads_image_blobs() {
+        let (s, dir) = temp_storage("summaries");
+        let png = vec![0x89, b'P', b'N', b'G', 9, 9, 9, 9];
+        let id = s.insert(&Entry::new_image(png)).unwrap();
+        for blob in blob_files(&dir) {
+            fs::remove_file(blob).unwrap();
+        }
+
+        let listed = s.list(10).unwrap();
+        assert_eq!(listed[0].id, id);
+        assert_eq!(listed[0].display_preview(80), "[Image: 8 bytes]");
+        assert!(matches!(s.get_by_id(id), Err(StickyError::Blob(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn summary_preview_matches_preview_chars() {
+        let s = unlocked_in_memory();
+        let long = "ü".repeat(PREVIEW_CHARS * 2);
+        s.insert(&make_text_entry(&long)).unwrap();
+        let plain = Storage::open_in_memory().unwrap();
+        plain.insert(&make_text_entry(&long)).unwrap();
+
+        for storage in [s, plain] {
+            let summary = storage.list(1).unwrap().remove(0);
+            assert_eq!(summary.preview.chars().count(), PREVIEW_CHARS);
+            assert_eq!(summary.size, long.len() as i64);
+        }
+    }
+
+    #[test]
+    fn cleanup_collects_orphan_blobs() {
+        let (s, dir) = temp_storage("blob-gc");
+        let mut image = Entry::new_image(vec![0x89, b'P', b'N', b'G', 1]);
+        image.created_at = chrono::Utc::now().timestamp() - (RETENTION_HOURS * 3600) - 100;
+        image.last_seen = image.created_at;
+        s.insert(&image).unwrap();
+
+        // Freshly written blobs get a grace period
+        let report = s.cleanup_old(&RetentionConfig::default()).unwrap();
+        assert_eq!((report.expired, report.orphan_blobs), (1, 0));
+
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        for blob in blob_files(&dir) {
+            fs::File::options()
+                .write(true)
+                .open(blob)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+        let report = s.cleanup_old(&RetentionConfig::default()).unwrap();
+        assert_eq!(report.orphan_blobs, 1);
+        assert!(blob_files(&dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Whether any of the database files (main, WAL, shm) contain `needle`
+    fn on_disk(dir: &Path, needle: &str) -> bool {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|f| f.unwrap().path())
+            .filter(|p| p.is_file())
+            .any(|p| {
+                fs::read(p)
+                    .unwrap()
+                    .windows(needle.len())
+                    .any(|w| w == needle.as_bytes())
+            })
+    }
+
+    #[test]
+    fn deleted_content_is_scrubbed_from_disk() {
+        let (s, dir) = temp_storage("scrub");
+        let secret = "hunter2-correct-horse-battery";
+        let cleared = "cleared-password-staple-4213";
+        let id = s.insert(&make_text_entry(secret)).unwrap();
+        s.insert(&make_text_entry(cleared)).unwrap();
+        s.insert(&make_text_entry("pinned")).unwrap();
+        s.set_pinned(3, true).unwrap();
+        // Copies in both the main file and the WAL
+        s.checkpoint().unwrap();
+        s.record_use(id).unwrap();
+        assert!(on_disk(&dir, secret));
+
+        s.delete(id).unwrap();
+        assert!(!on_disk(&dir, secret));
+        assert!(on_disk(&dir, cleared));
+
+        s.clear().unwrap();
+        assert!(!on_disk(&dir, cleared));
+        assert!(on_disk(&dir, "pinned"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn default_clear_is_scrubbed_from_disk() {
+        let (mut s, dir) = temp_storage("default-clear");
+        s.set_trash(Config::default().trash);
+        let secret = "default-config-secret-8841";
+        s.insert(&make_text_entry(secret)).unwrap();
+        s.checkpoint().unwrap();
+        s.record_use(1).unwrap();
+        assert!(on_disk(&dir, secret));
+
+        s.clear().unwrap();
+        assert!(!on_disk(&dir, secret));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn edit_keeps_revisions() {
+        let (s, dir) = temp_storage("edit");
+        let big = "z".repeat(BLOB_THRESHOLD_BYTES + 1);
+        let id = s.insert(&make_text_entry(&big)).unwrap();
+        s.add_tags(id, &["work".into()]).unwrap();
+
+        assert_eq!(s.edit(id, "https://example.com").unwrap(), id);
+        let edited = s.get_by_id(id).unwrap();
+        assert_eq!(edited.content.as_deref(), Some("https://example.com"));
+        assert_eq!(edited.content_type, ContentType::Link);
+        assert_eq!(edited.tags, vec!["work"]);
+        assert_eq!(s.find_by_hash(&edited.hash).unwrap(), Some(id));
+        assert_eq!(blob_files(&dir).len(), 1, "the revision keeps its blob");
+
+        s.edit(id, "short").unwrap();
+        let revisions = s.list_revisions(id).unwrap();
+        let contents: Vec<&str> = revisions.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, vec![big.as_str(), "https://example.com"]);
+
+        let other = s.insert(&make_text_entry("other")).unwrap();
+        assert_eq!(s.edit(id, "other").unwrap(), other);
+        assert!(s.get_by_id(id).is_err());
+        assert_eq!(s.get_by_id(other).unwrap().tags, vec!["work"]);
+        assert_eq!(s.list_revisions(other).unwrap().len(), 3);
+
+        let image = s.insert(&Entry::new_image(vec![1, 2, 3])).unwrap();
+        assert!(matches!(s.edit(image, "x"), Err(StickyError::NotText(_))));
+
+        // Replacing keeps no revision, and drops the blob it replaced
+        let collected = s.insert(&make_text_entry(&big)).unwrap();
+        assert_eq!(s.replace(collected, "a,b").unwrap(), collected);
+        assert!(s.list_revisions(collected).unwrap().is_empty());
+        assert_eq!(blob_files(&dir).len(), 2);
+
+        s.clear().unwrap();
+        assert!(blob_files(&dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn trash_undo_and_restore() {
+        let (mut s, dir) = temp_storage("trash");
+        s.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        let big = "x".repeat(BLOB_THRESHOLD_BYTES + 1);
+        let mut tagged = make_text_entry(&big);
+        tagged.tags = vec!["work".into()];
+        let a = s.insert(&tagged).unwrap();
+        let b = s.insert(&make_text_entry("b")).unwrap();
+        let pinned = s.insert(&make_text_entry("pinned")).unwrap();
+        s.set_pinned(pinned, true).unwrap();
+
+        s.delete(a).unwrap();
+        assert_eq!(s.clear().unwrap(), 1);
+        assert_eq!(s.count().unwrap(), 1);
+        let trashed: Vec<i64> = s.list_trash().unwrap().iter().map(|t| t.entry.id).collect();
+        assert_eq!(trashed, vec![b, a]);
+        assert_eq!(blob_files(&dir).len(), 1, "blobs stay while trashed");
+        assert!(s.list_tags().unwrap().is_empty());
+
+        // A new entry doesn't take a trashed entry's id
+        let c = s.insert(&make_text_entry("c")).unwrap();
+        assert!(c > b);
+
+        assert_eq!(s.undo().unwrap(), vec![b]);
+        assert_eq!(s.restore(a).unwrap(), a);
+        assert_eq!(
+            s.get_by_id(a).unwrap().content.as_deref(),
+            Some(big.as_str())
+        );
+        assert_eq!(s.get_by_id(a).unwrap().tags, vec!["work"]);
+        assert!(s.list_trash().unwrap().is_empty());
+        assert!(matches!(s.restore(a), Err(StickyError::NotInTrash(_))));
+        assert!(s.undo().unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn revisions_survive_the_trash_until_purged() {
+        let mut s = Storage::open_in_memory().unwrap();
+        s.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        let id = s.insert(&make_text_entry("draft")).unwrap();
+        s.edit(id, "final").unwrap();
+
+        s.delete(id).unwrap();
+        assert_eq!(s.undo().unwrap(), vec![id]);
+        assert_eq!(s.list_revisions(id).unwrap()[0].content, "draft");
+
+        s.delete(id).unwrap();
+        s.purge_trash(true).unwrap();
+        let left: i64 = s
+            .conn
+            .query_row("SELECT COUNT(*) FROM revisions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+
+    #[test]
+    fn revisions_lose_their_cascade() {
+        let path = std::env::temp_dir().join(format!("syo-test-revs-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "PRAGMA foreign_keys = OFF;
+                CREATE TABLE revisions (
+                    id INTEGER PRIMARY KEY,
+                    entry_id INTEGER NOT NULL REFERENCES entries(id) ON DELETE CASCADE,
+                    content_type TEXT NOT NULL,
+                    content TEXT,
+                    blob_ref TEXT,
+                    encrypted INTEGER NOT NULL,
+                    size INTEGER NOT NULL,
+                    replaced_at INTEGER NOT NULL
+                );
+                INSERT INTO revisions VALUES (1, 7, 'text', 'old', NULL, 0, 3, 0);",
+            )
+            .unwrap();
+        }
+
+        let s = Storage::open_at(&path).unwrap();
+        let cascades: bool = s
+            .conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM pragma_foreign_key_list('revisions'))",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!cascades);
+        let content: String = s
+            .conn
+            .query_row(
+                "SELECT content FROM revisions WHERE entry_id = 7",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(content, "old");
+        drop(s);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    #[test]
+    fn restore_merges_recopied_content() {
+        let mut s = Storage::open_in_memory().unwrap();
+        s.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        let old = s.insert(&make_text_entry("again")).unwrap();
+        s.delete(old).unwrap();
+        let new = s.insert(&make_text_entry("again")).unwrap();
+        assert_ne!(old, new);
+
+        assert_eq!(s.restore(old).unwrap(), new);
+        assert_eq!(s.count().unwrap(), 1);
+        assert_eq!(s.get_by_id(new).unwrap().copy_count, 2);
+    }
+
+    #[test]
+    fn trash_is_purged_after_grace() {
+        let (mut s, dir) = temp_storage("trash-purge");
+        s.set_trash(TrashConfig {
+            grace: Age(3600),
+            expired: true,
+        });
+        let secret = "purged-secret-trombone-5521";
+        let mut old = make_text_entry(secret);
+        old.last_seen = chrono::Utc::now().timestamp() - RETENTION_HOURS * 3600 - 100;
+        let big = "y".repeat(BLOB_THRESHOLD_BYTES + 1);
+        let kept = s.insert(&make_text_entry(&big)).unwrap();
+        let id = s.insert(&old).unwrap();
+
+        let report = s.cleanup_old(&RetentionConfig::default()).unwrap();
+        assert_eq!((report.expired, report.purged), (1, 0));
+        assert_eq!(s.list_trash().unwrap()[0].entry.id, id);
+
+        s.delete(kept).unwrap();
+        s.conn
+            .execute(
+                "UPDATE trash SET deleted_at = deleted_at - 7200 WHERE id = ?1",
+                [id],
+            )
+            .unwrap();
+        let report = s.cleanup_old(&RetentionConfig::default()).unwrap();
+        assert_eq!(report.purged, 1);
+        assert!(!on_disk(&dir, secret));
+        assert_eq!(blob_files(&dir).len(), 1);
+
+        assert_eq!(s.purge_trash(true).unwrap(), 1);
+        assert!(blob_files(&dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shred_skips_trash() {
+        let mut s = Storage::open_in_memory().unwrap();
+        s.set_trash(TrashConfig {
+            grace: Age(86_400),
+            expired: false,
+        });
+        s.set_shred(true).unwrap();
+        s.insert(&make_text_entry("gone")).unwrap();
+        assert_eq!(s.clear().unwrap(), 1);
+        assert!(s.list_trash().unwrap().is_empty());
+    }
+
+    #[test]
+    fn registers_survive_clear_and_keep_their_blobs() {
+        let (s, dir) = temp_storage("registers");
+        let image = Entry::new_image(vec![7; 64]);
+        let id = s.insert(&image).unwrap();
+        s.set_register('q', &s.get_by_id(id).unwrap()).unwrap();
+        s.set_register('w', &make_text_entry("word")).unwrap();
+        s.clear_all().unwrap();
+        s.cleanup_old(&RetentionConfig::default()).unwrap();
+
+        let stored = s.get_register('Q').unwrap();
+        assert_eq!(stored.image_data, image.image_data);
+        assert_eq!(blob_files(&dir).len(), 1);
+        let names: Vec<char> = s.list_registers().unwrap().iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!['q', 'w']);
+
+        s.set_register('q', &make_text_entry("text now")).unwrap();
+        assert!(blob_files(&dir).is_empty(), "replaced blob is removed");
+        s.clear_register('w').unwrap();
+        assert!(matches!(
+            s.clear_register('w'),
+            Err(StickyError::EmptyRegister('w'))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shred_overwrites_blobs() {
+        let (mut s, dir) = temp_storage("shred");
+        s.insert(&make_text_entry(&"secret ".repeat(20_000)))
+            .unwrap();
+        // A second name for the blob's data, to look at it after the delete
+        let link = dir.join("link");
+        fs::hard_link(&blob_files(&dir)[0], &link).unwrap();
+
+        s.set_shred(true).unwrap();
+        s.clear().unwrap();
+        assert!(blob_files(&dir).is_empty());
+        let data = fs::read(&link).unwrap();
+        assert!(!data.is_empty() && data.iter().all(|&b| b == 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unlock_reencrypts_and_moves_payloads() {
+        let (mut s, dir) = temp_storage("blob-crypt");
+        let png = b"\x89PNG plaintext pixels".to_vec();
+        let id = s.insert(&Entry::new_image(png.clone())).unwrap();
+        // A row written inline by an older version
+        s.conn
+            .execute(
+                "INSERT INTO entries (content_type, image_data, hash, created_at, last_seen, size)
+                 VALUES ('image', x'89504e4701', 'legacy', 1, 1, 5)",
+                [],
+            )
+            .unwrap();
+
+        s.unlock(Cipher::new(&crypto::generate_key())).unwrap();
+
+        let blobs = blob_files(&dir);
+        assert_eq!(blobs.len(), 2);
+        for blob in blobs {
+            let raw = fs::read(blob).unwrap();
+            assert!(!raw.windows(9).any(|w| w == b"plaintext"));
+        }
+        assert_eq!(s.get_by_id(id).unwrap().image_data, Some(png));
+        assert_eq!(s.count().unwrap(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dedup_by_hash() {
+        let s = Storage::open_in_memory().unwrap();
+        let e1 = make_text_entry("same");
+        let e2 = make_text_entry("same");
+        assert_eq!(e1.hash, e2.hash);
+        let id1 = s.insert(&e1).unwrap();
+        let id2 = s.insert(&e2).unwrap();
+        assert_eq!(id1, id2);
+        assert_eq!(s.count().unwrap(), 1);
+        assert_eq!(s.get_by_id(id1).unwrap().copy_count, 2);
+        assert_eq!(s.get_latest_hash().unwrap().unwrap(), e1.hash);
+    }
+
+    #[test]
+    fn recopy_bumps_to_top() {
+        let s = Storage::open_in_memory().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let mut a = make_text_entry("A");
+        a.created_at = now - 20;
+        a.last_seen = a.created_at;
+        let a_id = s.insert(&a).unwrap();
+        let mut b = make_text_entry("B");
+        b.created_at = now - 10;
+        b.last_seen = b.created_at;
+        s.insert(&b).unwrap();
+
+        s.insert(&make_text_entry("A")).unwrap();
+
+        let entries = s.list(10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, a_id);
+        assert_eq!(entries[0].created_at, now - 20);
+        assert!(entries[0].last_seen >= now);
+        assert_eq!(entries[0].copy_count, 2);
+    }
+
+    #[test]
+    fn migrates_duplicate_rows() {
+        let s = Storage::open_in_memory().unwrap();
+        s.conn
+            .execute_batch(
+                "DROP INDEX idx_hash_unique;
+                 INSERT INTO entries (content_type, content, hash, created_at, last_seen)
+                 VALUES ('text', 'A', 'h', 10, 10), ('text', 'B', 'g', 20, 20),
+                        ('text', 'A', 'h', 30, 30);",
+            )
+            .unwrap();
+        s.add_tags(3, &["work".into()]).unwrap();
+
+        s.init_schema().unwrap();
+
+        assert_eq!(s.count().unwrap(), 2);
+        let a = s.get_by_id(1).unwrap();
+        assert_eq!((a.created_at, a.last_seen, a.copy_count), (10, 30, 2));
+        assert_eq!(a.tags, vec!["work"]);
+        assert_eq!(s.list(1).unwrap()[0].id, 1);
+    }
+
+    /// Several processes writing at once, each running this test with the
+    /// worker env var set. Any busy error surfacing makes a worker fail.
+    #[test]
+    fn concurrent_processes_share_database() {
+        const WORKERS: usize = 4;
+        const ROUNDS: usize = 60;
+
+        if let Ok(db) = std::env::var("SYO_TEST_HAMMER_DB") {
+            let worker = std::env::var("SYO_TEST_HAMMER_WORKER").unwrap();
+            let s = Storage::open_at(Path::new(&db)).unwrap();
+            let policy = RetentionConfig {
+                max_entries: Some(40),
+                ..Default::default()
+            };
+            for i in 0..ROUNDS {
+                let mut entry = match i % 10 {
+                    // Large enough for the blob store
+                    0 => Entry::new_text(format!("{worker}-{i}").repeat(20_000)),
+                    _ => Entry::new_text(format!("{worker}-{i}")),
+                };
+                entry.tags = vec![format!("w{worker}")];
+                let id = s.insert(&entry).unwrap();
+                // Another worker's cleanup may have removed it already
+                for result in [s.record_use(id), s.set_pinned(id, i % 7 == 0)] {
+                    assert!(matches!(result, Ok(()) | Err(StickyError::NotFound(_))));
+                }
+                s.list(20).unwrap();
+                s.cleanup_old(&policy).unwrap();
+                if i % 25 == 24 {
+                    s.clear().unwrap();
+                }
+            }
+            return;
+        }
+
+        let dir = std::env::temp_dir().join(format!("syo-test-hammer-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("history.db");
+
+        // Test names are relative to the crate root
+        let (_, module) = module_path!().split_once("::").unwrap();
+        let name = format!("{module}::concurrent_processes_share_database");
+        let children: Vec<_> = (0..WORKERS)
+            .map(|worker| {
+                std::process::Command::new(std::env::current_exe().unwrap())
+                    .args(["--exact", &name])
+                    .env("SYO_TEST_HAMMER_DB", &db)
+                    .env("SYO_TEST_HAMMER_WORKER", worker.to_string())
+                    .stdout(std::process::Stdio::piped())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        for child in children {
+            let output = child.wait_with_output().unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(output.status.success(), "{stdout}");
+            assert!(stdout.contains("1 passed"), "worker didn't run: {stdout}");
+        }
+
+        let s = Storage::open_at(&db).unwrap();
+        let check: String = s
+            .conn
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(check, "ok");
+        let mode: String = s
+            .conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        // Every surviving entry still has its payload
+        for summary in s.list(100).unwrap() {
+            s.get_by_id(summary.id).unwrap();
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}